@@ -1,4 +1,4 @@
-use std::{path::Path, process};
+use std::{collections::BTreeMap, fmt, path::Path, process, str::FromStr};
 
 use anyhow::Error;
 use firecracker_prepare::Distro;
@@ -6,20 +6,102 @@ use owo_colors::OwoColorize;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct EtcdConfig {
-    pub endpoints: Option<Vec<String>>,
-    pub user: Option<String>,
-    pub password: Option<String>,
-    pub cacert: Option<String>,
-    pub cert: Option<String>,
+pub struct TailscaleOptions {
+    pub auth_key: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TailscaleOptions {
-    pub auth_key: Option<String>,
+pub struct VolumeMount {
+    pub name: String,
+    pub mount: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FirewallOptions {
+    /// `allow` (the default) or `deny` outgoing traffic not listed in `allow`.
+    pub egress: Option<String>,
+    /// Destinations reachable when egress is denied, e.g. `10.0.0.0/8:5432`
+    /// or `github.com:443`.
+    pub allow: Option<Vec<String>>,
+    /// Block the traffic with the other MicroVMs of the bridge.
+    pub isolate: Option<bool>,
+}
+
+/// Network interface of a MicroVM, the first one is `eth0`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NicOptions {
+    /// Network to attach the interface to, the default network if unset.
+    pub network: Option<String>,
+    pub mac: Option<String>,
+}
+
+/// Rate limits of a MicroVM, bandwidths are in megabits per second.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LimitsOptions {
+    pub net_rx_mbps: Option<u32>,
+    pub net_tx_mbps: Option<u32>,
+    /// Operations per second of each drive.
+    pub disk_iops: Option<u32>,
+    pub disk_mbps: Option<u32>,
+}
+
+/// File copied into the guest when provisioning it, `src` is relative to
+/// the directory of `fire.toml`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProvisionFile {
+    pub src: String,
+    pub dst: String,
+}
+
+/// Provisioning of a MicroVM once its network is up: the `files` are copied,
+/// then the `inline` commands and the `scripts` are run in the guest.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProvisionOptions {
+    pub inline: Option<Vec<String>>,
+    pub scripts: Option<Vec<String>>,
+    pub files: Option<Vec<ProvisionFile>>,
+}
+
+/// Whether `fireup serve` starts a MicroVM again once its Firecracker process
+/// is gone.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    #[default]
+    No,
+    /// When Firecracker crashed, exited with a non-zero code or the guest
+    /// kernel panicked.
+    OnFailure,
+    Always,
+}
+
+impl FromStr for RestartPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "no" => Ok(RestartPolicy::No),
+            "on-failure" => Ok(RestartPolicy::OnFailure),
+            "always" => Ok(RestartPolicy::Always),
+            _ => Err(anyhow::anyhow!(
+                "Invalid restart policy '{}', expected no, on-failure or always",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for RestartPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestartPolicy::No => write!(f, "no"),
+            RestartPolicy::OnFailure => write!(f, "on-failure"),
+            RestartPolicy::Always => write!(f, "always"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Vm {
     pub vcpu: Option<u16>,
     pub memory: Option<u16>,
@@ -32,13 +114,173 @@ pub struct Vm {
     pub mac: Option<String>,
     pub ssh_keys: Option<Vec<String>>,
     pub tailscale: Option<TailscaleOptions>,
+    pub persistent: Option<bool>,
+    pub disk_size: Option<u32>,
+    pub volumes: Option<Vec<VolumeMount>>,
+    pub jailer: Option<bool>,
+    pub ip: Option<String>,
+    pub network: Option<String>,
+    pub ports: Option<Vec<String>>,
+    pub firewall: Option<FirewallOptions>,
+    pub limits: Option<LimitsOptions>,
+    pub nics: Option<Vec<NicOptions>>,
+    pub restart: Option<RestartPolicy>,
+    /// Distribution of a MicroVM of `vms`, the one of the project if unset.
+    pub distro: Option<Distro>,
+    /// MicroVMs of `vms` started before this one.
+    pub depends_on: Option<Vec<String>>,
+    /// Provisioning of a MicroVM of `vms`, the one of the project if unset.
+    pub provision: Option<ProvisionOptions>,
+    /// Image built with `fireup build`, its distribution replaces `distro`.
+    pub image: Option<String>,
+}
+
+impl Vm {
+    /// Settings of `self`, falling back to the ones of `base`.
+    fn or(self, base: &Vm) -> Vm {
+        let base = base.clone();
+        Vm {
+            vcpu: self.vcpu.or(base.vcpu),
+            memory: self.memory.or(base.memory),
+            vmlinux: self.vmlinux.or(base.vmlinux),
+            rootfs: self.rootfs.or(base.rootfs),
+            boot_args: self.boot_args.or(base.boot_args),
+            bridge: self.bridge.or(base.bridge),
+            tap: self.tap.or(base.tap),
+            api_socket: self.api_socket.or(base.api_socket),
+            mac: self.mac.or(base.mac),
+            ssh_keys: self.ssh_keys.or(base.ssh_keys),
+            tailscale: self.tailscale.or(base.tailscale),
+            persistent: self.persistent.or(base.persistent),
+            disk_size: self.disk_size.or(base.disk_size),
+            volumes: self.volumes.or(base.volumes),
+            jailer: self.jailer.or(base.jailer),
+            ip: self.ip.or(base.ip),
+            network: self.network.or(base.network),
+            ports: self.ports.or(base.ports),
+            firewall: self.firewall.or(base.firewall),
+            limits: self.limits.or(base.limits),
+            nics: self.nics.or(base.nics),
+            restart: self.restart.or(base.restart),
+            distro: self.distro.or(base.distro),
+            depends_on: self.depends_on,
+            provision: self.provision.or(base.provision),
+            image: self.image.or(base.image),
+        }
+    }
+}
+
+/// Network created by `fireup up` for the MicroVMs of a project.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetworkConfig {
+    pub cidr: Option<String>,
+    pub gateway: Option<String>,
+    pub domain: Option<String>,
+    pub nat: Option<bool>,
+    pub ipv6: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FireConfig {
+    /// Distribution of the MicroVM, the one of `image` when it is set.
+    #[serde(default = "default_distro")]
     pub distro: Distro,
+    /// Settings of the MicroVM, shared by all the MicroVMs of `vms` when set.
+    #[serde(default)]
     pub vm: Vm,
-    pub etcd: Option<EtcdConfig>,
+    /// Name of the project, the name of its directory if unset.
+    pub project: Option<String>,
+    /// MicroVMs of a multi-VM project, by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vms: BTreeMap<String, Vm>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub networks: BTreeMap<String, NetworkConfig>,
+    /// Provisioning of the MicroVM, or of all the MicroVMs of `vms`.
+    pub provision: Option<ProvisionOptions>,
+}
+
+/// A MicroVM of a multi-VM project, its settings applied over the shared ones.
+#[derive(Debug)]
+pub struct Service {
+    pub name: String,
+    pub config: FireConfig,
+}
+
+impl FireConfig {
+    /// The MicroVMs of `vms`, each one after the ones it depends on.
+    pub fn services(&self) -> Result<Vec<Service>, Error> {
+        // what tells the MicroVMs apart cannot be shared between them
+        let shared = [
+            ("tap", self.vm.tap.is_some()),
+            ("api_socket", self.vm.api_socket.is_some()),
+            ("mac", self.vm.mac.is_some()),
+            ("ip", self.vm.ip.is_some()),
+            ("ports", self.vm.ports.is_some()),
+        ];
+        if let Some((key, _)) = shared.iter().find(|(_, set)| *set && !self.vms.is_empty()) {
+            return Err(anyhow::anyhow!(
+                "`{}` cannot be set in [vm] when the project has [vms], set it in [vms.<name>]",
+                key
+            ));
+        }
+
+        let mut ordered: Vec<&str> = vec![];
+        let mut visiting: Vec<&str> = vec![];
+        for name in self.vms.keys() {
+            self.visit(name, &mut visiting, &mut ordered)?;
+        }
+        Ok(ordered
+            .into_iter()
+            .map(|name| {
+                let mut vm = self.vms[name].clone().or(&self.vm);
+                vm.provision = vm.provision.or_else(|| self.provision.clone());
+                Service {
+                    name: name.into(),
+                    config: FireConfig {
+                        distro: vm.distro.unwrap_or(self.distro),
+                        vm,
+                        project: self.project.clone(),
+                        vms: BTreeMap::new(),
+                        networks: BTreeMap::new(),
+                        provision: None,
+                    },
+                }
+            })
+            .collect())
+    }
+
+    fn visit<'a>(
+        &'a self,
+        name: &'a str,
+        visiting: &mut Vec<&'a str>,
+        ordered: &mut Vec<&'a str>,
+    ) -> Result<(), Error> {
+        if ordered.contains(&name) {
+            return Ok(());
+        }
+        if visiting.contains(&name) {
+            visiting.push(name);
+            return Err(anyhow::anyhow!(
+                "Circular dependency between MicroVMs: {}",
+                visiting.join(" -> ")
+            ));
+        }
+        let (name, vm) = self
+            .vms
+            .get_key_value(name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown MicroVM '{}' in depends_on", name))?;
+        visiting.push(name);
+        for dependency in vm.depends_on.iter().flatten() {
+            self.visit(dependency, visiting, ordered)?;
+        }
+        visiting.pop();
+        ordered.push(name);
+        Ok(())
+    }
+}
+
+fn default_distro() -> Distro {
+    Distro::Ubuntu
 }
 
 impl Default for FireConfig {
@@ -57,8 +299,26 @@ impl Default for FireConfig {
                 mac: None,
                 ssh_keys: None,
                 tailscale: None,
+                persistent: None,
+                disk_size: None,
+                volumes: None,
+                jailer: None,
+                ip: None,
+                network: None,
+                ports: None,
+                firewall: None,
+                limits: None,
+                nics: None,
+                restart: None,
+                distro: None,
+                depends_on: None,
+                provision: None,
+                image: None,
             },
-            etcd: None,
+            project: None,
+            vms: BTreeMap::new(),
+            networks: BTreeMap::new(),
+            provision: None,
         }
     }
 }
@@ -94,3 +354,80 @@ pub fn read_config() -> Result<FireConfig, Error> {
     let config: FireConfig = toml::from_str(&content)?;
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_services() {
+        let config: FireConfig = toml::from_str(
+            r#"
+            distro = "Ubuntu"
+
+            [vm]
+            memory = 1024
+            network = "backend"
+
+            [vms.worker]
+            depends_on = ["api"]
+
+            [vms.api]
+            vcpu = 2
+            depends_on = ["db"]
+
+            [vms.db]
+            distro = "Debian"
+            memory = 2048
+            "#,
+        )
+        .unwrap();
+        let services = config.services().unwrap();
+        let names = services.iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["db", "api", "worker"]);
+
+        let db = &services[0].config;
+        assert_eq!(db.distro, Distro::Debian);
+        assert_eq!(db.vm.memory, Some(2048));
+        assert_eq!(db.vm.network.as_deref(), Some("backend"));
+        let api = &services[1].config;
+        assert_eq!(api.distro, Distro::Ubuntu);
+        assert_eq!((api.vm.vcpu, api.vm.memory), (Some(2), Some(1024)));
+
+        let cyclic: FireConfig = toml::from_str(
+            r#"
+            distro = "Ubuntu"
+            [vms.a]
+            depends_on = ["b"]
+            [vms.b]
+            depends_on = ["a"]
+            "#,
+        )
+        .unwrap();
+        let error = cyclic.services().unwrap_err().to_string();
+        assert!(error.contains("a -> b -> a"), "{}", error);
+
+        let unknown: FireConfig = toml::from_str(
+            r#"
+            distro = "Ubuntu"
+            [vms.a]
+            depends_on = ["c"]
+            "#,
+        )
+        .unwrap();
+        assert!(unknown.services().is_err());
+
+        let shared_mac: FireConfig = toml::from_str(
+            r#"
+            distro = "Ubuntu"
+            [vm]
+            mac = "02:fc:00:00:00:01"
+            [vms.a]
+            [vms.b]
+            "#,
+        )
+        .unwrap();
+        let error = shared_mac.services().unwrap_err().to_string();
+        assert!(error.contains("`mac`"), "{}", error);
+    }
+}
@@ -1,16 +1,18 @@
 use std::sync::Arc;
 
 use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use chrono::Local;
 use firecracker_state::repo;
+use firecracker_vm::logs::{self, Source};
 use serde::{Deserialize, Serialize};
 use sqlx::{Pool, Sqlite};
-use tokio_stream::StreamExt;
+use tokio_stream::{wrappers::ReceiverStream, StreamExt};
 use utoipa::ToSchema;
 use utoipa_actix_web::service_config::ServiceConfig;
 
 use crate::{
     read_payload, services,
-    types::microvm::{CreateMicroVM, MicroVM},
+    types::microvm::{CreateMicroVM, MicroVM, MicroVMLogs},
 };
 
 const MICRO_VM: &str = "MicroVM";
@@ -47,6 +49,15 @@ async fn create_microvm(
             ssh_keys: None,
             start: None,
             tailscale_auth_key: None,
+            disk_size: None,
+            jailer: None,
+            ip: None,
+            network: None,
+            ports: None,
+            firewall: None,
+            limits: None,
+            nics: None,
+            restart: None,
         },
         false => serde_json::from_slice::<CreateMicroVM>(&body)?,
     };
@@ -192,6 +203,118 @@ async fn stop_microvm(
     Ok(HttpResponse::Ok().json(vm))
 }
 
+#[utoipa::path(
+    tag = MICRO_VM,
+    responses(
+        (status = 200, description = "MicroVM paused successfully"),
+        (status = 404, description = "MicroVM with id not found", body = ErrorResponse, example = json!(ErrorResponse::NotFound(String::from("id = 1"))))
+    ),
+    params(
+        ("id", description = "The ID of the MicroVM to pause")
+    )
+)]
+#[post("/{id}/pause")]
+async fn pause_microvm(
+    id: web::Path<String>,
+    pool: web::Data<Arc<Pool<Sqlite>>>,
+) -> Result<impl Responder, actix_web::Error> {
+    let id = id.into_inner();
+    let pool = pool.get_ref().clone();
+    let vm = services::microvm::pause_microvm(pool, &id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    if vm.is_none() {
+        return Ok(HttpResponse::NotFound().json(ErrorResponse::NotFound(id)));
+    }
+
+    Ok(HttpResponse::Ok().json(vm))
+}
+
+#[utoipa::path(
+    tag = MICRO_VM,
+    responses(
+        (status = 200, description = "MicroVM resumed successfully"),
+        (status = 404, description = "MicroVM with id not found", body = ErrorResponse, example = json!(ErrorResponse::NotFound(String::from("id = 1"))))
+    ),
+    params(
+        ("id", description = "The ID of the MicroVM to resume")
+    )
+)]
+#[post("/{id}/resume")]
+async fn resume_microvm(
+    id: web::Path<String>,
+    pool: web::Data<Arc<Pool<Sqlite>>>,
+) -> Result<impl Responder, actix_web::Error> {
+    let id = id.into_inner();
+    let pool = pool.get_ref().clone();
+    let vm = services::microvm::resume_microvm(pool, &id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    if vm.is_none() {
+        return Ok(HttpResponse::NotFound().json(ErrorResponse::NotFound(id)));
+    }
+
+    Ok(HttpResponse::Ok().json(vm))
+}
+
+#[utoipa::path(
+    tag = MICRO_VM,
+    responses(
+        (status = 200, description = "Logs of the MicroVM, streamed as plain text lines", content_type = "text/plain"),
+        (status = 400, description = "Invalid log source or since value"),
+        (status = 404, description = "MicroVM with id not found", body = ErrorResponse, example = json!(ErrorResponse::NotFound(String::from("id = 1"))))
+    ),
+    params(
+        ("id", description = "The ID of the MicroVM to get the logs of"),
+        MicroVMLogs
+    )
+)]
+#[get("/{id}/logs")]
+async fn get_microvm_logs(
+    id: web::Path<String>,
+    query: web::Query<MicroVMLogs>,
+    pool: web::Data<Arc<Pool<Sqlite>>>,
+) -> Result<impl Responder, actix_web::Error> {
+    let id = id.into_inner();
+    let query = query.into_inner();
+    let sources = match &query.source {
+        Some(source) => vec![source
+            .parse::<Source>()
+            .map_err(actix_web::error::ErrorBadRequest)?],
+        None => Source::ALL.to_vec(),
+    };
+    let since = query
+        .since
+        .as_deref()
+        .map(|since| logs::parse_since(since, Local::now().naive_local()))
+        .transpose()
+        .map_err(actix_web::error::ErrorBadRequest)?;
+
+    let pool = pool.get_ref().clone();
+    let lines = services::microvm::logs(
+        pool,
+        &id,
+        sources,
+        query.follow.unwrap_or(false),
+        since,
+        query.tail,
+    )
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(match lines {
+        Some(lines) => HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .streaming(
+                ReceiverStream::new(lines)
+                    .map(|line| Ok::<_, actix_web::Error>(web::Bytes::from(line))),
+            ),
+        None => HttpResponse::NotFound().json(ErrorResponse::NotFound(id)),
+    })
+}
+
 pub fn configure() -> impl FnOnce(&mut ServiceConfig) {
     |config: &mut ServiceConfig| {
         config
@@ -200,6 +323,9 @@ pub fn configure() -> impl FnOnce(&mut ServiceConfig) {
             .service(get_microvm)
             .service(list_microvms)
             .service(start_microvm)
-            .service(stop_microvm);
+            .service(stop_microvm)
+            .service(pause_microvm)
+            .service(resume_microvm)
+            .service(get_microvm_logs);
     }
 }
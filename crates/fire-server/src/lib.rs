@@ -1,4 +1,5 @@
 pub mod api;
 pub mod server;
 pub mod services;
+pub mod supervisor;
 pub mod types;
@@ -9,7 +9,7 @@ use utoipa_actix_web::AppExt;
 use utoipa_rapidoc::RapiDoc;
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::api::microvm;
+use crate::{api::microvm, supervisor};
 
 #[derive(OpenApi)]
 #[openapi(
@@ -26,6 +26,14 @@ pub async fn run() -> Result<(), Error> {
         run_command("sudo", &["-v"], false)?;
     }
 
+    // when running as root, serve DHCP and DNS from this process instead of
+    // spawning a separate `fireup netd`
+    if is_root() {
+        if let Err(e) = firecracker_vm::netd::start_in_process().await {
+            println!("[!] Failed to start network services in process: {}", e);
+        }
+    }
+
     let port = env::var("FIREUP_PORT").unwrap_or_else(|_| "9090".to_string());
     let host = env::var("FIREUP_HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
     let addr = format!("{}:{}", host, port);
@@ -36,6 +44,8 @@ pub async fn run() -> Result<(), Error> {
     let pool = firecracker_state::create_connection_pool().await?;
     let pool = Arc::new(pool);
 
+    tokio::spawn(supervisor::run(pool.clone()));
+
     HttpServer::new(move || {
         App::new()
             .app_data(Data::new(pool.clone()))
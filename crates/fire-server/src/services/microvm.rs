@@ -1,17 +1,32 @@
-use std::{sync::Arc, thread};
+use std::{sync::Arc, thread, time::Duration};
 
 use crate::types::microvm::CreateMicroVM;
 use anyhow::Error;
-use fire_config::TailscaleOptions;
-use firecracker_state::{entity::virtual_machine::VirtualMachine, repo};
-use firecracker_vm::{constants::BRIDGE_DEV, types::VmOptions};
+use chrono::NaiveDateTime;
+use fire_config::{RestartPolicy, TailscaleOptions};
+use firecracker_state::{
+    entity::{virtual_machine::VirtualMachine, vm_interface::VmInterface},
+    repo,
+};
+use firecracker_vm::{
+    constants::BRIDGE_DEV,
+    firewall, image, limits,
+    logs::{self, Line, Logs, Source},
+    nic, provision,
+    types::VmOptions,
+    volume,
+};
 use owo_colors::OwoColorize;
 use sqlx::{Pool, Sqlite};
+use tokio::sync::mpsc;
 
 pub async fn create_microvm(
     pool: Arc<Pool<Sqlite>>,
     params: CreateMicroVM,
 ) -> Result<VirtualMachine, Error> {
+    if let Some(policy) = &params.restart {
+        policy.parse::<RestartPolicy>()?;
+    }
     let mut options: VmOptions = params.into();
 
     if options.api_socket.is_empty() {
@@ -37,7 +52,7 @@ pub async fn delete_microvm(
 
     let mut vm = vm.unwrap();
     firecracker_process::stop(Some(vm.name.clone())).await?;
-    repo::virtual_machine::delete(&pool, id).await?;
+    firecracker_vm::teardown::remove(&vm).await?;
     vm.status = "DELETED".into();
     Ok(Some(vm))
 }
@@ -57,8 +72,23 @@ pub async fn start_microvm(
     }
 
     let vm = vm.unwrap();
+    let interfaces = repo::vm_interface::find_by_vm(&pool, &vm.id).await?;
+    let id = vm.id.clone();
+    let options = restart_options(vm, &interfaces, tailscale_auth_key);
+
+    let vm = start(pool, options, Some(id)).await?;
 
-    let options = VmOptions {
+    Ok(vm)
+}
+
+/// Options starting `vm` again the way it was last started, provisioning
+/// included.
+fn restart_options(
+    vm: VirtualMachine,
+    interfaces: &[VmInterface],
+    tailscale_auth_key: Option<String>,
+) -> VmOptions {
+    VmOptions {
         debian: Some(vm.distro == "debian"),
         alpine: Some(vm.distro == "alpine"),
         ubuntu: Some(vm.distro == "ubuntu"),
@@ -73,25 +103,39 @@ pub async fn start_microvm(
         archlinux: Some(vm.distro == "archlinux"),
         vcpu: vm.vcpu,
         memory: vm.memory,
-        vmlinux: vm.vmlinux.clone(),
-        rootfs: vm.rootfs.clone(),
-        bootargs: vm.bootargs.clone(),
-        bridge: vm.bridge.clone(),
-        tap: vm.tap.clone(),
-        api_socket: vm.api_socket.clone(),
-        mac_address: vm.mac_address.clone(),
-        etcd: None,
+        vmlinux: vm.vmlinux,
+        rootfs: vm.rootfs,
+        bootargs: vm.bootargs,
+        bridge: vm.bridge,
+        tap: vm.tap,
+        api_socket: vm.api_socket,
+        mac_address: vm.mac_address,
         ssh_keys: vm
             .ssh_keys
             .map(|keys| keys.split(',').map(|s| s.to_string()).collect()),
         tailscale: tailscale_auth_key.map(|key| TailscaleOptions {
             auth_key: Some(key),
         }),
-    };
-
-    let vm = start(pool, options, Some(vm.id)).await?;
-
-    Ok(vm)
+        disk_size: vm.disk_size,
+        volumes: vm.volumes.map(|volumes| volume::from_column(&volumes)),
+        jailer: vm.jailer_uid.map(|_| true),
+        ip: vm.static_ip,
+        network: vm.network,
+        ports: vm
+            .ports
+            .map(|ports| ports.split(',').map(|s| s.to_string()).collect()),
+        firewall: vm
+            .firewall
+            .and_then(|column| firewall::from_column(&column)),
+        limits: vm.limits.and_then(|column| limits::from_column(&column)),
+        nics: nic::from_interfaces(interfaces),
+        restart: vm.restart.and_then(|policy| policy.parse().ok()),
+        project: vm.project,
+        provision: vm
+            .provision
+            .and_then(|column| provision::from_column(&column)),
+        image: vm.image,
+    }
 }
 
 pub async fn stop_microvm(
@@ -111,11 +155,44 @@ pub async fn stop_microvm(
     Ok(Some(vm))
 }
 
+pub async fn pause_microvm(
+    pool: Arc<Pool<Sqlite>>,
+    id: &str,
+) -> Result<Option<VirtualMachine>, Error> {
+    let vm = repo::virtual_machine::find(&pool, id).await?;
+    if vm.is_none() {
+        println!("[!] No virtual machine found with the name: {}", id);
+        return Ok(None);
+    }
+
+    let vm = vm.unwrap();
+    firecracker_process::pause(&vm.id).await?;
+    let vm = repo::virtual_machine::find(&pool, &vm.id).await?;
+    Ok(vm)
+}
+
+pub async fn resume_microvm(
+    pool: Arc<Pool<Sqlite>>,
+    id: &str,
+) -> Result<Option<VirtualMachine>, Error> {
+    let vm = repo::virtual_machine::find(&pool, id).await?;
+    if vm.is_none() {
+        println!("[!] No virtual machine found with the name: {}", id);
+        return Ok(None);
+    }
+
+    let vm = vm.unwrap();
+    firecracker_process::resume(&vm.id).await?;
+    let vm = repo::virtual_machine::find(&pool, &vm.id).await?;
+    Ok(vm)
+}
+
 async fn start(
     pool: Arc<Pool<Sqlite>>,
     mut options: VmOptions,
     vm_id: Option<String>,
 ) -> Result<VirtualMachine, Error> {
+    image::resolve(&mut options).await?;
     let vms = repo::virtual_machine::all(&pool).await?;
     if options.tap.is_empty() {
         let vms = vms
@@ -176,3 +253,87 @@ async fn start(
         .ok_or_else(|| Error::msg("Failed to retrieve the created VM"))?;
     Ok(vm)
 }
+
+fn format_line(line: &Line, labelled: bool) -> String {
+    match labelled {
+        true => format!("{:<7} | {}\n", line.source, line.text),
+        false => format!("{}\n", line.text),
+    }
+}
+
+/// Sends the logs of the MicroVM `id` line by line, then the lines written
+/// next when following them, until the receiver is dropped.
+pub async fn logs(
+    pool: Arc<Pool<Sqlite>>,
+    id: &str,
+    sources: Vec<Source>,
+    follow: bool,
+    since: Option<NaiveDateTime>,
+    tail: Option<usize>,
+) -> Result<Option<mpsc::Receiver<String>>, Error> {
+    let vm = match repo::virtual_machine::find(&pool, id).await? {
+        Some(vm) => vm,
+        None => return Ok(None),
+    };
+
+    let labelled = sources.len() > 1;
+    let mut logs = Logs::open(&vm.name, &sources)?;
+    let mut lines = logs.next_lines()?;
+    if !follow {
+        lines.extend(logs.partial_lines());
+    }
+
+    let (tx, rx) = mpsc::channel(256);
+    tokio::spawn(async move {
+        for line in logs::select(lines, since, tail) {
+            if tx.send(format_line(&line, labelled)).await.is_err() {
+                return;
+            }
+        }
+        if !follow {
+            return;
+        }
+        loop {
+            // stop polling as soon as the client goes away, even on a quiet VM
+            tokio::select! {
+                _ = tx.closed() => return,
+                _ = tokio::time::sleep(Duration::from_millis(250)) => {}
+            }
+            let Ok(lines) = logs.next_lines() else {
+                return;
+            };
+            for line in lines {
+                if tx.send(format_line(&line, labelled)).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    Ok(Some(rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use fire_config::ProvisionOptions;
+
+    use super::*;
+
+    #[test]
+    fn test_restart_options() {
+        let provision = ProvisionOptions {
+            inline: Some(vec!["apt-get install -y nginx".into()]),
+            scripts: Some(vec!["scripts/setup.sh".into()]),
+            files: None,
+        };
+        let vm = VirtualMachine {
+            distro: "debian".into(),
+            provision: Some(provision::to_column(&provision)),
+            restart: Some("on-failure".into()),
+            ..Default::default()
+        };
+        let options = restart_options(vm, &[], None);
+        assert_eq!(options.provision, Some(provision));
+        assert_eq!(options.restart, Some(RestartPolicy::OnFailure));
+        assert_eq!(options.debian, Some(true));
+    }
+}
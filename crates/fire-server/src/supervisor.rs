@@ -0,0 +1,138 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use fire_config::RestartPolicy;
+use firecracker_state::{entity::virtual_machine::VirtualMachine, repo};
+use owo_colors::OwoColorize;
+use sqlx::{Pool, Sqlite};
+
+use crate::services::microvm::start_microvm;
+
+/// How often the recorded MicroVM states are checked against the host.
+const INTERVAL: Duration = Duration::from_secs(5);
+
+/// Delay before the first restart, doubled after each attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// A MicroVM up for that long after a restart gets its backoff reset.
+const HEALTHY_AFTER: Duration = Duration::from_secs(60);
+
+struct Backoff {
+    attempts: u32,
+    next_attempt: Instant,
+    restarted_at: Instant,
+}
+
+fn delay(attempts: u32) -> Duration {
+    INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempts))
+        .min(MAX_BACKOFF)
+}
+
+/// Whether `policy` brings back a MicroVM with the given status.
+fn should_restart(policy: RestartPolicy, status: &str, exit_code: Option<i32>) -> bool {
+    match policy {
+        RestartPolicy::No => false,
+        RestartPolicy::OnFailure => {
+            status == "CRASHED" || (status == "EXITED" && exit_code != Some(0))
+        }
+        RestartPolicy::Always => status == "CRASHED" || status == "EXITED",
+    }
+}
+
+fn policy(vm: &VirtualMachine) -> RestartPolicy {
+    vm.restart
+        .as_deref()
+        .and_then(|policy| policy.parse().ok())
+        .unwrap_or_default()
+}
+
+/// Watches the MicroVMs for as long as the server runs: reconciles their
+/// states with the host, which also adopts the ones started before the
+/// server, and starts again those whose restart policy asks for it.
+pub async fn run(pool: Arc<Pool<Sqlite>>) {
+    let mut backoffs: HashMap<String, Backoff> = HashMap::new();
+    let mut interval = tokio::time::interval(INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = firecracker_process::reconcile::run().await {
+            println!("[!] Failed to reconcile MicroVM states: {}", e);
+            continue;
+        }
+        let vms = match repo::virtual_machine::all(&pool).await {
+            Ok(vms) => vms,
+            Err(e) => {
+                println!("[!] Failed to fetch MicroVMs: {}", e);
+                continue;
+            }
+        };
+
+        for vm in vms {
+            if vm.status == "RUNNING" || vm.status == "PAUSED" {
+                if backoffs
+                    .get(&vm.id)
+                    .is_some_and(|backoff| backoff.restarted_at.elapsed() >= HEALTHY_AFTER)
+                {
+                    backoffs.remove(&vm.id);
+                }
+                continue;
+            }
+            if !should_restart(policy(&vm), &vm.status, vm.exit_code) {
+                backoffs.remove(&vm.id);
+                continue;
+            }
+
+            let now = Instant::now();
+            let backoff = backoffs.entry(vm.id.clone()).or_insert(Backoff {
+                attempts: 0,
+                next_attempt: now,
+                restarted_at: now,
+            });
+            if now < backoff.next_attempt {
+                continue;
+            }
+            backoff.attempts += 1;
+            backoff.next_attempt = now + delay(backoff.attempts);
+            backoff.restarted_at = now;
+
+            println!(
+                "[+] Restarting {} ({}, attempt {})...",
+                vm.name.cyan(),
+                vm.status,
+                backoff.attempts
+            );
+            if let Err(e) = start_microvm(pool.clone(), &vm.id, None).await {
+                println!("[!] Failed to restart {}: {}", vm.name.cyan(), e);
+                // leave nothing half started behind, and retry after the backoff
+                firecracker_process::stop(Some(vm.name.clone())).await.ok();
+                repo::virtual_machine::update_process(&pool, &vm.id, "CRASHED", None, None)
+                    .await
+                    .ok();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_restart() {
+        assert!(!should_restart(RestartPolicy::No, "CRASHED", None));
+        assert!(should_restart(RestartPolicy::OnFailure, "CRASHED", None));
+        assert!(should_restart(RestartPolicy::OnFailure, "EXITED", Some(1)));
+        assert!(!should_restart(RestartPolicy::OnFailure, "EXITED", Some(0)));
+        assert!(should_restart(RestartPolicy::Always, "EXITED", Some(0)));
+        assert!(!should_restart(RestartPolicy::Always, "STOPPED", None));
+
+        assert_eq!(delay(1), Duration::from_secs(2));
+        assert_eq!(delay(4), Duration::from_secs(16));
+        assert_eq!(delay(40), MAX_BACKOFF);
+    }
+}
@@ -1,7 +1,8 @@
-use fire_config::TailscaleOptions;
+use fire_config::{FirewallOptions, LimitsOptions, NicOptions, TailscaleOptions};
+use firecracker_prepare::Distro;
 use firecracker_vm::{mac::generate_unique_mac, types::VmOptions};
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 
 #[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct MicroVM {
@@ -22,6 +23,19 @@ pub struct StartMicroVM {
     pub tailscale_auth_key: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MicroVMLogs {
+    /// `vmm`, `console` or `setup`, all of them interleaved when not set.
+    pub source: Option<String>,
+    /// Keep streaming the lines as they are written.
+    pub follow: Option<bool>,
+    /// A duration such as `10m`, or a local date and time.
+    pub since: Option<String>,
+    /// Number of lines to show from the end of the logs.
+    pub tail: Option<usize>,
+}
+
 #[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct CreateMicroVM {
     pub name: Option<String>,
@@ -34,6 +48,41 @@ pub struct CreateMicroVM {
     pub ssh_keys: Option<Vec<String>>,
     pub start: Option<bool>,
     pub tailscale_auth_key: Option<String>,
+    pub disk_size: Option<u32>,
+    pub jailer: Option<bool>,
+    pub ip: Option<String>,
+    pub network: Option<String>,
+    pub ports: Option<Vec<String>>,
+    pub firewall: Option<Firewall>,
+    pub limits: Option<Limits>,
+    pub nics: Option<Vec<Nic>>,
+    /// `no`, `on-failure` or `always`.
+    pub restart: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct Firewall {
+    /// `allow` or `deny` outgoing traffic not listed in `allow`.
+    pub egress: Option<String>,
+    pub allow: Option<Vec<String>>,
+    /// Block the traffic with the other MicroVMs of the bridge.
+    pub isolate: Option<bool>,
+}
+
+/// Rate limits, bandwidths are in megabits per second.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct Limits {
+    pub net_rx_mbps: Option<u32>,
+    pub net_tx_mbps: Option<u32>,
+    pub disk_iops: Option<u32>,
+    pub disk_mbps: Option<u32>,
+}
+
+/// Network interface, the first one is `eth0`.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct Nic {
+    pub network: Option<String>,
+    pub mac: Option<String>,
 }
 
 impl Into<VmOptions> for CreateMicroVM {
@@ -120,6 +169,33 @@ impl Into<VmOptions> for CreateMicroVM {
             tailscale: self.tailscale_auth_key.map(|key| TailscaleOptions {
                 auth_key: Some(key),
             }),
+            disk_size: self.disk_size,
+            jailer: self.jailer,
+            ip: self.ip,
+            network: self.network,
+            ports: self.ports,
+            firewall: self.firewall.map(|firewall| FirewallOptions {
+                egress: firewall.egress,
+                allow: firewall.allow,
+                isolate: firewall.isolate,
+            }),
+            limits: self.limits.map(|limits| LimitsOptions {
+                net_rx_mbps: limits.net_rx_mbps,
+                net_tx_mbps: limits.net_tx_mbps,
+                disk_iops: limits.disk_iops,
+                disk_mbps: limits.disk_mbps,
+            }),
+            nics: self.nics.map(|nics| {
+                nics.into_iter()
+                    .map(|nic| NicOptions {
+                        network: nic.network,
+                        mac: nic.mac,
+                    })
+                    .collect()
+            }),
+            restart: self.restart.and_then(|policy| policy.parse().ok()),
+            // any other name is one of an image built with `fireup build`
+            image: self.image.filter(|image| image.parse::<Distro>().is_err()),
             ..Default::default()
         }
     }
@@ -0,0 +1,388 @@
+use std::{
+    fs,
+    path::{Component, Path},
+};
+
+use anyhow::{anyhow, Context, Result};
+use owo_colors::OwoColorize;
+
+use crate::{
+    command::{run_command, run_command_with_stdout_inherit},
+    rootfs, Distro,
+};
+
+/// Instruction of a Firefile applied on top of the base rootfs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// `RUN <command>`, run with `/bin/sh -c` in a chroot.
+    Run(String),
+    /// `COPY <src> <dst>`, `src` being relative to the directory of the Firefile.
+    Copy { src: String, dst: String },
+    /// `ENV <key>=<value>`, exported to the next `RUN` steps and written to
+    /// `/etc/environment`.
+    Env { key: String, value: String },
+    /// `ENABLE <service>`, started at boot by systemd or OpenRC.
+    Enable(String),
+}
+
+/// Recipe of `fireup build`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Firefile {
+    pub from: Distro,
+    pub steps: Vec<Step>,
+}
+
+impl Firefile {
+    pub fn load(path: &Path) -> Result<Firefile> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Firefile::parse(&content).with_context(|| format!("Invalid {}", path.display()))
+    }
+
+    /// Parses a Firefile: one instruction per line, `FROM <distro>` first,
+    /// `#` comments and lines continued with a trailing `\`.
+    pub fn parse(content: &str) -> Result<Firefile> {
+        let mut from = None;
+        let mut steps = vec![];
+
+        let mut instruction = String::new();
+        let mut start = 0;
+        for (i, line) in content.lines().enumerate() {
+            if instruction.is_empty() {
+                start = i + 1;
+            }
+            let line = line.trim();
+            if instruction.is_empty() && (line.is_empty() || line.starts_with('#')) {
+                continue;
+            }
+            if let Some(line) = line.strip_suffix('\\') {
+                instruction.push_str(line.trim_end());
+                instruction.push(' ');
+                continue;
+            }
+            instruction.push_str(line);
+
+            let (keyword, args) = instruction
+                .trim()
+                .split_once(char::is_whitespace)
+                .map(|(keyword, args)| (keyword, args.trim()))
+                .unwrap_or((instruction.trim(), ""));
+            if args.is_empty() {
+                return Err(anyhow!("line {}: {} needs an argument", start, keyword));
+            }
+
+            match keyword.to_uppercase().as_str() {
+                "FROM" if from.is_some() => {
+                    return Err(anyhow!("line {}: FROM can only be given once", start));
+                }
+                "FROM" => from = Some(args.parse::<Distro>()?),
+                _ if from.is_none() => {
+                    return Err(anyhow!("line {}: a Firefile must start with FROM", start));
+                }
+                "RUN" => steps.push(Step::Run(args.to_string())),
+                "COPY" => match args.split_whitespace().collect::<Vec<_>>()[..] {
+                    [src, _] if Path::new(src).is_absolute() => {
+                        return Err(anyhow!(
+                            "line {}: COPY source must be relative to the Firefile",
+                            start
+                        ));
+                    }
+                    [_, dst] if !dst.starts_with('/') => {
+                        return Err(anyhow!("line {}: COPY destination must be absolute", start));
+                    }
+                    [_, dst]
+                        if Path::new(dst)
+                            .components()
+                            .any(|c| c == Component::ParentDir) =>
+                    {
+                        return Err(anyhow!(
+                            "line {}: COPY destination cannot contain '..'",
+                            start
+                        ));
+                    }
+                    [src, dst] => steps.push(Step::Copy {
+                        src: src.into(),
+                        dst: dst.into(),
+                    }),
+                    _ => return Err(anyhow!("line {}: expected COPY <src> <dst>", start)),
+                },
+                "ENV" => {
+                    let first = args.split_whitespace().next().unwrap_or_default();
+                    let (key, value) = match first.contains('=') {
+                        true => args.split_once('='),
+                        false => args.split_once(char::is_whitespace),
+                    }
+                    .map(|(key, value)| (key.trim(), unquote(value.trim())))
+                    .ok_or_else(|| anyhow!("line {}: expected ENV <key>=<value>", start))?;
+                    if key.is_empty() || key.contains(char::is_whitespace) {
+                        return Err(anyhow!("line {}: invalid ENV key '{}'", start, key));
+                    }
+                    steps.push(Step::Env {
+                        key: key.into(),
+                        value: value.into(),
+                    });
+                }
+                "ENABLE" => steps.extend(
+                    args.split_whitespace()
+                        .map(|service| Step::Enable(service.into())),
+                ),
+                _ => return Err(anyhow!("line {}: unknown instruction {}", start, keyword)),
+            }
+            instruction.clear();
+        }
+
+        if !instruction.is_empty() {
+            return Err(anyhow!("line {}: unterminated instruction", start));
+        }
+
+        Ok(Firefile {
+            from: from.ok_or_else(|| anyhow!("a Firefile must start with FROM"))?,
+            steps,
+        })
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .or_else(|| {
+            value
+                .strip_prefix('\'')
+                .and_then(|value| value.strip_suffix('\''))
+        })
+        .unwrap_or(value)
+}
+
+/// Quotes `value` for `/bin/sh`.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Mounts of the build chroot, undone on drop.
+struct Chroot<'a> {
+    dir: &'a str,
+}
+
+impl<'a> Chroot<'a> {
+    fn mount(dir: &'a str) -> Result<Chroot<'a>> {
+        let chroot = Chroot { dir };
+        for (source, target, args) in [
+            ("proc", "proc", vec!["-t", "proc"]),
+            ("sysfs", "sys", vec!["-t", "sysfs"]),
+            ("/dev", "dev", vec!["--bind"]),
+        ] {
+            let target = format!("{}/{}", dir, target);
+            run_command("mkdir", &["-p", &target], true)?;
+            let mut args = args;
+            args.extend([source, target.as_str()]);
+            run_command("mount", &args, true)?;
+        }
+
+        // the image may link resolv.conf to a file of systemd-resolved
+        let resolv_conf = format!("{}/etc/resolv.conf", dir);
+        run_command(
+            "sh",
+            &[
+                "-c",
+                &format!(
+                    "if [ -e {0} ] || [ -L {0} ]; then mv {0} {0}.fireup; fi && cp /etc/resolv.conf {0}",
+                    quote(&resolv_conf)
+                ),
+            ],
+            true,
+        )?;
+        Ok(chroot)
+    }
+}
+
+impl Drop for Chroot<'_> {
+    fn drop(&mut self) {
+        for target in ["dev", "sys", "proc"] {
+            let target = format!("{}/{}", self.dir, target);
+            let _ = run_command("umount", &["-l", &target], true);
+        }
+        let resolv_conf = format!("{}/etc/resolv.conf", self.dir);
+        let _ = run_command(
+            "sh",
+            &[
+                "-c",
+                &format!(
+                    "rm -f {0} && if [ -e {0}.fireup ] || [ -L {0}.fireup ]; then mv {0}.fireup {0}; fi",
+                    quote(&resolv_conf)
+                ),
+            ],
+            true,
+        );
+    }
+}
+
+/// Applies the steps of `firefile` on a copy of the `base` squashfs and
+/// writes the result to the squashfs `output`. `context` is the directory
+/// `COPY` sources are relative to.
+pub fn build(firefile: &Firefile, context: &Path, base: &str, output: &str) -> Result<()> {
+    let build_dir = format!("{}.build", output);
+    run_command("rm", &["-rf", "--one-file-system", &build_dir], true)?;
+
+    println!("[+] Extracting {}...", base.cyan());
+    run_command("unsquashfs", &["-d", &build_dir, base], true)?;
+
+    let result = apply(firefile, context, &build_dir).and_then(|_| {
+        run_command("rm", &["-f", output], true)?;
+        rootfs::create_squashfs(&build_dir, output)
+    });
+    // never follow the mounts of the chroot if unmounting them failed
+    run_command("rm", &["-rf", "--one-file-system", &build_dir], true)?;
+    result
+}
+
+fn apply(firefile: &Firefile, context: &Path, dir: &str) -> Result<()> {
+    let context = &fs::canonicalize(context)
+        .with_context(|| format!("Failed to resolve {}", context.display()))?;
+    let _chroot = Chroot::mount(dir)?;
+    let mut env: Vec<(&String, &String)> = vec![];
+
+    for (i, step) in firefile.steps.iter().enumerate() {
+        let n = format!("[{}/{}]", i + 1, firefile.steps.len());
+        match step {
+            Step::Run(command) => {
+                println!("{} RUN {}", n.bright_green(), command);
+                let script = env
+                    .iter()
+                    .map(|(key, value)| format!("export {}={}; ", key, quote(value)))
+                    .collect::<String>()
+                    + command;
+                run_command_with_stdout_inherit("chroot", &[dir, "/bin/sh", "-c", &script], true)
+                    .with_context(|| format!("RUN {} failed", command))?;
+            }
+            Step::Copy { src, dst } => {
+                println!("{} COPY {} {}", n.bright_green(), src, dst);
+                let src = fs::canonicalize(context.join(src))
+                    .with_context(|| format!("COPY source {} does not exist", src))?;
+                if !src.starts_with(context) {
+                    return Err(anyhow!(
+                        "COPY source {} is outside of {}",
+                        src.display(),
+                        context.display()
+                    ));
+                }
+                let src = src.display().to_string();
+                let target = format!("{}{}", dir, dst);
+                if Path::new(&src).is_dir() {
+                    run_command("mkdir", &["-p", &target], true)?;
+                    run_command("cp", &["-a", &format!("{}/.", src), &target], true)?;
+                } else if dst.ends_with('/') {
+                    run_command("mkdir", &["-p", &target], true)?;
+                    run_command("cp", &["-a", &src, &target], true)?;
+                } else {
+                    let parent = Path::new(&target)
+                        .parent()
+                        .map(|parent| parent.display().to_string())
+                        .unwrap_or(dir.to_string());
+                    run_command("mkdir", &["-p", &parent], true)?;
+                    run_command("cp", &["-a", &src, &target], true)?;
+                }
+                run_command("chown", &["-R", "root:root", &target], true)?;
+            }
+            Step::Env { key, value } => {
+                println!("{} ENV {}={}", n.bright_green(), key, value);
+                env.retain(|(k, _)| *k != key);
+                env.push((key, value));
+            }
+            Step::Enable(service) => {
+                println!("{} ENABLE {}", n.bright_green(), service);
+                run_command(
+                    "chroot",
+                    &[
+                        dir,
+                        "/bin/sh",
+                        "-c",
+                        &format!(
+                            "if command -v systemctl >/dev/null 2>&1; then systemctl enable {0}; else rc-update add {0} default; fi",
+                            quote(service)
+                        ),
+                    ],
+                    true,
+                )?;
+            }
+        }
+    }
+
+    if !env.is_empty() {
+        let environment = env
+            .iter()
+            .map(|(key, value)| format!("{}={}\n", key, quote(value)))
+            .collect::<String>();
+        run_command(
+            "sh",
+            &[
+                "-c",
+                &format!(
+                    "printf %s {} >> {}",
+                    quote(&environment),
+                    quote(&format!("{}/etc/environment", dir))
+                ),
+            ],
+            true,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let firefile = Firefile::parse(
+            r#"
+            # web server
+            FROM debian
+
+            ENV DEBIAN_FRONTEND=noninteractive
+            ENV GREETING "hello world"
+            RUN apt-get update && \
+                apt-get install -y nginx
+            COPY ./site /var/www/html
+            ENABLE nginx ssh
+            "#,
+        )
+        .unwrap();
+        assert_eq!(firefile.from, Distro::Debian);
+        assert_eq!(
+            firefile.steps,
+            vec![
+                Step::Env {
+                    key: "DEBIAN_FRONTEND".into(),
+                    value: "noninteractive".into()
+                },
+                Step::Env {
+                    key: "GREETING".into(),
+                    value: "hello world".into()
+                },
+                Step::Run("apt-get update && apt-get install -y nginx".into()),
+                Step::Copy {
+                    src: "./site".into(),
+                    dst: "/var/www/html".into()
+                },
+                Step::Enable("nginx".into()),
+                Step::Enable("ssh".into()),
+            ]
+        );
+
+        assert!(Firefile::parse("RUN true").is_err());
+        assert!(Firefile::parse("FROM debian\nFROM alpine").is_err());
+        assert!(Firefile::parse("FROM windows").is_err());
+        assert!(Firefile::parse("FROM debian\nCOPY a").is_err());
+        assert!(Firefile::parse("FROM debian\nCOPY a etc/a").is_err());
+        assert!(Firefile::parse("FROM debian\nCOPY /etc/shadow /a").is_err());
+        assert!(Firefile::parse("FROM debian\nCOPY a /../../etc/sudoers").is_err());
+        assert!(Firefile::parse("FROM debian\nRUN apt-get update \\").is_err());
+        assert!(Firefile::parse("FROM debian\nVOLUME /data").is_err());
+        assert!(Firefile::parse("# empty").is_err());
+
+        assert_eq!(quote("it's"), r#"'it'\''s'"#);
+    }
+}
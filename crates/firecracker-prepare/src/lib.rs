@@ -1,6 +1,6 @@
-use std::{env, fs};
+use std::{env, fs, str::FromStr};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use owo_colors::OwoColorize;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
@@ -13,6 +13,7 @@ use crate::{
 pub mod command;
 pub mod config;
 pub mod downloader;
+pub mod firefile;
 pub mod rootfs;
 pub mod ssh;
 pub mod vmlinuz;
@@ -58,6 +59,28 @@ impl ToString for Distro {
     }
 }
 
+impl FromStr for Distro {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "debian" => Ok(Distro::Debian),
+            "alpine" => Ok(Distro::Alpine),
+            "ubuntu" => Ok(Distro::Ubuntu),
+            "nixos" => Ok(Distro::NixOS),
+            "fedora" => Ok(Distro::Fedora),
+            "gentoo" => Ok(Distro::Gentoo),
+            "slackware" => Ok(Distro::Slackware),
+            "opensuse" => Ok(Distro::Opensuse),
+            "opensuse-tumbleweed" => Ok(Distro::OpensuseTumbleweed),
+            "almalinux" => Ok(Distro::Almalinux),
+            "rockylinux" => Ok(Distro::RockyLinux),
+            "archlinux" => Ok(Distro::Archlinux),
+            _ => Err(anyhow!("Unknown distribution '{}'", s)),
+        }
+    }
+}
+
 pub fn prepare(
     distro: Distro,
     kernel_file: Option<String>,
@@ -262,6 +285,7 @@ impl RootfsPreparer for DebianPreparer {
 
         rootfs::create_overlay_dirs(&debootstrap_dir)?;
         rootfs::add_overlay_init(&debootstrap_dir)?;
+        rootfs::add_agent(&debootstrap_dir)?;
         rootfs::create_squashfs(&debootstrap_dir, &img_file)?;
 
         let ssh_key_file = match ssh_keys {
@@ -422,6 +446,7 @@ impl RootfsPreparer for AlpinePreparer {
             None => ssh::generate_and_copy_ssh_key(&ssh_key_name, &minirootfs)?,
         }
 
+        rootfs::add_agent(&minirootfs)?;
         rootfs::create_squashfs(&minirootfs, &img_file)?;
 
         let ssh_key_file = match ssh_keys {
@@ -515,6 +540,7 @@ impl RootfsPreparer for UbuntuPreparer {
 
         rootfs::create_overlay_dirs(&squashfs_root_dir)?;
         rootfs::add_overlay_init(&squashfs_root_dir)?;
+        rootfs::add_agent(&squashfs_root_dir)?;
         rootfs::create_squashfs(&squashfs_root_dir, &img_file)?;
 
         let ssh_key_file = match ssh_keys {
@@ -568,6 +594,7 @@ impl RootfsPreparer for NixOSPreparer {
             None => ssh::generate_and_copy_ssh_key(&ssh_key_name, &nixos_rootfs)?,
         }
 
+        rootfs::add_agent(&nixos_rootfs)?;
         rootfs::create_squashfs(&nixos_rootfs, &img_file)?;
 
         let ssh_key_file = match ssh_keys {
@@ -634,6 +661,7 @@ impl RootfsPreparer for FedoraPreparer {
             None => ssh::generate_and_copy_ssh_key(&ssh_key_name, &fedora_rootfs)?,
         }
 
+        rootfs::add_agent(&fedora_rootfs)?;
         rootfs::create_squashfs(&fedora_rootfs, &img_file)?;
 
         let ssh_key_file = match ssh_keys {
@@ -702,6 +730,7 @@ impl RootfsPreparer for GentooPreparer {
             None => ssh::generate_and_copy_ssh_key(&ssh_key_name, &gentoo_rootfs)?,
         }
 
+        rootfs::add_agent(&gentoo_rootfs)?;
         rootfs::create_squashfs(&gentoo_rootfs, &img_file)?;
 
         let ssh_key_file = match ssh_keys {
@@ -769,6 +798,7 @@ impl RootfsPreparer for SlackwarePreparer {
             None => ssh::generate_and_copy_ssh_key(&ssh_key_name, &slackware_rootfs)?,
         }
 
+        rootfs::add_agent(&slackware_rootfs)?;
         rootfs::create_squashfs(&slackware_rootfs, &img_file)?;
 
         let ssh_key_file = match ssh_keys {
@@ -830,6 +860,7 @@ impl RootfsPreparer for OpensusePreparer {
             None => ssh::generate_and_copy_ssh_key(&ssh_key_name, &opensuse_rootfs)?,
         }
 
+        rootfs::add_agent(&opensuse_rootfs)?;
         rootfs::create_squashfs(&opensuse_rootfs, &img_file)?;
 
         let ssh_key_file = match ssh_keys {
@@ -885,6 +916,7 @@ impl RootfsPreparer for AlmalinuxPreparer {
             None => ssh::generate_and_copy_ssh_key(&ssh_key_name, &almalinux_rootfs)?,
         }
 
+        rootfs::add_agent(&almalinux_rootfs)?;
         rootfs::create_squashfs(&almalinux_rootfs, &img_file)?;
 
         let ssh_key_file = match ssh_keys {
@@ -939,6 +971,7 @@ impl RootfsPreparer for RockyLinuxPreparer {
             Some(ref keys) => ssh::copy_ssh_keys(keys, &rockylinux_rootfs)?,
             None => ssh::generate_and_copy_ssh_key(&ssh_key_name, &rockylinux_rootfs)?,
         }
+        rootfs::add_agent(&rockylinux_rootfs)?;
         rootfs::create_squashfs(&rockylinux_rootfs, &img_file)?;
 
         let ssh_key_file = match ssh_keys {
@@ -1004,6 +1037,7 @@ impl RootfsPreparer for ArchlinuxPreparer {
             None => ssh::generate_and_copy_ssh_key(&ssh_key_name, &archlinux_rootfs)?,
         }
 
+        rootfs::add_agent(&archlinux_rootfs)?;
         rootfs::create_squashfs(&archlinux_rootfs, &img_file)?;
 
         let ssh_key_file = match ssh_keys {
@@ -1066,6 +1100,7 @@ impl RootfsPreparer for OpensuseTumbleweedPreparer {
             None => ssh::generate_and_copy_ssh_key(&ssh_key_name, &opensuse_rootfs)?,
         }
 
+        rootfs::add_agent(&opensuse_rootfs)?;
         rootfs::create_squashfs(&opensuse_rootfs, &img_file)?;
 
         let ssh_key_file = match ssh_keys {
@@ -72,3 +72,51 @@ pub fn add_overlay_init(rootfs_dir: &str) -> Result<()> {
     )?;
     Ok(())
 }
+
+/// The agent is looked up next to the fireup binary, then in the PATH. It
+/// runs on every distro, so it must be statically linked.
+fn agent_binary() -> Option<String> {
+    let sibling = std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join("fireup-agent")))
+        .filter(|path| path.exists())
+        .map(|path| path.display().to_string());
+    sibling.or_else(|| {
+        run_command("which", &["fireup-agent"], false)
+            .ok()
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            .filter(|path| !path.is_empty())
+    })
+}
+
+pub fn add_agent(rootfs_dir: &str) -> Result<()> {
+    let Some(agent) = agent_binary() else {
+        println!(
+            "[!] Warning: fireup-agent not found, the MicroVMs will only be reachable over SSH."
+        );
+        return Ok(());
+    };
+    println!("Adding fireup-agent...");
+    run_command("mkdir", &["-p", &format!("{}/sbin", rootfs_dir)], true)?;
+    let target = format!("{}/sbin/fireup-agent", rootfs_dir);
+    run_command("cp", &[&agent, &target], true)?;
+    run_command("chmod", &["755", &target], true)?;
+
+    // images coming with their own overlay-init need to start it too
+    let overlay_init = format!("{}/sbin/overlay-init", rootfs_dir);
+    let starts_agent = std::fs::read_to_string(&overlay_init)
+        .map(|script| script.contains("fireup-agent"))
+        .unwrap_or(true);
+    if !starts_agent {
+        run_command(
+            "sed",
+            &[
+                "-i",
+                "/^exec /i [ -x /sbin/fireup-agent ] && /sbin/fireup-agent &",
+                &overlay_init,
+            ],
+            true,
+        )?;
+    }
+    Ok(())
+}
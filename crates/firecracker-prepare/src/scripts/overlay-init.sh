@@ -57,6 +57,12 @@ fi
 
 do_overlay
 
+# start the fireup agent, it answers the host over vsock even when the
+# guest network is down
+if [ -x /sbin/fireup-agent ]; then
+    /sbin/fireup-agent &
+fi
+
 # invoke the actual system init program and proceed with the boot
 # process.
 exec /sbin/init $@
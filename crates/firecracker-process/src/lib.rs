@@ -1,13 +1,18 @@
 use std::{process, thread};
 
-use anyhow::Result;
-use firecracker_state::repo;
-use firecracker_vm::types::VmOptions;
+use anyhow::{anyhow, Context, Result};
+use firecracker_state::{entity::virtual_machine::VirtualMachine, repo};
+use firecracker_vm::{
+    client::{FirecrackerClient, State, Vm},
+    console, firewall, jailer, teardown,
+    types::VmOptions,
+};
 use owo_colors::OwoColorize;
 
 use crate::command::{run_command, run_command_in_background};
 
 pub mod command;
+pub mod reconcile;
 
 pub async fn start(config: &VmOptions) -> Result<u32> {
     let name = config
@@ -16,9 +21,32 @@ pub async fn start(config: &VmOptions) -> Result<u32> {
         .trim_end_matches(".sock")
         .to_string();
 
-    stop(Some(name)).await?;
-    println!("[+] Starting Firecracker...");
-    let pid = run_command_in_background("firecracker", &["--api-sock", &config.api_socket], true)?;
+    stop(Some(name.clone())).await?;
+
+    let pid = match config.jailer.unwrap_or(false) {
+        true => {
+            jailer::cleanup(&name).await?;
+            jailer::setup_netns(&name)?;
+            let uid = jailer::uid(&name).await?;
+            let args = jailer::args(&name, uid, config)?;
+            let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+            println!("[+] Starting Firecracker in a jail (uid {})...", uid);
+            run_command("rm", &["-rf", &config.api_socket], true)?;
+            let pid = spawn_with_console(&name, &config.api_socket, "jailer", &args)?;
+            jailer::link_api_socket(&name, &config.api_socket)?;
+            pid
+        }
+        false => {
+            println!("[+] Starting Firecracker...");
+            spawn_with_console(
+                &name,
+                &config.api_socket,
+                "firecracker",
+                &["--api-sock", &config.api_socket],
+            )?
+        }
+    };
 
     let mut attempts = 0;
     while !std::path::Path::new(&config.api_socket).exists() {
@@ -30,9 +58,37 @@ pub async fn start(config: &VmOptions) -> Result<u32> {
         thread::sleep(std::time::Duration::from_millis(500));
     }
 
+    // Firecracker runs as root, hand the API socket over to the current user
+    // so the API client doesn't need sudo.
+    if !command::is_root() {
+        let owner = unsafe { format!("{}:{}", libc::getuid(), libc::getgid()) };
+        run_command("chown", &[&owner, &config.api_socket], true)?;
+    }
+
     Ok(pid)
 }
 
+/// Starts `program` under `fireup serial`, which keeps the serial console of
+/// the guest in its console log and lets `fireup console` attach to it.
+fn spawn_with_console(name: &str, api_socket: &str, program: &str, args: &[&str]) -> Result<u32> {
+    let exe = std::env::current_exe()
+        .with_context(|| "Failed to get current executable")?
+        .display()
+        .to_string();
+    let log = console::log_file(name)?;
+    if let Some(logs_dir) = std::path::Path::new(&log).parent() {
+        std::fs::create_dir_all(logs_dir)
+            .with_context(|| format!("Failed to create logs directory: {}", logs_dir.display()))?;
+    }
+    let socket = console::socket(api_socket);
+    let owner = unsafe { format!("{}:{}", libc::getuid(), libc::getgid()) };
+    let mut relay_args = vec![
+        "serial", "--log", &log, "--socket", &socket, "--owner", &owner, "--", program,
+    ];
+    relay_args.extend_from_slice(args);
+    run_command_in_background(&exe, &relay_args, true)
+}
+
 pub async fn stop(name: Option<String>) -> Result<()> {
     if name.is_none() {
         return stop_all().await;
@@ -40,16 +96,11 @@ pub async fn stop(name: Option<String>) -> Result<()> {
 
     let name = name.unwrap();
 
-    if !vm_is_running(&name).await? {
+    if !vm_is_running(&name).await? && !vm_is_paused(&name).await? {
         println!("[!] {} is not running.", name.cyan());
         return Ok(());
     }
 
-    let config = VmOptions {
-        api_socket: format!("/tmp/firecracker-{}.sock", name),
-        ..Default::default()
-    };
-
     let pool = firecracker_state::create_connection_pool().await?;
 
     let vm = repo::virtual_machine::find(&pool, &name).await?;
@@ -62,32 +113,102 @@ pub async fn stop(name: Option<String>) -> Result<()> {
     }
 
     let vm = vm.unwrap();
-    if let Some(pid) = vm.pid {
+    kill(&vm);
+
+    teardown::release(&vm).await?;
+    println!("[+] {} has been stopped.", name.cyan());
+
+    repo::virtual_machine::update_process(&pool, &vm.id, "STOPPED", None, None).await?;
+    firewall::sync().await?;
+
+    Ok(())
+}
+
+/// Kills the Firecracker processes of `vm`, and the `sudo` they run under.
+fn kill(vm: &VirtualMachine) {
+    let mut pids = reconcile::processes()
+        .into_iter()
+        .filter(|process| process.owns(vm))
+        .map(|process| process.pid)
+        .collect::<Vec<_>>();
+    if pids.is_empty() {
+        pids.extend(vm.pid);
+    }
+    for pid in pids {
         if run_command("kill", &["-s", "KILL", &pid.to_string()], true).is_err() {
             println!("[!] Failed to kill process with PID {}.", pid);
         }
     }
+}
 
-    run_command("rm", &["-rf", &config.api_socket], true)?;
-    println!("[+] {} has been stopped.", name.cyan());
+pub async fn vm_is_running(name: &str) -> Result<bool> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let vm = repo::virtual_machine::find(&pool, name).await?;
 
-    repo::virtual_machine::update_status(&pool, &name, "STOPPED").await?;
+    Ok(vm
+        .map(|vm| vm.status != "PAUSED" && reconcile::find(&vm, &reconcile::processes()).is_some())
+        .unwrap_or(false))
+}
+
+pub async fn vm_is_paused(name: &str) -> Result<bool> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let vm = repo::virtual_machine::find(&pool, name).await?;
+
+    Ok(vm
+        .map(|vm| vm.status == "PAUSED" && reconcile::find(&vm, &reconcile::processes()).is_some())
+        .unwrap_or(false))
+}
+
+pub async fn pause(name: &str) -> Result<()> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let vm = repo::virtual_machine::find(&pool, name)
+        .await?
+        .ok_or_else(|| anyhow!("No virtual machine found with name or id '{}'", name))?;
+
+    if vm_is_paused(&vm.id).await? {
+        println!("[!] {} is already paused.", vm.name.cyan());
+        return Ok(());
+    }
+
+    if !vm_is_running(&vm.id).await? {
+        return Err(anyhow!("{} is not running", vm.name));
+    }
+
+    FirecrackerClient::new(&vm.api_socket)
+        .patch_vm(&Vm {
+            state: State::Paused,
+        })
+        .await
+        .with_context(|| format!("Failed to pause {}", vm.name))?;
+
+    repo::virtual_machine::update_status(&pool, &vm.id, "PAUSED").await?;
+    println!("[+] {} has been paused.", vm.name.cyan());
 
     Ok(())
 }
 
-pub async fn vm_is_running(name: &str) -> Result<bool> {
+pub async fn resume(name: &str) -> Result<()> {
     let pool = firecracker_state::create_connection_pool().await?;
-    let vm = repo::virtual_machine::find(&pool, name).await?;
+    let vm = repo::virtual_machine::find(&pool, name)
+        .await?
+        .ok_or_else(|| anyhow!("No virtual machine found with name or id '{}'", name))?;
 
-    if let Some(vm) = vm {
-        if std::path::Path::new(&vm.api_socket).exists() {
-            return Ok(true);
-        }
-        repo::virtual_machine::update_status(&pool, name, "STOPPED").await?;
+    if !vm_is_paused(&vm.id).await? {
+        println!("[!] {} is not paused.", vm.name.cyan());
+        return Ok(());
     }
 
-    Ok(false)
+    FirecrackerClient::new(&vm.api_socket)
+        .patch_vm(&Vm {
+            state: State::Resumed,
+        })
+        .await
+        .with_context(|| format!("Failed to resume {}", vm.name))?;
+
+    repo::virtual_machine::update_status(&pool, &vm.id, "RUNNING").await?;
+    println!("[+] {} has been resumed.", vm.name.cyan());
+
+    Ok(())
 }
 
 pub fn is_running() -> bool {
@@ -97,17 +218,33 @@ pub fn is_running() -> bool {
     }
 }
 
+/// Stops every MicroVM fireup started, leaving other Firecracker processes
+/// of the host alone.
 pub async fn stop_all() -> Result<()> {
-    if !is_running() {
-        println!("[!] No Firecracker process is running.");
+    let pool = firecracker_state::create_connection_pool().await?;
+    let processes = reconcile::processes();
+    let vms = repo::virtual_machine::all(&pool)
+        .await?
+        .into_iter()
+        .filter(|vm| {
+            vm.status == "RUNNING"
+                || vm.status == "PAUSED"
+                || reconcile::find(vm, &processes).is_some()
+        })
+        .collect::<Vec<_>>();
+    if vms.is_empty() {
+        println!("[!] No Firecracker MicroVM is running.");
         return Ok(());
     }
 
-    run_command("pkill", &["-x", "firecracker"], true)?;
-    run_command("bash", &["-c", "rm -rf /tmp/firecracker-*.sock"], true)?;
-    println!("[+] All Firecracker processes have been stopped.");
+    for vm in &vms {
+        kill(vm);
+        teardown::release(vm).await?;
+        repo::virtual_machine::update_process(&pool, &vm.id, "STOPPED", None, None).await?;
+        println!("[+] {} has been stopped.", vm.name.cyan());
+    }
+    println!("[+] All Firecracker MicroVMs have been stopped.");
 
-    let pool = firecracker_state::create_connection_pool().await?;
-    repo::virtual_machine::update_all_status(&pool, "STOPPED").await?;
+    firewall::sync().await?;
     Ok(())
 }
@@ -0,0 +1,282 @@
+use std::{fs, path::Path};
+
+use anyhow::Result;
+use firecracker_state::{entity::virtual_machine::VirtualMachine, repo};
+use firecracker_vm::{firewall, teardown};
+use owo_colors::OwoColorize;
+
+/// A Firecracker process running on the host, with its command line.
+#[derive(Debug, Clone)]
+pub struct Process {
+    pub pid: u32,
+    pub args: Vec<String>,
+}
+
+impl Process {
+    fn program(&self) -> &str {
+        self.args
+            .first()
+            .and_then(|arg| arg.rsplit('/').next())
+            .unwrap_or_default()
+    }
+
+    fn arg(&self, flag: &str) -> Option<&str> {
+        self.args
+            .windows(2)
+            .find(|pair| pair[0] == flag)
+            .map(|pair| pair[1].as_str())
+    }
+
+    /// The jailer passes `--id` down to Firecracker, a plain Firecracker is
+    /// told apart by its API socket.
+    pub fn owns(&self, vm: &VirtualMachine) -> bool {
+        match vm.jailer_uid {
+            Some(_) => self.arg("--id") == Some(vm.name.as_str()),
+            None => self.arg("--api-sock") == Some(vm.api_socket.as_str()),
+        }
+    }
+}
+
+/// Parses `/proc/<pid>/cmdline`, keeping Firecracker and jailer processes,
+/// including the `sudo` wrapping them.
+fn parse_cmdline(pid: u32, cmdline: &[u8]) -> Option<Process> {
+    let args = cmdline
+        .split(|byte| *byte == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).to_string())
+        .collect::<Vec<_>>();
+    let is_firecracker = args.iter().take(2).any(|arg| {
+        let program = arg.rsplit('/').next().unwrap_or_default();
+        program == "firecracker" || program == "jailer"
+    });
+    is_firecracker.then_some(Process { pid, args })
+}
+
+/// Firecracker processes currently running on the host.
+pub fn processes() -> Vec<Process> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return vec![];
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| parse_cmdline(pid, &fs::read(format!("/proc/{}/cmdline", pid)).ok()?))
+        .collect()
+}
+
+/// Process of `vm` among `processes`, Firecracker itself rather than the
+/// `sudo` that started it, so killing the recorded pid stops the VM.
+pub fn find<'a>(vm: &VirtualMachine, processes: &'a [Process]) -> Option<&'a Process> {
+    let owned = processes
+        .iter()
+        .filter(|process| process.owns(vm))
+        .collect::<Vec<_>>();
+    owned
+        .iter()
+        .find(|process| process.program() != "sudo")
+        .or_else(|| owned.first())
+        .copied()
+}
+
+/// Exit code Firecracker logged when it exited, `None` when it was killed
+/// before it could.
+fn exit_code(log: &str) -> Option<i32> {
+    log.lines()
+        .rev()
+        .find_map(|line| line.split("exit_code=").nth(1))
+        .and_then(|code| {
+            code.chars()
+                .take_while(|c| c.is_ascii_digit() || *c == '-')
+                .collect::<String>()
+                .parse()
+                .ok()
+        })
+}
+
+fn log_exit_code(vm: &VirtualMachine) -> Option<i32> {
+    let logfile = firecracker_vm::log_file(&vm.name).ok()?;
+    exit_code(&fs::read_to_string(logfile).ok()?)
+}
+
+/// Whether the kernel panicked since the guest last booted: the panic
+/// reboots it, and Firecracker then exits as cleanly as on a `reboot`.
+fn guest_panicked(console: &str) -> bool {
+    console
+        .lines()
+        .rev()
+        .take_while(|line| !line.contains("Linux version"))
+        .any(|line| line.contains("Kernel panic"))
+}
+
+fn console_panicked(vm: &VirtualMachine) -> bool {
+    firecracker_vm::console::log_file(&vm.name)
+        .ok()
+        .and_then(|logfile| fs::read_to_string(logfile).ok())
+        .is_some_and(|console| guest_panicked(&console))
+}
+
+/// What the reconciler changed.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// VMs whose status changed, with their new status.
+    pub changed: Vec<(String, String, Option<i32>)>,
+    /// Firecracker processes no VM of the state database accounts for.
+    pub orphans: Vec<Process>,
+}
+
+/// Brings the status and pid of every VM in line with the Firecracker
+/// processes actually running: a VM whose process is gone has `EXITED`, or
+/// `CRASHED` unless Firecracker logged a zero exit code and the guest did not
+/// panic, and a stopped VM whose process still runs is `RUNNING` again.
+pub async fn reconcile() -> Result<Report> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let vms = repo::virtual_machine::all(&pool).await?;
+    let processes = processes();
+    let mut report = Report::default();
+
+    for vm in &vms {
+        let active = vm.status == "RUNNING" || vm.status == "PAUSED";
+        match (find(vm, &processes), active) {
+            (Some(process), true) if Some(process.pid) != vm.pid => {
+                repo::virtual_machine::update_process(
+                    &pool,
+                    &vm.id,
+                    &vm.status,
+                    Some(process.pid),
+                    None,
+                )
+                .await?;
+            }
+            (Some(process), false) => {
+                repo::virtual_machine::update_process(
+                    &pool,
+                    &vm.id,
+                    "RUNNING",
+                    Some(process.pid),
+                    None,
+                )
+                .await?;
+                report
+                    .changed
+                    .push((vm.name.clone(), "RUNNING".into(), None));
+            }
+            (None, true) => {
+                let exit_code = log_exit_code(vm);
+                let status = match exit_code {
+                    Some(0) if !console_panicked(vm) => "EXITED",
+                    _ => "CRASHED",
+                };
+                teardown::release(vm).await?;
+                repo::virtual_machine::update_process(&pool, &vm.id, status, None, exit_code)
+                    .await?;
+                report
+                    .changed
+                    .push((vm.name.clone(), status.into(), exit_code));
+            }
+            _ => {}
+        }
+    }
+
+    report.orphans = processes
+        .into_iter()
+        .filter(|process| process.program() != "sudo")
+        .filter(|process| !vms.iter().any(|vm| process.owns(vm)))
+        .collect();
+
+    if !report.changed.is_empty() {
+        firewall::sync().await?;
+    }
+    Ok(report)
+}
+
+/// Runs [`reconcile`] and reports what changed.
+pub async fn run() -> Result<()> {
+    let report = reconcile().await?;
+    for (name, status, exit_code) in &report.changed {
+        match exit_code {
+            Some(code) => println!("[!] {} {} with exit code {}.", name.cyan(), status, code),
+            None => println!("[!] {} is now {}.", name.cyan(), status),
+        }
+    }
+    for process in &report.orphans {
+        let socket = process
+            .arg("--api-sock")
+            .filter(|socket| Path::new(socket).is_absolute())
+            .unwrap_or("unknown API socket");
+        println!(
+            "[!] Firecracker process {} ({}) is not managed by fireup.",
+            process.pid, socket
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_process() {
+        let processes = [
+            parse_cmdline(
+                10,
+                b"sudo\0firecracker\0--api-sock\0/tmp/firecracker-web.sock\0",
+            ),
+            parse_cmdline(11, b"firecracker\0--api-sock\0/tmp/firecracker-web.sock\0"),
+            parse_cmdline(
+                12,
+                b"/usr/bin/jailer\0--id\0db\0--\0--api-sock\0/firecracker.socket\0",
+            ),
+            parse_cmdline(13, b"bash\0-c\0firecracker\0"),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        assert_eq!(processes.len(), 3);
+
+        let web = VirtualMachine {
+            name: "web".into(),
+            api_socket: "/tmp/firecracker-web.sock".into(),
+            pid: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(find(&web, &processes).map(|process| process.pid), Some(11));
+        assert_eq!(
+            find(&web, &processes[..1]).map(|process| process.pid),
+            Some(10)
+        );
+
+        let db = VirtualMachine {
+            name: "db".into(),
+            api_socket: "/tmp/firecracker-db.sock".into(),
+            jailer_uid: Some(10000),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, &processes).map(|process| process.pid), Some(12));
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let log = "2026-10-18T10:00:00 [anonymous-instance:main] Running Firecracker v1.12.0\n\
+                   2026-10-18T10:05:00 [anonymous-instance:main] Firecracker exiting with error. exit_code=1\n";
+        assert_eq!(exit_code(log), Some(1));
+        assert_eq!(
+            exit_code("Firecracker exiting successfully. exit_code=0"),
+            Some(0)
+        );
+        assert_eq!(exit_code("Running Firecracker v1.12.0"), None);
+    }
+
+    #[test]
+    fn test_guest_panicked() {
+        let panic = "[    0.000000] Linux version 6.1.102\n\
+                     [    1.200000] Kernel panic - not syncing: Attempted to kill init!\n\
+                     [    1.300000] Rebooting in 1 seconds..\n";
+        assert!(guest_panicked(panic));
+        assert!(!guest_panicked(&format!(
+            "{}[    0.000000] Linux version 6.1.102\nreboot: Restarting system\n",
+            panic
+        )));
+        assert!(!guest_panicked(""));
+    }
+}
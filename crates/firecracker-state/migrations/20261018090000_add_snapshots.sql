@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS snapshots (
+    id VARCHAR(255) PRIMARY KEY,
+    name VARCHAR(255) UNIQUE NOT NULL,
+    vm_id VARCHAR(255) NOT NULL,
+    vm_name VARCHAR(255) NOT NULL,
+    state_file VARCHAR(255) NOT NULL,
+    memory_file VARCHAR(255) NOT NULL,
+    vmlinux VARCHAR(255),
+    rootfs VARCHAR(255),
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
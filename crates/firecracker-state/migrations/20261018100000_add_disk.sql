@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE virtual_machines
+ADD COLUMN disk VARCHAR(255);
+
+ALTER TABLE virtual_machines
+ADD COLUMN disk_size INT;
@@ -0,0 +1,8 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS leases (
+    mac_address VARCHAR(255) PRIMARY KEY,
+    ip_address VARCHAR(255) UNIQUE NOT NULL,
+    hostname VARCHAR(255),
+    expires_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE virtual_machines
+ADD COLUMN static_ip VARCHAR(255);
+
+ALTER TABLE leases
+ADD COLUMN reserved BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE virtual_machines
+ADD COLUMN ports TEXT;
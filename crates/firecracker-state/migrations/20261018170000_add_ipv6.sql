@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE networks
+ADD COLUMN ipv6_prefix VARCHAR(255);
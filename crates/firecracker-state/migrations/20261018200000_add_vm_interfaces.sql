@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS vm_interfaces (
+    id VARCHAR(255) PRIMARY KEY,
+    vm_id VARCHAR(255) NOT NULL,
+    iface_id VARCHAR(255) NOT NULL,
+    network VARCHAR(255) NOT NULL,
+    tap VARCHAR(255) NOT NULL,
+    mac_address VARCHAR(255) UNIQUE NOT NULL,
+    ip_address VARCHAR(255),
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (vm_id, iface_id)
+);
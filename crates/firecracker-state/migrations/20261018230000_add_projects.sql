@@ -0,0 +1,61 @@
+-- Add migration script here
+-- The MicroVMs of a project share its directory, SQLite only drops the
+-- UNIQUE constraint of project_dir by rebuilding the table
+BEGIN;
+
+CREATE TABLE virtual_machines_new (
+    id VARCHAR(255) PRIMARY KEY,
+    name VARCHAR(255) UNIQUE NOT NULL,
+    status VARCHAR(255)  NOT NULL,
+    vcpu INT NOT NULL,
+    memory INT NOT NULL,
+    distro VARCHAR(255) NOT NULL,
+    pid INT,
+    mac_address VARCHAR(255) NOT NULL,
+    bridge VARCHAR(255) NOT NULL,
+    tap VARCHAR(255) NOT NULL,
+    api_socket VARCHAR(255) UNIQUE NOT NULL,
+    project_dir VARCHAR(255),
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ip_address VARCHAR(255),
+    vmlinux VARCHAR(255),
+    rootfs VARCHAR(255),
+    bootargs TEXT,
+    ssh_keys TEXT,
+    disk VARCHAR(255),
+    disk_size INT,
+    volumes TEXT,
+    jailer_uid INT,
+    static_ip VARCHAR(255),
+    network VARCHAR(255),
+    ports TEXT,
+    firewall TEXT,
+    limits TEXT,
+    exit_code INTEGER,
+    restart TEXT,
+    project VARCHAR(255)
+);
+
+INSERT INTO virtual_machines_new (
+    id, name, status, vcpu, memory, distro, pid, mac_address, bridge, tap,
+    api_socket, project_dir, created_at, updated_at, ip_address, vmlinux,
+    rootfs, bootargs, ssh_keys, disk, disk_size, volumes, jailer_uid,
+    static_ip, network, ports, firewall, limits, exit_code, restart
+)
+SELECT
+    id, name, status, vcpu, memory, distro, pid, mac_address, bridge, tap,
+    api_socket, project_dir, created_at, updated_at, ip_address, vmlinux,
+    rootfs, bootargs, ssh_keys, disk, disk_size, volumes, jailer_uid,
+    static_ip, network, ports, firewall, limits, exit_code, restart
+FROM virtual_machines;
+
+DROP TABLE virtual_machines;
+ALTER TABLE virtual_machines_new RENAME TO virtual_machines;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_unique_mac_address ON virtual_machines (mac_address);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_unique_pid ON virtual_machines (pid);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_unique_tap ON virtual_machines (tap);
+CREATE INDEX IF NOT EXISTS idx_project_dir ON virtual_machines (project_dir);
+
+COMMIT;
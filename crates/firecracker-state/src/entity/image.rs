@@ -0,0 +1,16 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Rootfs built from a Firefile with `fireup build`.
+#[derive(sqlx::FromRow, Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Image {
+    pub id: String,
+    /// Tag of the image, referenced by `image` in `fire.toml`.
+    pub name: String,
+    /// Distribution the image is based on.
+    pub distro: String,
+    /// Squashfs file of the image.
+    pub path: String,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub created_at: DateTime<Utc>,
+}
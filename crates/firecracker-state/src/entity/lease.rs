@@ -0,0 +1,15 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(sqlx::FromRow, Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Lease {
+    pub mac_address: String,
+    pub ip_address: String,
+    pub hostname: Option<String>,
+    /// Pinned to the MAC address, never handed out to another client.
+    pub reserved: bool,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub expires_at: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub updated_at: DateTime<Utc>,
+}
@@ -1 +1,7 @@
+pub mod image;
+pub mod lease;
+pub mod network;
+pub mod snapshot;
 pub mod virtual_machine;
+pub mod vm_interface;
+pub mod volume;
@@ -0,0 +1,17 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(sqlx::FromRow, Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Network {
+    pub id: String,
+    pub name: String,
+    pub bridge: String,
+    pub cidr: String,
+    pub gateway: String,
+    pub domain: String,
+    pub nat: bool,
+    /// IPv6 /64 prefix handed out through router advertisements, if enabled.
+    pub ipv6_prefix: Option<String>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub created_at: DateTime<Utc>,
+}
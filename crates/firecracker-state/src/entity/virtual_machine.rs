@@ -20,8 +20,55 @@ pub struct VirtualMachine {
     pub rootfs: Option<String>,
     pub bootargs: Option<String>,
     pub ssh_keys: Option<String>,
+    pub disk: Option<String>,
+    pub disk_size: Option<u32>,
+    pub volumes: Option<String>,
+    pub jailer_uid: Option<u32>,
+    pub static_ip: Option<String>,
+    pub network: Option<String>,
+    pub ports: Option<String>,
+    pub firewall: Option<String>,
+    pub limits: Option<String>,
+    /// Exit code of Firecracker once the VM has `EXITED` or `CRASHED`.
+    pub exit_code: Option<i32>,
+    /// Restart policy applied by `fireup serve`: `no`, `on-failure` or `always`.
+    pub restart: Option<String>,
+    /// Name of the multi-VM project the VM belongs to, see `project_dir`.
+    pub project: Option<String>,
+    /// Hash of the provisioning last applied to the VM.
+    pub provision_hash: Option<String>,
+    /// Image of `fireup build` the rootfs of the VM comes from.
+    pub image: Option<String>,
+    /// Provisioning of the VM as JSON, applied again on every start.
+    pub provision: Option<String>,
     #[serde(with = "chrono::serde::ts_seconds")]
     pub created_at: DateTime<Utc>,
     #[serde(with = "chrono::serde::ts_seconds")]
     pub updated_at: DateTime<Utc>,
 }
+
+impl VirtualMachine {
+    /// Addresses of the VM, stored comma-separated in `ip_address` with the
+    /// IPv4 address first.
+    pub fn ip_addresses(&self) -> Vec<String> {
+        self.ip_address
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .filter(|addr| !addr.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    pub fn ipv4_address(&self) -> Option<String> {
+        self.ip_addresses()
+            .into_iter()
+            .find(|addr| !addr.contains(':'))
+    }
+
+    pub fn ipv6_address(&self) -> Option<String> {
+        self.ip_addresses()
+            .into_iter()
+            .find(|addr| addr.contains(':'))
+    }
+}
@@ -0,0 +1,30 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Network interface of a MicroVM, `eth0` mirrors the tap, MAC address and
+/// network recorded on the VM itself.
+#[derive(sqlx::FromRow, Default, Debug, Clone, Serialize, Deserialize)]
+pub struct VmInterface {
+    pub id: String,
+    pub vm_id: String,
+    pub iface_id: String,
+    pub network: String,
+    pub tap: String,
+    pub mac_address: String,
+    /// IPv4 address, followed by the IPv6 address if the network has one.
+    pub ip_address: Option<String>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl VmInterface {
+    pub fn ip_addresses(&self) -> Vec<String> {
+        self.ip_address
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .filter(|addr| !addr.is_empty())
+            .map(String::from)
+            .collect()
+    }
+}
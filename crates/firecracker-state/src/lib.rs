@@ -67,6 +67,234 @@ pub async fn create_connection_pool() -> Result<Pool<Sqlite>, Error> {
         }
     }
 
+    pool.execute(include_str!(
+        "../migrations/20261018090000_add_snapshots.sql"
+    ))
+    .await?;
+
+    match pool
+        .execute(include_str!("../migrations/20261018100000_add_disk.sql"))
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => {
+            if e.to_string().contains("duplicate column name: disk")
+                || e.to_string().contains("duplicate column name: disk_size")
+            {
+            } else {
+                return Err(anyhow!("Failed to apply migration: {}", e));
+            }
+        }
+    }
+
+    match pool
+        .execute(include_str!("../migrations/20261018110000_add_volumes.sql"))
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => {
+            if e.to_string().contains("duplicate column name: volumes") {
+            } else {
+                return Err(anyhow!("Failed to apply migration: {}", e));
+            }
+        }
+    }
+
+    match pool
+        .execute(include_str!(
+            "../migrations/20261018120000_add_jailer_uid.sql"
+        ))
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => {
+            if e.to_string().contains("duplicate column name: jailer_uid") {
+            } else {
+                return Err(anyhow!("Failed to apply migration: {}", e));
+            }
+        }
+    }
+
+    pool.execute(include_str!("../migrations/20261018130000_add_leases.sql"))
+        .await?;
+
+    match pool
+        .execute(include_str!(
+            "../migrations/20261018140000_add_static_ip.sql"
+        ))
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => {
+            if e.to_string().contains("duplicate column name: static_ip")
+                || e.to_string().contains("duplicate column name: reserved")
+            {
+            } else {
+                return Err(anyhow!("Failed to apply migration: {}", e));
+            }
+        }
+    }
+
+    match pool
+        .execute(include_str!(
+            "../migrations/20261018150000_add_networks.sql"
+        ))
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => {
+            if e.to_string().contains("duplicate column name: network") {
+            } else {
+                return Err(anyhow!("Failed to apply migration: {}", e));
+            }
+        }
+    }
+
+    match pool
+        .execute(include_str!("../migrations/20261018160000_add_ports.sql"))
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => {
+            if e.to_string().contains("duplicate column name: ports") {
+            } else {
+                return Err(anyhow!("Failed to apply migration: {}", e));
+            }
+        }
+    }
+
+    match pool
+        .execute(include_str!("../migrations/20261018170000_add_ipv6.sql"))
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => {
+            if e.to_string().contains("duplicate column name: ipv6_prefix") {
+            } else {
+                return Err(anyhow!("Failed to apply migration: {}", e));
+            }
+        }
+    }
+
+    match pool
+        .execute(include_str!(
+            "../migrations/20261018180000_add_firewall.sql"
+        ))
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => {
+            if e.to_string().contains("duplicate column name: firewall") {
+            } else {
+                return Err(anyhow!("Failed to apply migration: {}", e));
+            }
+        }
+    }
+
+    match pool
+        .execute(include_str!("../migrations/20261018190000_add_limits.sql"))
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => {
+            if e.to_string().contains("duplicate column name: limits") {
+            } else {
+                return Err(anyhow!("Failed to apply migration: {}", e));
+            }
+        }
+    }
+
+    pool.execute(include_str!(
+        "../migrations/20261018200000_add_vm_interfaces.sql"
+    ))
+    .await?;
+
+    match pool
+        .execute(include_str!(
+            "../migrations/20261018210000_add_exit_code.sql"
+        ))
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => {
+            if e.to_string().contains("duplicate column name: exit_code") {
+            } else {
+                return Err(anyhow!("Failed to apply migration: {}", e));
+            }
+        }
+    }
+
+    match pool
+        .execute(include_str!("../migrations/20261018220000_add_restart.sql"))
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => {
+            if e.to_string().contains("duplicate column name: restart") {
+            } else {
+                return Err(anyhow!("Failed to apply migration: {}", e));
+            }
+        }
+    }
+
+    let (schema,): (String,) = sqlx::query_as(
+        "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'virtual_machines'",
+    )
+    .fetch_one(&pool)
+    .await?;
+    if schema.contains("project_dir VARCHAR(255) UNIQUE") {
+        pool.execute(include_str!(
+            "../migrations/20261018230000_add_projects.sql"
+        ))
+        .await?;
+    }
+
+    // after the rebuild above, which only copies the columns it knows of
+    match pool
+        .execute(include_str!(
+            "../migrations/20261018240000_add_provision_hash.sql"
+        ))
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => {
+            if e.to_string()
+                .contains("duplicate column name: provision_hash")
+            {
+            } else {
+                return Err(anyhow!("Failed to apply migration: {}", e));
+            }
+        }
+    }
+
+    match pool
+        .execute(include_str!("../migrations/20261018250000_add_images.sql"))
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => {
+            if e.to_string().contains("duplicate column name: image") {
+            } else {
+                return Err(anyhow!("Failed to apply migration: {}", e));
+            }
+        }
+    }
+
+    match pool
+        .execute(include_str!(
+            "../migrations/20261018260000_add_provision.sql"
+        ))
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => {
+            if e.to_string().contains("duplicate column name: provision") {
+            } else {
+                return Err(anyhow!("Failed to apply migration: {}", e));
+            }
+        }
+    }
+
     sqlx::query("PRAGMA journal_mode=WAL")
         .execute(&pool)
         .await?;
@@ -0,0 +1,58 @@
+use anyhow::{Context, Error};
+use sqlx::{Pool, Sqlite};
+
+use crate::entity::image::Image;
+
+pub async fn all(pool: &Pool<Sqlite>) -> Result<Vec<Image>, Error> {
+    let result: Vec<Image> = sqlx::query_as("SELECT * FROM images ORDER BY name")
+        .fetch_all(pool)
+        .await
+        .with_context(|| "Failed to fetch images")?;
+    Ok(result)
+}
+
+pub async fn find(pool: &Pool<Sqlite>, name: &str) -> Result<Option<Image>, Error> {
+    let result: Option<Image> = sqlx::query_as("SELECT * FROM images WHERE name = ? OR id = ?")
+        .bind(name)
+        .bind(name)
+        .fetch_optional(pool)
+        .await
+        .with_context(|| format!("Failed to find image with name or id '{}'", name))?;
+    Ok(result)
+}
+
+/// Records `image`, replacing the image with the same name if any.
+pub async fn save(pool: &Pool<Sqlite>, image: Image) -> Result<String, Error> {
+    let id = xid::new().to_string();
+    sqlx::query(
+        "INSERT INTO images (
+      id,
+      name,
+      distro,
+      path
+    ) VALUES (?, ?, ?, ?)
+    ON CONFLICT(name) DO UPDATE SET
+      id = excluded.id,
+      distro = excluded.distro,
+      path = excluded.path,
+      created_at = CURRENT_TIMESTAMP",
+    )
+    .bind(&id)
+    .bind(&image.name)
+    .bind(&image.distro)
+    .bind(&image.path)
+    .execute(pool)
+    .await
+    .with_context(|| format!("Failed to save image '{}'", image.name))?;
+    Ok(id)
+}
+
+pub async fn delete(pool: &Pool<Sqlite>, name: &str) -> Result<(), Error> {
+    sqlx::query("DELETE FROM images WHERE name = ? OR id = ?")
+        .bind(name)
+        .bind(name)
+        .execute(pool)
+        .await
+        .with_context(|| format!("Failed to delete image with name or id '{}'", name))?;
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use anyhow::{Context, Error};
+use chrono::Utc;
+use sqlx::{Pool, Sqlite};
+
+use crate::entity::lease::Lease;
+
+pub async fn all(pool: &Pool<Sqlite>) -> Result<Vec<Lease>, Error> {
+    let result: Vec<Lease> = sqlx::query_as("SELECT * FROM leases ORDER BY updated_at")
+        .fetch_all(pool)
+        .await
+        .with_context(|| "Failed to fetch leases")?;
+    Ok(result)
+}
+
+pub async fn find_by_mac(pool: &Pool<Sqlite>, mac_address: &str) -> Result<Option<Lease>, Error> {
+    let result: Option<Lease> = sqlx::query_as("SELECT * FROM leases WHERE mac_address = ?")
+        .bind(mac_address.to_lowercase())
+        .fetch_optional(pool)
+        .await
+        .with_context(|| format!("Failed to find lease for '{}'", mac_address))?;
+    Ok(result)
+}
+
+pub async fn find_by_ip(pool: &Pool<Sqlite>, ip_address: &str) -> Result<Option<Lease>, Error> {
+    let result: Option<Lease> = sqlx::query_as("SELECT * FROM leases WHERE ip_address = ?")
+        .bind(ip_address)
+        .fetch_optional(pool)
+        .await
+        .with_context(|| format!("Failed to find lease for '{}'", ip_address))?;
+    Ok(result)
+}
+
+/// Inserts or renews the lease of `lease.mac_address`, replacing any expired
+/// lease that was holding the same IP address.
+pub async fn upsert(pool: &Pool<Sqlite>, lease: Lease) -> Result<(), Error> {
+    let mac_address = lease.mac_address.to_lowercase();
+    sqlx::query("DELETE FROM leases WHERE ip_address = ? AND mac_address != ?")
+        .bind(&lease.ip_address)
+        .bind(&mac_address)
+        .execute(pool)
+        .await
+        .with_context(|| format!("Failed to free IP address '{}'", lease.ip_address))?;
+
+    sqlx::query(
+        "INSERT INTO leases (mac_address, ip_address, hostname, reserved, expires_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?)
+         ON CONFLICT(mac_address) DO UPDATE SET
+            ip_address = excluded.ip_address,
+            hostname = excluded.hostname,
+            expires_at = excluded.expires_at,
+            updated_at = excluded.updated_at",
+    )
+    .bind(&mac_address)
+    .bind(&lease.ip_address)
+    .bind(&lease.hostname)
+    .bind(lease.reserved)
+    .bind(lease.expires_at)
+    .bind(lease.updated_at)
+    .execute(pool)
+    .await
+    .with_context(|| format!("Failed to save lease for '{}'", mac_address))?;
+    Ok(())
+}
+
+/// Pins `ip_address` to `mac_address` so the DHCP server always hands it out
+/// to that client and to no other.
+pub async fn reserve(
+    pool: &Pool<Sqlite>,
+    mac_address: &str,
+    ip_address: &str,
+    hostname: &str,
+) -> Result<(), Error> {
+    let now = Utc::now();
+    upsert(
+        pool,
+        Lease {
+            mac_address: mac_address.into(),
+            ip_address: ip_address.into(),
+            hostname: Some(hostname.into()),
+            reserved: true,
+            expires_at: now,
+            updated_at: now,
+        },
+    )
+    .await?;
+    sqlx::query("UPDATE leases SET reserved = 1 WHERE mac_address = ?")
+        .bind(mac_address.to_lowercase())
+        .execute(pool)
+        .await
+        .with_context(|| format!("Failed to reserve IP address '{}'", ip_address))?;
+    Ok(())
+}
+
+pub async fn unreserve(pool: &Pool<Sqlite>, mac_address: &str) -> Result<(), Error> {
+    sqlx::query("UPDATE leases SET reserved = 0 WHERE mac_address = ?")
+        .bind(mac_address.to_lowercase())
+        .execute(pool)
+        .await
+        .with_context(|| format!("Failed to release reservation for '{}'", mac_address))?;
+    Ok(())
+}
+
+/// Gives back the lease of `mac_address`, on DHCPRELEASE or DHCPDECLINE:
+/// a reserved lease expires but stays pinned, any other one is deleted.
+pub async fn release(pool: &Pool<Sqlite>, mac_address: &str) -> Result<(), Error> {
+    let mac_address = mac_address.to_lowercase();
+    sqlx::query("DELETE FROM leases WHERE mac_address = ? AND reserved = 0")
+        .bind(&mac_address)
+        .execute(pool)
+        .await
+        .with_context(|| format!("Failed to delete lease for '{}'", mac_address))?;
+    let now = Utc::now();
+    sqlx::query(
+        "UPDATE leases SET expires_at = ?, updated_at = ? WHERE mac_address = ? AND reserved = 1",
+    )
+    .bind(now)
+    .bind(now)
+    .bind(&mac_address)
+    .execute(pool)
+    .await
+    .with_context(|| format!("Failed to expire lease for '{}'", mac_address))?;
+    Ok(())
+}
+
+pub async fn delete_by_mac(pool: &Pool<Sqlite>, mac_address: &str) -> Result<(), Error> {
+    sqlx::query("DELETE FROM leases WHERE mac_address = ?")
+        .bind(mac_address.to_lowercase())
+        .execute(pool)
+        .await
+        .with_context(|| format!("Failed to delete lease for '{}'", mac_address))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{sqlite::SqlitePoolOptions, Executor};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_release_keeps_reservation() -> Result<(), Error> {
+        // an in-memory database lives as long as its only connection
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await?;
+        pool.execute(include_str!("../../migrations/20250804092946_init.sql"))
+            .await?;
+        pool.execute(include_str!(
+            "../../migrations/20261018130000_add_leases.sql"
+        ))
+        .await?;
+        pool.execute(include_str!(
+            "../../migrations/20261018140000_add_static_ip.sql"
+        ))
+        .await?;
+
+        reserve(&pool, "02:FC:00:00:00:01", "172.16.0.10", "web").await?;
+        let now = Utc::now();
+        upsert(
+            &pool,
+            Lease {
+                mac_address: "02:fc:00:00:00:02".into(),
+                ip_address: "172.16.0.11".into(),
+                hostname: None,
+                reserved: false,
+                expires_at: now + chrono::Duration::hours(1),
+                updated_at: now,
+            },
+        )
+        .await?;
+
+        release(&pool, "02:fc:00:00:00:01").await?;
+        release(&pool, "02:fc:00:00:00:02").await?;
+
+        let lease = find_by_mac(&pool, "02:fc:00:00:00:01").await?.unwrap();
+        assert!(lease.reserved);
+        assert_eq!(lease.ip_address, "172.16.0.10");
+        assert!(lease.expires_at <= Utc::now());
+        assert!(find_by_mac(&pool, "02:fc:00:00:00:02").await?.is_none());
+        Ok(())
+    }
+}
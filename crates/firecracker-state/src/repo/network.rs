@@ -0,0 +1,61 @@
+use anyhow::{Context, Error};
+use sqlx::{Pool, Sqlite};
+
+use crate::entity::network::Network;
+
+pub async fn all(pool: &Pool<Sqlite>) -> Result<Vec<Network>, Error> {
+    let result: Vec<Network> = sqlx::query_as("SELECT * FROM networks ORDER BY created_at")
+        .fetch_all(pool)
+        .await
+        .with_context(|| "Failed to fetch networks")?;
+    Ok(result)
+}
+
+pub async fn find(pool: &Pool<Sqlite>, name: &str) -> Result<Option<Network>, Error> {
+    let result: Option<Network> = sqlx::query_as("SELECT * FROM networks WHERE name = ? OR id = ?")
+        .bind(name)
+        .bind(name)
+        .fetch_optional(pool)
+        .await
+        .with_context(|| format!("Failed to find network with name or id '{}'", name))?;
+    Ok(result)
+}
+
+pub async fn find_by_bridge(pool: &Pool<Sqlite>, bridge: &str) -> Result<Option<Network>, Error> {
+    let result: Option<Network> = sqlx::query_as("SELECT * FROM networks WHERE bridge = ?")
+        .bind(bridge)
+        .fetch_optional(pool)
+        .await
+        .with_context(|| format!("Failed to find network with bridge '{}'", bridge))?;
+    Ok(result)
+}
+
+pub async fn create(pool: &Pool<Sqlite>, network: Network) -> Result<String, Error> {
+    let id = xid::new().to_string();
+    sqlx::query(
+        "INSERT INTO networks (id, name, bridge, cidr, gateway, domain, nat, ipv6_prefix)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&id)
+    .bind(&network.name)
+    .bind(&network.bridge)
+    .bind(&network.cidr)
+    .bind(&network.gateway)
+    .bind(&network.domain)
+    .bind(network.nat)
+    .bind(&network.ipv6_prefix)
+    .execute(pool)
+    .await
+    .with_context(|| format!("Failed to create network '{}'", network.name))?;
+    Ok(id)
+}
+
+pub async fn delete(pool: &Pool<Sqlite>, name: &str) -> Result<(), Error> {
+    sqlx::query("DELETE FROM networks WHERE name = ? OR id = ?")
+        .bind(name)
+        .bind(name)
+        .execute(pool)
+        .await
+        .with_context(|| format!("Failed to delete network with name or id '{}'", name))?;
+    Ok(())
+}
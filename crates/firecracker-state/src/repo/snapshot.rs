@@ -0,0 +1,71 @@
+use anyhow::{Context, Error};
+use sqlx::{Pool, Sqlite};
+
+use crate::entity::snapshot::Snapshot;
+
+pub async fn all(pool: &Pool<Sqlite>) -> Result<Vec<Snapshot>, Error> {
+    let result: Vec<Snapshot> = sqlx::query_as("SELECT * FROM snapshots ORDER BY created_at")
+        .fetch_all(pool)
+        .await
+        .with_context(|| "Failed to fetch snapshots")?;
+    Ok(result)
+}
+
+pub async fn find(pool: &Pool<Sqlite>, name: &str) -> Result<Option<Snapshot>, Error> {
+    let result: Option<Snapshot> =
+        sqlx::query_as("SELECT * FROM snapshots WHERE name = ? OR id = ?")
+            .bind(name)
+            .bind(name)
+            .fetch_optional(pool)
+            .await
+            .with_context(|| format!("Failed to find snapshot with name or id '{}'", name))?;
+    Ok(result)
+}
+
+pub async fn find_by_vm(pool: &Pool<Sqlite>, vm_id: &str) -> Result<Vec<Snapshot>, Error> {
+    let result: Vec<Snapshot> =
+        sqlx::query_as("SELECT * FROM snapshots WHERE vm_id = ? ORDER BY created_at")
+            .bind(vm_id)
+            .fetch_all(pool)
+            .await
+            .with_context(|| format!("Failed to fetch snapshots of VM '{}'", vm_id))?;
+    Ok(result)
+}
+
+pub async fn create(pool: &Pool<Sqlite>, snapshot: Snapshot) -> Result<String, Error> {
+    let id = xid::new().to_string();
+    sqlx::query(
+        "INSERT INTO snapshots (
+      id,
+      name,
+      vm_id,
+      vm_name,
+      state_file,
+      memory_file,
+      vmlinux,
+      rootfs
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&id)
+    .bind(&snapshot.name)
+    .bind(&snapshot.vm_id)
+    .bind(&snapshot.vm_name)
+    .bind(&snapshot.state_file)
+    .bind(&snapshot.memory_file)
+    .bind(&snapshot.vmlinux)
+    .bind(&snapshot.rootfs)
+    .execute(pool)
+    .await
+    .with_context(|| format!("Failed to create snapshot '{}'", snapshot.name))?;
+    Ok(id)
+}
+
+pub async fn delete(pool: &Pool<Sqlite>, name: &str) -> Result<(), Error> {
+    sqlx::query("DELETE FROM snapshots WHERE name = ? OR id = ?")
+        .bind(name)
+        .bind(name)
+        .execute(pool)
+        .await
+        .with_context(|| format!("Failed to delete snapshot with name or id '{}'", name))?;
+    Ok(())
+}
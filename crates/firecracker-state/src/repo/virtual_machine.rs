@@ -31,7 +31,7 @@ pub async fn find_by_project_dir(
     path: &str,
 ) -> Result<Option<VirtualMachine>, Error> {
     let result: Option<VirtualMachine> =
-        sqlx::query_as("SELECT * FROM virtual_machines WHERE project_dir = ?")
+        sqlx::query_as("SELECT * FROM virtual_machines WHERE project_dir = ? AND project IS NULL")
             .bind(path)
             .fetch_optional(pool)
             .await
@@ -41,6 +41,26 @@ pub async fn find_by_project_dir(
     Ok(result)
 }
 
+/// The VMs of the multi-VM project in the directory `path`.
+pub async fn find_all_by_project_dir(
+    pool: &Pool<Sqlite>,
+    path: &str,
+) -> Result<Vec<VirtualMachine>, Error> {
+    let result: Vec<VirtualMachine> = sqlx::query_as(
+        "SELECT * FROM virtual_machines WHERE project_dir = ? AND project IS NOT NULL",
+    )
+    .bind(path)
+    .fetch_all(pool)
+    .await
+    .with_context(|| {
+        format!(
+            "Failed to find virtual machines with project_dir '{}'",
+            path
+        )
+    })?;
+    Ok(result)
+}
+
 pub async fn create(pool: &Pool<Sqlite>, vm: VirtualMachine) -> Result<String, Error> {
     let id = xid::new().to_string();
     let project_dir = match Path::exists(Path::new("fire.toml")) {
@@ -65,8 +85,21 @@ pub async fn create(pool: &Pool<Sqlite>, vm: VirtualMachine) -> Result<String, E
       vmlinux,
       rootfs,
       bootargs,
-      ssh_keys
-    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+      ssh_keys,
+      disk,
+      disk_size,
+      volumes,
+      jailer_uid,
+      static_ip,
+      network,
+      ports,
+      firewall,
+      limits,
+      restart,
+      project,
+      image,
+      provision
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
     )
     .bind(&vm.name)
     .bind(&id)
@@ -85,6 +118,19 @@ pub async fn create(pool: &Pool<Sqlite>, vm: VirtualMachine) -> Result<String, E
     .bind(&vm.rootfs)
     .bind(&vm.bootargs)
     .bind(&vm.ssh_keys)
+    .bind(&vm.disk)
+    .bind(vm.disk_size)
+    .bind(&vm.volumes)
+    .bind(vm.jailer_uid)
+    .bind(&vm.static_ip)
+    .bind(&vm.network)
+    .bind(&vm.ports)
+    .bind(&vm.firewall)
+    .bind(&vm.limits)
+    .bind(&vm.restart)
+    .bind(&vm.project)
+    .bind(&vm.image)
+    .bind(&vm.provision)
     .execute(pool)
     .await
     .with_context(|| "Failed to create virtual machine")?;
@@ -124,6 +170,20 @@ pub async fn update(pool: &Pool<Sqlite>, id: &str, vm: VirtualMachine) -> Result
             vmlinux = ?,
             rootfs = ?,
             bootargs = ?,
+            disk = ?,
+            disk_size = ?,
+            volumes = ?,
+            jailer_uid = ?,
+            static_ip = ?,
+            network = ?,
+            ports = ?,
+            firewall = ?,
+            limits = ?,
+            exit_code = ?,
+            restart = ?,
+            project = ?,
+            image = ?,
+            provision = ?,
             updated_at = CURRENT_TIMESTAMP
         WHERE id = ?"#,
     )
@@ -141,6 +201,20 @@ pub async fn update(pool: &Pool<Sqlite>, id: &str, vm: VirtualMachine) -> Result
     .bind(&vm.vmlinux)
     .bind(&vm.rootfs)
     .bind(&vm.bootargs)
+    .bind(&vm.disk)
+    .bind(vm.disk_size)
+    .bind(&vm.volumes)
+    .bind(vm.jailer_uid)
+    .bind(&vm.static_ip)
+    .bind(&vm.network)
+    .bind(&vm.ports)
+    .bind(&vm.firewall)
+    .bind(&vm.limits)
+    .bind(vm.exit_code)
+    .bind(&vm.restart)
+    .bind(&vm.project)
+    .bind(&vm.image)
+    .bind(&vm.provision)
     .bind(id)
     .execute(pool)
     .await
@@ -169,6 +243,102 @@ pub async fn update_status(pool: &Pool<Sqlite>, name: &str, status: &str) -> Res
     Ok(())
 }
 
+/// Records the provisioning applied to the VM `id`.
+pub async fn update_provision_hash(
+    pool: &Pool<Sqlite>,
+    id: &str,
+    provision_hash: &str,
+) -> Result<(), Error> {
+    sqlx::query("UPDATE virtual_machines SET provision_hash = ? WHERE id = ?")
+        .bind(provision_hash)
+        .bind(id)
+        .execute(pool)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to update provision hash of virtual machine with id '{}'",
+                id
+            )
+        })?;
+    Ok(())
+}
+
+/// Records what became of the Firecracker process of the VM `id`.
+pub async fn update_process(
+    pool: &Pool<Sqlite>,
+    id: &str,
+    status: &str,
+    pid: Option<u32>,
+    exit_code: Option<i32>,
+) -> Result<(), Error> {
+    sqlx::query(
+        "UPDATE virtual_machines
+            SET status = ?, pid = ?, exit_code = ?, updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?",
+    )
+    .bind(status)
+    .bind(pid)
+    .bind(exit_code)
+    .bind(id)
+    .execute(pool)
+    .await
+    .with_context(|| {
+        format!(
+            "Failed to update process of virtual machine with id '{}'",
+            id
+        )
+    })?;
+    Ok(())
+}
+
+pub async fn update_limits(
+    pool: &Pool<Sqlite>,
+    id: &str,
+    limits: Option<String>,
+) -> Result<(), Error> {
+    sqlx::query(
+        "UPDATE virtual_machines SET limits = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+    )
+    .bind(limits)
+    .bind(id)
+    .execute(pool)
+    .await
+    .with_context(|| {
+        format!(
+            "Failed to update limits for virtual machine with id '{}'",
+            id
+        )
+    })?;
+    Ok(())
+}
+
+pub async fn update_ip_address(
+    pool: &Pool<Sqlite>,
+    mac_address: &str,
+    ip_address: &str,
+) -> Result<(), Error> {
+    // only the IPv4 address comes from DHCP, keep the other addresses
+    sqlx::query(
+        "UPDATE virtual_machines
+            SET ip_address = CASE WHEN instr(ip_address, ',') > 0
+                THEN ? || substr(ip_address, instr(ip_address, ','))
+                ELSE ? END
+        WHERE lower(mac_address) = ?",
+    )
+    .bind(ip_address)
+    .bind(ip_address)
+    .bind(mac_address.to_lowercase())
+    .execute(pool)
+    .await
+    .with_context(|| {
+        format!(
+            "Failed to update IP address for virtual machine with MAC '{}'",
+            mac_address
+        )
+    })?;
+    Ok(())
+}
+
 pub async fn update_all_status(pool: &Pool<Sqlite>, status: &str) -> Result<(), Error> {
     sqlx::query("UPDATE virtual_machines SET status = ?")
         .bind(status)
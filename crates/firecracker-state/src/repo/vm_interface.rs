@@ -0,0 +1,84 @@
+use anyhow::{Context, Error};
+use sqlx::{Pool, Sqlite};
+
+use crate::entity::vm_interface::VmInterface;
+
+pub async fn all(pool: &Pool<Sqlite>) -> Result<Vec<VmInterface>, Error> {
+    let result: Vec<VmInterface> =
+        sqlx::query_as("SELECT * FROM vm_interfaces ORDER BY vm_id, iface_id")
+            .fetch_all(pool)
+            .await
+            .with_context(|| "Failed to fetch network interfaces")?;
+    Ok(result)
+}
+
+pub async fn find_by_vm(pool: &Pool<Sqlite>, vm_id: &str) -> Result<Vec<VmInterface>, Error> {
+    let result: Vec<VmInterface> =
+        sqlx::query_as("SELECT * FROM vm_interfaces WHERE vm_id = ? ORDER BY iface_id")
+            .bind(vm_id)
+            .fetch_all(pool)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to fetch network interfaces of virtual machine '{}'",
+                    vm_id
+                )
+            })?;
+    Ok(result)
+}
+
+/// Replaces the network interfaces of the VM `vm_id`.
+pub async fn replace(
+    pool: &Pool<Sqlite>,
+    vm_id: &str,
+    interfaces: &[VmInterface],
+) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM vm_interfaces WHERE vm_id = ?")
+        .bind(vm_id)
+        .execute(&mut *tx)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to delete network interfaces of virtual machine '{}'",
+                vm_id
+            )
+        })?;
+    for interface in interfaces {
+        sqlx::query(
+            "INSERT INTO vm_interfaces (id, vm_id, iface_id, network, tap, mac_address, ip_address)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(xid::new().to_string())
+        .bind(vm_id)
+        .bind(&interface.iface_id)
+        .bind(&interface.network)
+        .bind(&interface.tap)
+        .bind(interface.mac_address.to_lowercase())
+        .bind(&interface.ip_address)
+        .execute(&mut *tx)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to create network interface '{}' of virtual machine '{}'",
+                interface.iface_id, vm_id
+            )
+        })?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn delete_by_vm(pool: &Pool<Sqlite>, vm_id: &str) -> Result<(), Error> {
+    sqlx::query("DELETE FROM vm_interfaces WHERE vm_id = ?")
+        .bind(vm_id)
+        .execute(pool)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to delete network interfaces of virtual machine '{}'",
+                vm_id
+            )
+        })?;
+    Ok(())
+}
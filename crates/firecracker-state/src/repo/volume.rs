@@ -0,0 +1,45 @@
+use anyhow::{Context, Error};
+use sqlx::{Pool, Sqlite};
+
+use crate::entity::volume::Volume;
+
+pub async fn all(pool: &Pool<Sqlite>) -> Result<Vec<Volume>, Error> {
+    let result: Vec<Volume> = sqlx::query_as("SELECT * FROM volumes ORDER BY created_at")
+        .fetch_all(pool)
+        .await
+        .with_context(|| "Failed to fetch volumes")?;
+    Ok(result)
+}
+
+pub async fn find(pool: &Pool<Sqlite>, name: &str) -> Result<Option<Volume>, Error> {
+    let result: Option<Volume> = sqlx::query_as("SELECT * FROM volumes WHERE name = ? OR id = ?")
+        .bind(name)
+        .bind(name)
+        .fetch_optional(pool)
+        .await
+        .with_context(|| format!("Failed to find volume with name or id '{}'", name))?;
+    Ok(result)
+}
+
+pub async fn create(pool: &Pool<Sqlite>, volume: Volume) -> Result<String, Error> {
+    let id = xid::new().to_string();
+    sqlx::query("INSERT INTO volumes (id, name, path, size) VALUES (?, ?, ?, ?)")
+        .bind(&id)
+        .bind(&volume.name)
+        .bind(&volume.path)
+        .bind(volume.size)
+        .execute(pool)
+        .await
+        .with_context(|| format!("Failed to create volume '{}'", volume.name))?;
+    Ok(id)
+}
+
+pub async fn delete(pool: &Pool<Sqlite>, name: &str) -> Result<(), Error> {
+    sqlx::query("DELETE FROM volumes WHERE name = ? OR id = ?")
+        .bind(name)
+        .bind(name)
+        .execute(pool)
+        .await
+        .with_context(|| format!("Failed to delete volume with name or id '{}'", name))?;
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use std::{fs, path::Path};
+
+use anyhow::Error;
+use firecracker_state::repo;
+use owo_colors::OwoColorize;
+
+use crate::date::format_duration_ago;
+
+pub async fn build(file: &str, tag: &str) -> Result<(), Error> {
+    let image = firecracker_vm::image::build(Path::new(file), tag).await?;
+
+    println!(
+        "[✓] Image {} built: {}",
+        image.name.bright_green(),
+        image.path.cyan()
+    );
+    println!("Use it in fire.toml with:");
+    println!("{}", format!("image = \"{}\"", image.name).bright_green());
+
+    Ok(())
+}
+
+pub async fn list_images() -> Result<(), Error> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let images = repo::image::all(&pool).await?;
+
+    if images.is_empty() {
+        println!("No images found.");
+        return Ok(());
+    }
+
+    let sizes = images
+        .iter()
+        .map(|image| match fs::metadata(&image.path) {
+            Ok(metadata) => format!("{} MiB", metadata.len() / (1024 * 1024)),
+            Err(_) => "missing".into(),
+        })
+        .collect::<Vec<_>>();
+
+    let name_length = images
+        .iter()
+        .map(|i| i.name.len())
+        .max()
+        .unwrap_or(10)
+        .max(10)
+        + 2;
+    let distro_length = images
+        .iter()
+        .map(|i| i.distro.len())
+        .max()
+        .unwrap_or(10)
+        .max(10)
+        + 2;
+    let size_length = sizes.iter().map(|s| s.len()).max().unwrap_or(10).max(10) + 2;
+
+    println!(
+        "{:<name_length$} {:<distro_length$} {:<size_length$} CREATED",
+        "NAME", "DISTRO", "SIZE"
+    );
+    for (image, size) in images.iter().zip(sizes) {
+        println!(
+            "{:<name_length$} {:<distro_length$} {:<size_length$} {}",
+            image.name,
+            image.distro,
+            size,
+            format_duration_ago(image.created_at),
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use anyhow::Error;
+use firecracker_state::repo;
+use firecracker_vm::console;
+use owo_colors::OwoColorize;
+
+pub async fn attach(name: &str, detach_keys: &str) -> Result<(), Error> {
+    let keys = console::parse_detach_keys(detach_keys)?;
+    let pool = firecracker_state::create_connection_pool().await?;
+    let vm = repo::virtual_machine::find(&pool, name).await?;
+
+    if vm.is_none() {
+        println!("[-] MicroVM '{}' not found.", name);
+        std::process::exit(1);
+    }
+    let vm = vm.unwrap();
+
+    if !firecracker_process::vm_is_running(name).await? {
+        println!("[-] MicroVM '{}' is not running.", name);
+        let start_cmd = format!("fireup start {}", name);
+        println!("    Start it with {}", start_cmd.cyan());
+        std::process::exit(1);
+    }
+
+    println!(
+        "[+] Attached to the console of {}, detach with {}.",
+        vm.name.cyan(),
+        detach_keys.cyan()
+    );
+    match console::attach(&vm.api_socket, &keys)? {
+        true => println!("\n[✓] Detached from {}.", vm.name.cyan()),
+        false => println!("\n[!] Console of {} closed.", vm.name.cyan()),
+    }
+    Ok(())
+}
@@ -1,4 +1,7 @@
+use std::path::Path;
+
 use anyhow::Error;
+use firecracker_vm::agent;
 use owo_colors::OwoColorize;
 
 use crate::{command::run_command_with_stdout_inherit, ssh::get_private_key_path};
@@ -22,6 +25,7 @@ pub async fn cp(from: &str, to: &str) -> Result<(), Error> {
         println!("[-] MicroVM '{}' not found.", vm_name);
         std::process::exit(1);
     }
+    let vm = vm.unwrap();
 
     if !firecracker_process::vm_is_running(vm_name).await? {
         println!("[-] MicroVM '{}' is not running.", vm_name);
@@ -30,7 +34,28 @@ pub async fn cp(from: &str, to: &str) -> Result<(), Error> {
         std::process::exit(1);
     }
 
-    let guest_ip = format!("{}.firecracker", vm_name);
+    if agent::ping(&vm.api_socket).is_ok() {
+        let copied = match from.split_once(':') {
+            Some((_, remote)) => agent::copy_from(&vm.api_socket, remote, Path::new(to)),
+            None => agent::copy_to(
+                &vm.api_socket,
+                Path::new(from),
+                to.split_once(':')
+                    .map(|(_, remote)| remote)
+                    .unwrap_or_default(),
+            ),
+        };
+        match copied {
+            Ok(()) => return Ok(()),
+            // directories are copied recursively by scp
+            Err(e) => println!("[!] {}, falling back to scp...", e),
+        }
+    }
+
+    let guest_ip = match vm.ipv4_address() {
+        Some(ip) => ip,
+        None => firecracker_vm::network::fqdn(vm_name).await?,
+    };
     let key_path = get_private_key_path()?;
 
     let scp_args = if from.contains(':') {
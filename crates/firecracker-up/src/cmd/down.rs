@@ -1,6 +1,40 @@
 use anyhow::Error;
+use firecracker_state::repo;
+use owo_colors::OwoColorize;
+
+use crate::project;
 
 pub async fn down() -> Result<(), Error> {
-    firecracker_process::stop(None).await?;
+    let project = match project::load()? {
+        Some(project) => project,
+        None => {
+            firecracker_process::stop(None).await?;
+            return Ok(());
+        }
+    };
+
+    // dependents stop first, then the MicroVMs no longer in fire.toml
+    let mut names = project
+        .config
+        .services()?
+        .into_iter()
+        .rev()
+        .map(|service| service.name)
+        .collect::<Vec<_>>();
+    let pool = firecracker_state::create_connection_pool().await?;
+    for vm in repo::virtual_machine::find_all_by_project_dir(&pool, &project.dir).await? {
+        if !names.contains(&vm.name) {
+            names.push(vm.name);
+        }
+    }
+
+    for name in names {
+        if firecracker_process::vm_is_running(&name).await?
+            || firecracker_process::vm_is_paused(&name).await?
+        {
+            firecracker_process::stop(Some(name)).await?;
+        }
+    }
+    println!("[✓] Project {} is down.", project.name.bright_green());
     Ok(())
 }
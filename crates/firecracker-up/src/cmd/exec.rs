@@ -1,5 +1,6 @@
 use anyhow::{Error, Result};
 use firecracker_state::repo;
+use firecracker_vm::agent;
 use owo_colors::OwoColorize;
 
 use crate::{command::run_ssh_command, ssh::get_private_key_path};
@@ -12,6 +13,7 @@ pub async fn exec(name: &str, args: Vec<String>) -> Result<(), Error> {
         println!("[-] MicroVM '{}' not found.", name);
         std::process::exit(1);
     }
+    let vm = vm.unwrap();
 
     if !firecracker_process::vm_is_running(name).await? {
         println!("[-] MicroVM '{}' is not running.", name);
@@ -20,7 +22,19 @@ pub async fn exec(name: &str, args: Vec<String>) -> Result<(), Error> {
         std::process::exit(1);
     }
 
-    let guest_ip = format!("{}.firecracker", name);
+    if agent::ping(&vm.api_socket).is_ok() {
+        let code = agent::exec(&vm.api_socket, Some(&args.join(" ")), false)?;
+        if code != 0 {
+            std::process::exit(code);
+        }
+        return Ok(());
+    }
+
+    // images prepared without the agent are reached over SSH
+    let guest_ip = match vm.ipv4_address() {
+        Some(ip) => ip,
+        None => firecracker_vm::network::fqdn(name).await?,
+    };
     run_ssh_command(&get_private_key_path()?, &guest_ip, args.join(" ").as_str())?;
 
     Ok(())
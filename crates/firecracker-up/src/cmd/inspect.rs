@@ -3,6 +3,7 @@ use std::process;
 use anyhow::Error;
 use colored_json::ToColoredJson;
 use firecracker_state::repo;
+use firecracker_vm::{firewall, limits, volume};
 use serde_json::json;
 
 pub async fn inspect_microvm(id: &str) -> Result<(), Error> {
@@ -14,10 +15,12 @@ pub async fn inspect_microvm(id: &str) -> Result<(), Error> {
     }
 
     let vm = vm.unwrap();
+    let interfaces = repo::vm_interface::find_by_vm(&pool, &vm.id).await?;
     let vm = json!({
         "id": vm.id,
         "name": vm.name,
-        "image": vm.distro,
+        "image": vm.image.as_deref().unwrap_or(&vm.distro),
+        "distro": vm.distro,
         "vcpu": vm.vcpu,
         "memory": vm.memory,
         "vmlinux": vm.vmlinux,
@@ -30,8 +33,32 @@ pub async fn inspect_microvm(id: &str) -> Result<(), Error> {
         "ssh_keys": vm.ssh_keys,
         "status": vm.status,
         "pid": vm.pid,
-        "ip_address": vm.ip_address,
+        "exit_code": vm.exit_code,
+        "ip_address": vm.ip_addresses(),
         "project_dir": vm.project_dir,
+        "provision_hash": vm.provision_hash,
+        "disk": vm.disk,
+        "disk_size": vm.disk_size,
+        "jailer_uid": vm.jailer_uid,
+        "static_ip": vm.static_ip,
+        "network": vm.network,
+        "interfaces": interfaces
+            .iter()
+            .map(|interface| json!({
+                "iface_id": interface.iface_id,
+                "network": interface.network,
+                "tap": interface.tap,
+                "mac_address": interface.mac_address,
+                "ip_address": interface.ip_addresses(),
+            }))
+            .collect::<Vec<_>>(),
+        "ports": vm
+            .ports
+            .map(|ports| ports.split(',').map(|s| s.to_string()).collect::<Vec<_>>()),
+        "firewall": vm.firewall.as_deref().and_then(firewall::from_column),
+        "limits": vm.limits.as_deref().and_then(limits::from_column),
+        "restart": vm.restart,
+        "volumes": vm.volumes.map(|volumes| volume::from_column(&volumes)),
         "created_at": vm.created_at.to_rfc3339(),
         "updated_at": vm.updated_at.to_rfc3339(),
     });
@@ -1,18 +1,58 @@
+use std::time::Duration;
+
 use anyhow::Error;
+use chrono::Local;
+use firecracker_state::repo;
+use firecracker_vm::logs::{self, Line, Logs, Source};
+use owo_colors::OwoColorize;
+
+fn print_line(line: &Line, labelled: bool) {
+    match labelled {
+        true => println!("{:<7} | {}", line.source.cyan(), line.text),
+        false => println!("{}", line.text),
+    }
+}
+
+pub async fn logs(
+    name: &str,
+    source: Option<&str>,
+    follow: bool,
+    since: Option<&str>,
+    tail: Option<usize>,
+) -> Result<(), Error> {
+    let sources = match source {
+        Some(source) => vec![source.parse::<Source>()?],
+        None => Source::ALL.to_vec(),
+    };
+    let since = since
+        .map(|since| logs::parse_since(since, Local::now().naive_local()))
+        .transpose()?;
+
+    let pool = firecracker_state::create_connection_pool().await?;
+    let vm = repo::virtual_machine::find(&pool, name).await?;
+    if vm.is_none() {
+        println!("[-] MicroVM '{}' not found.", name);
+        std::process::exit(1);
+    }
+    let vm = vm.unwrap();
 
-use crate::command::run_command;
+    let labelled = sources.len() > 1;
+    let mut logs = Logs::open(&vm.name, &sources)?;
+    let mut lines = logs.next_lines()?;
+    if !follow {
+        lines.extend(logs.partial_lines());
+    }
+    for line in logs::select(lines, since, tail) {
+        print_line(&line, labelled);
+    }
 
-pub fn logs(follow: bool) -> Result<(), Error> {
-    let app_dir = crate::config::get_config_dir()?;
-    let logfile = format!("{}/firecracker.log", app_dir);
-    let logfile = logfile.as_str();
-    run_command(
-        "tail",
-        &match follow {
-            true => vec!["-f", logfile],
-            false => vec![logfile],
-        },
-        true,
-    )?;
+    if follow {
+        loop {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            for line in logs.next_lines()? {
+                print_line(&line, labelled);
+            }
+        }
+    }
     Ok(())
 }
@@ -1,15 +1,28 @@
+pub mod build;
+pub mod console;
 pub mod cp;
 pub mod down;
 pub mod exec;
 pub mod init;
 pub mod inspect;
 pub mod logs;
+pub mod netd;
+pub mod network;
+pub mod pause;
+pub mod port;
+pub mod prune;
 pub mod ps;
 pub mod reset;
+pub mod restore;
+pub mod resume;
 pub mod rm;
+pub mod serial;
 pub mod serve;
+pub mod snapshot;
 pub mod ssh;
 pub mod start;
 pub mod status;
 pub mod stop;
 pub mod up;
+pub mod update;
+pub mod volume;
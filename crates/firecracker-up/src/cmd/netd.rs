@@ -0,0 +1,6 @@
+use anyhow::Error;
+
+pub async fn netd() -> Result<(), Error> {
+    firecracker_vm::netd::run().await?;
+    Ok(())
+}
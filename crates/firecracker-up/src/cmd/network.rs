@@ -0,0 +1,110 @@
+use anyhow::Error;
+use colored_json::ToColoredJson;
+use firecracker_vm::network::{self, NetworkOptions};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use crate::date::format_duration_ago;
+
+pub async fn create_network(name: &str, options: NetworkOptions) -> Result<(), Error> {
+    let network = network::create(name, options).await?;
+    let subnets = [Some(network.cidr.clone()), network.ipv6_prefix.clone()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(", ");
+    println!(
+        "[✓] Network {} created ({}, *.{}) 🎉",
+        network.name.bright_green(),
+        subnets,
+        network.domain
+    );
+    Ok(())
+}
+
+pub async fn list_networks() -> Result<(), Error> {
+    let networks = network::all().await?;
+
+    let mut rows = vec![];
+    for n in networks {
+        let vms = network::vms(&n.name).await?.len();
+        let ipv6 = n.ipv6_prefix.unwrap_or_else(|| "-".into());
+        rows.push((
+            n.name,
+            n.bridge,
+            n.cidr,
+            ipv6,
+            n.domain,
+            n.nat,
+            vms,
+            n.created_at,
+        ));
+    }
+
+    let name_length = rows.iter().map(|r| r.0.len()).max().unwrap_or(10).max(10) + 2;
+    let bridge_length = rows.iter().map(|r| r.1.len()).max().unwrap_or(6).max(6) + 2;
+    let cidr_length = rows.iter().map(|r| r.2.len()).max().unwrap_or(6).max(6) + 2;
+    let ipv6_length = rows.iter().map(|r| r.3.len()).max().unwrap_or(4).max(4) + 2;
+    let domain_length = rows.iter().map(|r| r.4.len()).max().unwrap_or(6).max(6) + 2;
+
+    println!(
+        "{:<name_length$} {:<bridge_length$} {:<cidr_length$} {:<ipv6_length$} {:<domain_length$} {:<5} {:<10} CREATED",
+        "NAME", "BRIDGE", "SUBNET", "IPV6", "DOMAIN", "NAT", "MICROVMS"
+    );
+    for (name, bridge, cidr, ipv6, domain, nat, vms, created_at) in rows {
+        println!(
+            "{:<name_length$} {:<bridge_length$} {:<cidr_length$} {:<ipv6_length$} {:<domain_length$} {:<5} {:<10} {}",
+            name,
+            bridge,
+            cidr,
+            ipv6,
+            domain,
+            if nat { "yes" } else { "no" },
+            vms,
+            format_duration_ago(created_at),
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn remove_network(name: &str) -> Result<(), Error> {
+    let network = network::remove(name).await?;
+    println!("{}", network.id);
+    Ok(())
+}
+
+pub async fn prune_networks() -> Result<(), Error> {
+    let networks = network::prune().await?;
+    println!(
+        "[✓] Removed the firewall rules and {} bridge(s) of fireup networks.",
+        networks.len()
+    );
+    Ok(())
+}
+
+pub async fn inspect_network(name: &str) -> Result<(), Error> {
+    let network = network::get(name).await?;
+    let vms = network::vms(&network.name)
+        .await?
+        .into_iter()
+        .map(|vm| vm.name)
+        .collect::<Vec<_>>();
+    let network = json!({
+        "id": network.id,
+        "name": network.name,
+        "bridge": network.bridge,
+        "cidr": network.cidr,
+        "gateway": network.gateway,
+        "domain": network.domain,
+        "nat": network.nat,
+        "ipv6_prefix": network.ipv6_prefix,
+        "microvms": vms,
+        "created_at": network.created_at.to_rfc3339(),
+    });
+
+    let network_json = serde_json::to_string_pretty(&network)?;
+    println!("{}", network_json.to_colored_json_auto()?);
+
+    Ok(())
+}
@@ -0,0 +1,6 @@
+use anyhow::Error;
+
+pub async fn pause(name: &str) -> Result<(), Error> {
+    firecracker_process::pause(name).await?;
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use std::process;
+
+use anyhow::Error;
+use firecracker_state::repo;
+use firecracker_vm::ports;
+
+pub async fn port(name: &str) -> Result<(), Error> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let vm = repo::virtual_machine::find(&pool, name).await?;
+
+    if vm.is_none() {
+        println!("[!] No virtual machine found with the name: {}", name);
+        process::exit(1);
+    }
+    let vm = vm.unwrap();
+
+    for mapping in vm
+        .ports
+        .as_deref()
+        .map(ports::from_column)
+        .unwrap_or_default()
+    {
+        println!(
+            "{}/{} -> 0.0.0.0:{}",
+            mapping.guest_port, mapping.protocol, mapping.host_port
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,15 @@
+use anyhow::Error;
+use firecracker_vm::teardown;
+
+pub async fn prune() -> Result<(), Error> {
+    let pruned = teardown::prune().await?;
+    println!(
+        "[✓] Removed {} interface(s), {} socket(s), {} jail(s), {} lease(s) and {} log file(s) left behind.",
+        pruned.interfaces.len(),
+        pruned.sockets.len(),
+        pruned.jails.len(),
+        pruned.leases,
+        pruned.logs.len()
+    );
+    Ok(())
+}
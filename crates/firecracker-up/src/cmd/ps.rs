@@ -1,14 +1,26 @@
 use anyhow::Error;
+use firecracker_state::entity::virtual_machine::VirtualMachine;
+use firecracker_vm::ports;
 
-use crate::date::{format_duration_ago, format_status};
+use crate::{
+    date::{format_duration_ago, format_status},
+    project,
+};
 
 pub async fn list_all_instances(all: bool) -> Result<(), Error> {
     let pool = firecracker_state::create_connection_pool().await?;
-    let mut vms = firecracker_state::repo::virtual_machine::all(&pool).await?;
+    // inside a multi-VM project, only its MicroVMs
+    let mut vms = match project::load()? {
+        Some(project) => {
+            firecracker_state::repo::virtual_machine::find_all_by_project_dir(&pool, &project.dir)
+                .await?
+        }
+        None => firecracker_state::repo::virtual_machine::all(&pool).await?,
+    };
     if !all {
         vms = vms
             .into_iter()
-            .filter(|vm| vm.status == "RUNNING")
+            .filter(|vm| vm.status == "RUNNING" || vm.status == "PAUSED")
             .collect::<Vec<_>>();
     }
 
@@ -38,7 +50,7 @@ pub async fn list_all_instances(all: bool) -> Result<(), Error> {
         + 2;
     let status_length = vms
         .iter()
-        .map(|vm| format_status(&vm.status, vm.updated_at).len())
+        .map(|vm| format_status(&vm.status, vm.exit_code, vm.updated_at).len())
         .max()
         .unwrap_or(10);
     let pid_length = vms
@@ -49,7 +61,7 @@ pub async fn list_all_instances(all: bool) -> Result<(), Error> {
         + 2;
     let ip_length = vms
         .iter()
-        .map(|vm| vm.ip_address.clone().unwrap_or_default().len())
+        .map(|vm| vm.ip_addresses().join(", ").len())
         .max()
         .unwrap_or(10)
         + 2;
@@ -61,22 +73,35 @@ pub async fn list_all_instances(all: bool) -> Result<(), Error> {
         + 2;
 
     println!(
-        "{:<name_length$} {:<distro_length$} {:<vcpu_length$} {:<memory_length$} {:<status_length$} {:<pid_length$} {:<ip_length$} {:<created_length$}",
+        "{:<name_length$} {:<distro_length$} {:<vcpu_length$} {:<memory_length$} {:<status_length$} {:<pid_length$} {:<ip_length$} {:<created_length$} PORTS",
         "NAME", "IMAGE", "VCPU", "MEMORY", "STATUS", "PID", "IP", "CREATED"
     );
     for vm in vms {
+        let ports = format_ports(&vm);
         println!(
-            "{:<name_length$} {:<distro_length$} {:<vcpu_length$} {:<memory_length$} {:<status_length$} {:<pid_length$} {:<ip_length$} {:<created_length$}",
+            "{:<name_length$} {:<distro_length$} {:<vcpu_length$} {:<memory_length$} {:<status_length$} {:<pid_length$} {:<ip_length$} {:<created_length$} {}",
             vm.name,
             vm.distro,
             vm.vcpu,
             format!("{} MiB", vm.memory),
-            format_status(&vm.status, vm.updated_at),
+            format_status(&vm.status, vm.exit_code, vm.updated_at),
             vm.pid.unwrap_or(0),
-            vm.ip_address.unwrap_or_default(),
+            vm.ip_addresses().join(", "),
             format_duration_ago(vm.created_at),
+            ports,
         );
     }
 
     Ok(())
 }
+
+fn format_ports(vm: &VirtualMachine) -> String {
+    vm.ports
+        .as_deref()
+        .map(ports::from_column)
+        .unwrap_or_default()
+        .iter()
+        .map(|m| format!("0.0.0.0:{}->{}/{}", m.host_port, m.guest_port, m.protocol))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
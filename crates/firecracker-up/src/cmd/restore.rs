@@ -0,0 +1,55 @@
+use std::process;
+
+use anyhow::Error;
+use firecracker_state::repo;
+use firecracker_vm::types::VmOptions;
+use owo_colors::OwoColorize;
+
+use crate::cmd::up::check_kvm_support;
+
+pub async fn restore(name: &str) -> Result<(), Error> {
+    check_kvm_support()?;
+
+    let pool = firecracker_state::create_connection_pool().await?;
+    let snapshot = repo::snapshot::find(&pool, name).await?;
+    if snapshot.is_none() {
+        println!("[!] No snapshot found with the name: {}", name);
+        process::exit(1);
+    }
+
+    let snapshot = snapshot.unwrap();
+    let vm = repo::virtual_machine::find(&pool, &snapshot.vm_id).await?;
+    if vm.is_none() {
+        println!(
+            "[!] MicroVM {} of snapshot {} no longer exists.",
+            snapshot.vm_name.cyan(),
+            snapshot.name.cyan()
+        );
+        process::exit(1);
+    }
+
+    let vm = vm.unwrap();
+    let options = VmOptions {
+        vcpu: vm.vcpu,
+        memory: vm.memory,
+        bridge: vm.bridge,
+        tap: vm.tap,
+        api_socket: vm.api_socket,
+        mac_address: vm.mac_address,
+        network: vm.network,
+        ..Default::default()
+    };
+
+    let pid = firecracker_process::start(&options).await?;
+    firecracker_vm::snapshot::restore(&options, pid, &snapshot).await?;
+
+    println!(
+        "[✓] {} restored from snapshot {} 🎉",
+        vm.name.cyan(),
+        snapshot.name.bright_green()
+    );
+    println!("SSH into the VM using the following command:");
+    println!("{} {}", "fireup ssh".bright_green(), vm.name.bright_green());
+
+    Ok(())
+}
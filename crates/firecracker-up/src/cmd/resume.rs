@@ -0,0 +1,6 @@
+use anyhow::Error;
+
+pub async fn resume(name: &str) -> Result<(), Error> {
+    firecracker_process::resume(name).await?;
+    Ok(())
+}
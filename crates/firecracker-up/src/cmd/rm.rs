@@ -13,8 +13,8 @@ pub async fn remove(name: &str) -> Result<(), Error> {
     }
     let vm = vm.unwrap();
 
-    firecracker_process::stop(Some(vm.name)).await.ok();
-    repo::virtual_machine::delete(&pool, &vm.id).await?;
+    firecracker_process::stop(Some(vm.name.clone())).await.ok();
+    firecracker_vm::teardown::remove(&vm).await?;
 
     println!("{}", vm.id);
 
@@ -0,0 +1,14 @@
+use anyhow::Error;
+
+pub fn serial(
+    log: &str,
+    socket: &str,
+    owner: Option<&str>,
+    command: &[String],
+) -> Result<(), Error> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| Error::msg("No command to run"))?;
+    let code = firecracker_vm::console::relay(log, socket, owner, program, args)?;
+    std::process::exit(code);
+}
@@ -0,0 +1,97 @@
+use std::process;
+
+use anyhow::Error;
+use chrono::Utc;
+use firecracker_state::repo;
+use owo_colors::OwoColorize;
+
+use crate::date::format_duration_ago;
+
+pub async fn snapshot(name: &str, snapshot_name: Option<String>) -> Result<(), Error> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let vm = repo::virtual_machine::find(&pool, name).await?;
+    if vm.is_none() {
+        println!("[!] No virtual machine found with the name: {}", name);
+        process::exit(1);
+    }
+
+    let vm = vm.unwrap();
+    if !firecracker_process::vm_is_running(&vm.name).await?
+        && !firecracker_process::vm_is_paused(&vm.name).await?
+    {
+        println!("[-] MicroVM '{}' is not running.", vm.name);
+        let start_cmd = format!("fireup start {}", vm.name);
+        println!("    Start it with {}", start_cmd.cyan());
+        process::exit(1);
+    }
+
+    let snapshot_name = snapshot_name
+        .unwrap_or_else(|| format!("{}-{}", vm.name, Utc::now().format("%Y%m%d%H%M%S")));
+    let snapshot = firecracker_vm::snapshot::create(&vm, &snapshot_name).await?;
+
+    println!(
+        "[✓] Snapshot {} of {} created 🎉",
+        snapshot.name.bright_green(),
+        vm.name.cyan()
+    );
+    println!("Restore it using the following command:");
+    println!(
+        "{} {}",
+        "fireup restore".bright_green(),
+        snapshot.name.bright_green()
+    );
+
+    Ok(())
+}
+
+pub async fn remove_snapshot(name: &str) -> Result<(), Error> {
+    let snapshot = firecracker_vm::snapshot::remove(name).await?;
+    println!("{}", snapshot.id);
+    Ok(())
+}
+
+pub async fn list_snapshots() -> Result<(), Error> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let snapshots = repo::snapshot::all(&pool).await?;
+
+    if snapshots.is_empty() {
+        println!("No snapshots found.");
+        return Ok(());
+    }
+
+    let name_length = snapshots
+        .iter()
+        .map(|s| s.name.len())
+        .max()
+        .unwrap_or(10)
+        .max(10)
+        + 2;
+    let vm_length = snapshots
+        .iter()
+        .map(|s| s.vm_name.len())
+        .max()
+        .unwrap_or(10)
+        .max(10)
+        + 2;
+    let created_length = snapshots
+        .iter()
+        .map(|s| format_duration_ago(s.created_at).len())
+        .max()
+        .unwrap_or(10)
+        + 2;
+
+    println!(
+        "{:<name_length$} {:<vm_length$} {:<created_length$}",
+        "NAME", "MICROVM", "CREATED"
+    );
+    for snapshot in snapshots {
+        println!(
+            "{:<name_length$} {:<vm_length$} {:<created_length$}",
+            snapshot.name,
+            snapshot.vm_name,
+            format_duration_ago(snapshot.created_at),
+        );
+    }
+
+    Ok(())
+}
@@ -1,28 +1,38 @@
 use crate::{command::run_command, config::get_config_dir};
 use anyhow::Error;
 use firecracker_state::repo;
+use firecracker_vm::agent;
 use glob::glob;
 use sqlx::{Pool, Sqlite};
 
 pub async fn ssh(pool: Pool<Sqlite>, name: Option<String>) -> Result<(), Error> {
-    let guest_ip = match name {
-        Some(name) => format!("{}.firecracker", name),
+    let vm = match name {
+        Some(name) => repo::virtual_machine::find(&pool, &name).await?,
         None => {
             let current_dir = std::env::current_dir()
                 .map_err(|e| Error::msg(format!("Failed to get current directory: {}", e)))?
                 .display()
                 .to_string();
-            let vm = repo::virtual_machine::find_by_project_dir(&pool, &current_dir).await?;
-            match vm {
-                Some(vm) => format!("{}.firecracker", vm.name),
-                None => {
-                    return Err(Error::msg(
-                        "No virtual machine found with the given name or project directory.",
-                    ))
-                }
-            }
+            repo::virtual_machine::find_by_project_dir(&pool, &current_dir).await?
         }
     };
+    let vm = vm.ok_or_else(|| {
+        Error::msg("No virtual machine found with the given name or project directory.")
+    })?;
+
+    // a login shell through the agent works even when the guest network is down
+    if agent::ping(&vm.api_socket).is_ok() {
+        let code = agent::exec(&vm.api_socket, None, true)?;
+        if code != 0 {
+            std::process::exit(code);
+        }
+        return Ok(());
+    }
+
+    let guest_ip = match vm.ipv4_address() {
+        Some(ip) => ip,
+        None => firecracker_vm::network::fqdn(&vm.name).await?,
+    };
     let app_dir = get_config_dir()?;
     let private_key = glob(format!("{}/id_rsa", app_dir).as_str())
         .map_err(|e| Error::msg(format!("Failed to find SSH key: {}", e)))?
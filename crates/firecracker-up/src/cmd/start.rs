@@ -3,15 +3,11 @@ use std::process;
 use anyhow::Error;
 use fire_config::TailscaleOptions;
 use firecracker_state::repo;
-use firecracker_vm::types::VmOptions;
+use firecracker_vm::{firewall, limits, nic, provision, types::VmOptions, volume};
 
 use crate::cmd::up::up;
 
 pub async fn start(name: &str, tailscale_auth_key: Option<String>) -> Result<(), Error> {
-    let etcd = match fire_config::read_config() {
-        Ok(config) => config.etcd,
-        Err(_) => None,
-    };
     let pool = firecracker_state::create_connection_pool().await?;
     let vm = repo::virtual_machine::find(&pool, name).await?;
     if vm.is_none() {
@@ -20,6 +16,7 @@ pub async fn start(name: &str, tailscale_auth_key: Option<String>) -> Result<(),
     }
 
     let vm = vm.unwrap();
+    let interfaces = repo::vm_interface::find_by_vm(&pool, &vm.id).await?;
 
     up(VmOptions {
         debian: Some(vm.distro == "debian"),
@@ -43,13 +40,31 @@ pub async fn start(name: &str, tailscale_auth_key: Option<String>) -> Result<(),
         tap: vm.tap,
         api_socket: vm.api_socket,
         mac_address: vm.mac_address,
-        etcd,
         ssh_keys: vm
             .ssh_keys
             .map(|keys| keys.split(',').map(|s| s.to_string()).collect()),
         tailscale: tailscale_auth_key.map(|key| TailscaleOptions {
             auth_key: Some(key),
         }),
+        disk_size: vm.disk_size,
+        volumes: vm.volumes.map(|volumes| volume::from_column(&volumes)),
+        jailer: vm.jailer_uid.map(|_| true),
+        ip: vm.static_ip,
+        network: vm.network,
+        ports: vm
+            .ports
+            .map(|ports| ports.split(',').map(|s| s.to_string()).collect()),
+        firewall: vm
+            .firewall
+            .and_then(|column| firewall::from_column(&column)),
+        limits: vm.limits.and_then(|column| limits::from_column(&column)),
+        nics: nic::from_interfaces(&interfaces),
+        restart: vm.restart.and_then(|policy| policy.parse().ok()),
+        project: vm.project,
+        provision: vm
+            .provision
+            .and_then(|column| provision::from_column(&column)),
+        image: vm.image,
     })
     .await?;
 
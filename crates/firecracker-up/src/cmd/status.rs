@@ -13,6 +13,15 @@ pub async fn status(name: Option<String>) -> Result<(), Error> {
                 return Ok(());
             }
 
+            if firecracker_process::vm_is_paused(&name).await? {
+                println!(
+                    "{} is paused. {}",
+                    name.cyan(),
+                    "[‖] PAUSED".bright_yellow()
+                );
+                return Ok(());
+            }
+
             println!(
                 "{} is not running. {}",
                 name.cyan(),
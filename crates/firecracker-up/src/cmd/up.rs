@@ -1,16 +1,29 @@
 use std::{process, thread};
 
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use fire_config::read_config;
 use firecracker_state::repo;
-use firecracker_vm::types::VmOptions;
+use firecracker_vm::{
+    image, logs,
+    mac::generate_unique_mac,
+    network::{self, NetworkOptions},
+    types::VmOptions,
+};
 use owo_colors::OwoColorize;
+use sqlx::{Pool, Sqlite};
 
-use crate::command::run_command;
+use crate::{
+    command::run_command,
+    project::{self, Project},
+};
 
 pub async fn up(options: VmOptions) -> Result<(), Error> {
     check_kvm_support()?;
 
+    if let Some(project) = project::load()? {
+        return up_project(project).await;
+    }
+
     let mut options = match read_config() {
         Ok(config) => VmOptions::from(config),
         Err(_) => options.clone(),
@@ -37,7 +50,93 @@ pub async fn up(options: VmOptions) -> Result<(), Error> {
         }
     }
 
-    let vms = repo::virtual_machine::all(&pool).await?;
+    launch(&pool, options, vm_id).await
+}
+
+/// Starts the MicroVMs of `project` which are not running yet, each one after
+/// the ones it depends on.
+async fn up_project(project: Project) -> Result<(), Error> {
+    let services = project.config.services()?;
+    let pool = firecracker_state::create_connection_pool().await?;
+
+    let networks = network::all().await?;
+    for (name, config) in &project.config.networks {
+        if networks.iter().any(|network| &network.name == name) {
+            continue;
+        }
+        let network = network::create(
+            name,
+            NetworkOptions {
+                cidr: config.cidr.clone(),
+                gateway: config.gateway.clone(),
+                domain: config.domain.clone(),
+                nat: config.nat.unwrap_or(true),
+                ipv6: config.ipv6.unwrap_or(false),
+                ..Default::default()
+            },
+        )
+        .await?;
+        println!(
+            "[+] Network {} created ({}).",
+            network.name.cyan(),
+            network.cidr
+        );
+    }
+
+    println!(
+        "[+] Starting project {} ({} MicroVMs)...",
+        project.name.cyan(),
+        services.len()
+    );
+    for service in services {
+        let vm = repo::virtual_machine::find(&pool, &service.name).await?;
+        if let Some(vm) = &vm {
+            if vm.project_dir.as_deref() != Some(project.dir.as_str()) {
+                return Err(anyhow!(
+                    "MicroVM '{}' already exists outside of project '{}'",
+                    service.name,
+                    project.name
+                ));
+            }
+        }
+        if firecracker_process::vm_is_running(&service.name).await?
+            || firecracker_process::vm_is_paused(&service.name).await?
+        {
+            println!("[✓] {} is already running.", service.name.cyan());
+            continue;
+        }
+
+        let has_mac = service.config.vm.mac.is_some();
+        let mut options = VmOptions::from(service.config);
+        options.project = Some(project.name.clone());
+        // the name of the MicroVM is the one of its API socket
+        options.api_socket = match &vm {
+            Some(vm) => vm.api_socket.clone(),
+            None => format!("/tmp/firecracker-{}.sock", service.name),
+        };
+        if !has_mac {
+            options.mac_address = match &vm {
+                Some(vm) => vm.mac_address.clone(),
+                None => generate_unique_mac(),
+            };
+        }
+
+        println!("[+] Starting {}...", service.name.cyan());
+        launch(&pool, options, vm.map(|vm| vm.id)).await?;
+    }
+    println!("[✓] Project {} is up.", project.name.bright_green());
+    Ok(())
+}
+
+/// Starts Firecracker for `options` on a free tap and sets the MicroVM up,
+/// as `vm_id` when it already exists.
+async fn launch(
+    pool: &Pool<Sqlite>,
+    mut options: VmOptions,
+    vm_id: Option<String>,
+) -> Result<(), Error> {
+    image::resolve(&mut options).await?;
+    let vms = repo::virtual_machine::all(pool).await?;
     if options.tap.is_empty() {
         let vms = vms
             .into_iter()
@@ -78,6 +177,7 @@ pub async fn up(options: VmOptions) -> Result<(), Error> {
         }
     }
 
+    logs::capture_setups();
     let kernel_file = firecracker_prepare::prepare(
         options.clone().into(),
         options.vmlinux.clone(),
@@ -0,0 +1,18 @@
+use anyhow::Error;
+use fire_config::LimitsOptions;
+use firecracker_vm::limits;
+use owo_colors::OwoColorize;
+
+pub async fn update(name: &str, changes: LimitsOptions) -> Result<(), Error> {
+    let limits = limits::update(name, &changes).await?;
+    let limit = |value: Option<u32>, unit: &str| match value {
+        Some(value) => format!("{} {}", value, unit),
+        None => "unlimited".into(),
+    };
+    println!("[✓] Rate limits of {} updated:", name.cyan());
+    println!("  net rx: {}", limit(limits.net_rx_mbps, "Mbit/s"));
+    println!("  net tx: {}", limit(limits.net_tx_mbps, "Mbit/s"));
+    println!("  disk:   {}", limit(limits.disk_mbps, "Mbit/s"));
+    println!("  iops:   {}", limit(limits.disk_iops, "ops/s"));
+    Ok(())
+}
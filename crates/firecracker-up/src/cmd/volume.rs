@@ -0,0 +1,86 @@
+use std::process;
+
+use anyhow::Error;
+use colored_json::ToColoredJson;
+use firecracker_state::repo;
+use firecracker_vm::{constants::DEFAULT_VOLUME_SIZE, volume};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use crate::date::format_duration_ago;
+
+pub async fn create_volume(name: &str, size: Option<u32>) -> Result<(), Error> {
+    let volume = volume::create(name, size.unwrap_or(DEFAULT_VOLUME_SIZE)).await?;
+    println!("[✓] Volume {} created 🎉", volume.name.bright_green());
+    Ok(())
+}
+
+pub async fn list_volumes() -> Result<(), Error> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let volumes = repo::volume::all(&pool).await?;
+
+    if volumes.is_empty() {
+        println!("No volumes found.");
+        return Ok(());
+    }
+
+    let mut rows = vec![];
+    for v in volumes {
+        let vm = volume::attached_to(&v.name)
+            .await?
+            .map(|vm| vm.name)
+            .unwrap_or_else(|| "-".into());
+        rows.push((v.name, format!("{} MiB", v.size), vm, v.created_at));
+    }
+
+    let name_length = rows.iter().map(|r| r.0.len()).max().unwrap_or(10).max(10) + 2;
+    let size_length = rows.iter().map(|r| r.1.len()).max().unwrap_or(6).max(6) + 2;
+    let vm_length = rows.iter().map(|r| r.2.len()).max().unwrap_or(10).max(10) + 2;
+
+    println!(
+        "{:<name_length$} {:<size_length$} {:<vm_length$} CREATED",
+        "NAME", "SIZE", "MICROVM"
+    );
+    for (name, size, vm, created_at) in rows {
+        println!(
+            "{:<name_length$} {:<size_length$} {:<vm_length$} {}",
+            name,
+            size,
+            vm,
+            format_duration_ago(created_at),
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn remove_volume(name: &str) -> Result<(), Error> {
+    let volume = volume::remove(name).await?;
+    println!("{}", volume.id);
+    Ok(())
+}
+
+pub async fn inspect_volume(name: &str) -> Result<(), Error> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let volume = repo::volume::find(&pool, name).await?;
+    if volume.is_none() {
+        println!("[!] No volume found with the name: {}", name);
+        process::exit(1);
+    }
+
+    let volume = volume.unwrap();
+    let attached_to = volume::attached_to(&volume.name).await?.map(|vm| vm.name);
+    let volume = json!({
+        "id": volume.id,
+        "name": volume.name,
+        "path": volume.path,
+        "size": volume.size,
+        "attached_to": attached_to,
+        "created_at": volume.created_at.to_rfc3339(),
+    });
+
+    let volume_json = serde_json::to_string_pretty(&volume)?;
+    println!("{}", volume_json.to_colored_json_auto()?);
+
+    Ok(())
+}
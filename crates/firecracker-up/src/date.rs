@@ -37,10 +37,14 @@ pub fn format_duration_ago(created: DateTime<Utc>) -> String {
     }
 }
 
-pub fn format_status(status: &str, date: DateTime<Utc>) -> String {
-    if status == "RUNNING" {
-        format!("Up {}", format_duration_ago(date))
-    } else {
-        status.to_string()
+pub fn format_status(status: &str, exit_code: Option<i32>, date: DateTime<Utc>) -> String {
+    match status {
+        "RUNNING" => format!("Up {}", format_duration_ago(date)),
+        "PAUSED" => format!("Up {} (Paused)", format_duration_ago(date)),
+        "EXITED" | "CRASHED" => match exit_code {
+            Some(code) => format!("{} ({}) {}", status, code, format_duration_ago(date)),
+            None => format!("{} {}", status, format_duration_ago(date)),
+        },
+        _ => status.to_string(),
     }
 }
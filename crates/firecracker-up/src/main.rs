@@ -1,18 +1,48 @@
 use anyhow::Result;
-use clap::{arg, Arg, Command};
-use firecracker_vm::{constants::BRIDGE_DEV, mac::generate_unique_mac, types::VmOptions};
+use clap::{arg, Arg, ArgMatches, Command};
+use fire_config::{LimitsOptions, RestartPolicy};
+use firecracker_vm::{
+    console, constants::BRIDGE_DEV, mac::generate_unique_mac, network::NetworkOptions,
+    types::VmOptions,
+};
 use owo_colors::OwoColorize;
 
 use crate::cmd::{
-    cp::cp, down::down, exec::exec, init::init, inspect::inspect_microvm, logs::logs,
-    ps::list_all_instances, reset::reset, rm::remove, serve::serve, ssh::ssh, start::start,
-    status::status, stop::stop, up::up,
+    build::{build, list_images},
+    console::attach,
+    cp::cp,
+    down::down,
+    exec::exec,
+    init::init,
+    inspect::inspect_microvm,
+    logs::logs,
+    netd::netd,
+    network::{create_network, inspect_network, list_networks, prune_networks, remove_network},
+    pause::pause,
+    port::port,
+    prune::prune,
+    ps::list_all_instances,
+    reset::reset,
+    restore::restore,
+    resume::resume,
+    rm::remove,
+    serial::serial,
+    serve::serve,
+    snapshot::{list_snapshots, remove_snapshot, snapshot},
+    ssh::ssh,
+    start::start,
+    status::status,
+    stop::stop,
+    up::up,
+    update::update,
+    volume::{create_volume, inspect_volume, list_volumes, remove_volume},
 };
 
 pub mod cmd;
 pub mod command;
 pub mod config;
 pub mod date;
+pub mod project;
 pub mod ssh;
 
 fn cli() -> Command {
@@ -58,6 +88,16 @@ fn cli() -> Command {
                 .arg(arg!([name] "Name of the Firecracker MicroVM to stop").required(false))
                 .about("Stop Firecracker MicroVM"),
         )
+        .subcommand(
+            Command::new("pause")
+                .arg(arg!(<name> "Name of the Firecracker MicroVM to pause").required(true))
+                .about("Pause Firecracker MicroVM"),
+        )
+        .subcommand(
+            Command::new("resume")
+                .arg(arg!(<name> "Name of the Firecracker MicroVM to resume").required(true))
+                .about("Resume a paused Firecracker MicroVM"),
+        )
         .subcommand(
             Command::new("restart")
                 .arg(arg!(<name> "Name of the Firecracker MicroVM to restart").required(true))
@@ -89,6 +129,26 @@ fn cli() -> Command {
                 .arg(arg!(--ubuntu "Prepare Ubuntu MicroVM").default_value("true"))
                 .arg(arg!(--vcpu <n> "Number of vCPUs"))
                 .arg(arg!(--memory <m> "Memory size in MiB"))
+                .arg(
+                    Arg::new("disk-size")
+                        .long("disk-size")
+                        .value_name("MiB")
+                        .help(
+                        "Size in MiB of a persistent disk keeping the VM changes across restarts",
+                    ),
+                )
+                .arg(arg!(--ip <ip> "Static IPv4 address of the MicroVM"))
+                .arg(arg!(--network <name> "Name of the network to attach the MicroVM to"))
+                .arg(
+                    Arg::new("publish")
+                        .short('p')
+                        .long("publish")
+                        .value_name("HOST:GUEST[/PROTO]")
+                        .action(clap::ArgAction::Append)
+                        .help("Forward a host port to the MicroVM, e.g. 8080:80 or 5353:53/udp"),
+                )
+                .args(limit_args())
+                .arg(restart_arg())
                 .arg(arg!(--vmlinux <path> "Path to the kernel image"))
                 .arg(arg!(--rootfs <path> "Path to the root filesystem image"))
                 .arg(arg!(--bridge <name> "Name of the bridge interface").default_value(BRIDGE_DEV))
@@ -123,9 +183,13 @@ fn cli() -> Command {
                         .value_name("TAILSCALE_AUTH_KEY")
                         .help("Tailscale auth key to connect the VM to a Tailscale network"),
                 )
-                .about("Start a new Firecracker MicroVM"),
+                .about("Start a new Firecracker MicroVM, or the MicroVMs of the project in fire.toml"),
+        )
+        .subcommand(Command::new("down").about("Stop Firecracker MicroVM, or the MicroVMs of the project in fire.toml"))
+        .subcommand(
+            Command::new("prune")
+                .about("Remove the taps, sockets, jails and leases no MicroVM accounts for"),
         )
-        .subcommand(Command::new("down").about("Stop Firecracker MicroVM"))
         .subcommand(
             Command::new("status")
                 .arg(arg!([name] "Name of the Firecracker MicroVM to check status").required(false))
@@ -133,12 +197,22 @@ fn cli() -> Command {
         )
         .subcommand(
             Command::new("logs")
+                .arg(arg!(<name> "Name of the Firecracker MicroVM").required(true))
+                .arg(
+                    arg!(--source <SOURCE> "Only show the logs of Firecracker, the serial console or the setup")
+                        .value_parser(["vmm", "console", "setup"]),
+                )
                 .arg(
                     arg!(-f --follow "Follow the logs")
                         .short('f')
                         .long("follow")
                         .default_value("false"),
                 )
+                .arg(arg!(--since <SINCE> "Show the logs since a time, e.g. 10m, 1h30m or 2026-10-18 12:00:00"))
+                .arg(
+                    arg!(--tail <N> "Number of lines to show from the end of the logs")
+                        .value_parser(clap::value_parser!(usize)),
+                )
                 .about("View the logs of the Firecracker MicroVM"),
         )
         .subcommand(
@@ -156,9 +230,29 @@ fn cli() -> Command {
                 .arg(arg!(<name> "Name or ID of the Firecracker MicroVM to delete").required(true))
                 .about("Delete the Firecracker MicroVM"),
         )
+        .subcommand(
+            Command::new("serial")
+                .hide(true)
+                .arg(arg!(--log <path> "Where to keep the console output").required(true))
+                .arg(arg!(--socket <path> "Socket to attach to the console through").required(true))
+                .arg(arg!(--owner <owner> "Owner of the socket and the log"))
+                .arg(
+                    Arg::new("command")
+                        .help("Command to run with the console")
+                        .required(true)
+                        .num_args(1..)
+                        .last(true),
+                )
+                .about("Run a command, relaying its serial console"),
+        )
+        .subcommand(
+            Command::new("netd")
+                .hide(true)
+                .about("Run the fireup network daemon (DHCP and DNS servers)"),
+        )
         .subcommand(
             Command::new("serve")
-                .about("Start fireup HTTP API server")
+                .about("Start fireup HTTP API server and supervise the MicroVMs")
                 .arg(arg!(--host <host> "Host to bind the server"))
                 .arg(arg!(--port <port> "Port to bind the server")),
         )
@@ -167,6 +261,17 @@ fn cli() -> Command {
                 .arg(arg!(<name> "Name or ID of the Firecracker MicroVM to inspect").required(true))
                 .about("Inspect the Firecracker MicroVM details"),
         )
+        .subcommand(
+            Command::new("update")
+                .arg(arg!(<name> "Name or ID of the Firecracker MicroVM").required(true))
+                .args(limit_args())
+                .about("Update the rate limits of a Firecracker MicroVM, live if it is running"),
+        )
+        .subcommand(
+            Command::new("port")
+                .arg(arg!(<name> "Name or ID of the Firecracker MicroVM").required(true))
+                .about("List the port mappings of the Firecracker MicroVM"),
+        )
         .subcommand(
             Command::new("exec")
                 .arg(
@@ -181,12 +286,118 @@ fn cli() -> Command {
                 )
                 .about("Execute a command inside the Firecracker MicroVM"),
         )
+        .subcommand(
+            Command::new("console")
+                .arg(arg!(<name> "Name of the Firecracker MicroVM to attach to").required(true))
+                .arg(
+                    arg!(--"detach-keys" <KEYS> "Key sequence to detach from the console")
+                        .default_value(console::DEFAULT_DETACH_KEYS),
+                )
+                .about("Attach to the serial console of the Firecracker MicroVM"),
+        )
         .subcommand(
             Command::new("cp")
                 .arg(arg!(<source> "Source file path").required(true))
                 .arg(arg!(<destination> "Destination file path").required(true))
                 .about("Copy files to/from the Firecracker MicroVM"),
         )
+        .subcommand(
+            Command::new("snapshot")
+                .arg(arg!(<name> "Name of the Firecracker MicroVM to snapshot").required(true))
+                .arg(arg!([snapshot] "Name of the snapshot to create"))
+                .args_conflicts_with_subcommands(true)
+                .subcommand(
+                    Command::new("rm")
+                        .arg(arg!(<name> "Name or ID of the snapshot to delete").required(true))
+                        .about("Delete a snapshot"),
+                )
+                .about("Create a snapshot of a running Firecracker MicroVM"),
+        )
+        .subcommand(Command::new("snapshots").about("List all Firecracker MicroVM snapshots"))
+        .subcommand(
+            Command::new("build")
+                .arg(
+                    arg!(-f --file <FILE> "Path to the Firefile")
+                        .default_value("Firefile"),
+                )
+                .arg(arg!(-t --tag <TAG> "Name of the image to build").required(true))
+                .about("Build a MicroVM image from a Firefile"),
+        )
+        .subcommand(Command::new("images").about("List the images built with fireup build"))
+        .subcommand(
+            Command::new("restore")
+                .arg(arg!(<snapshot> "Name or ID of the snapshot to restore").required(true))
+                .about("Restore a Firecracker MicroVM from a snapshot"),
+        )
+        .subcommand(
+            Command::new("volume")
+                .about("Manage volumes attachable to Firecracker MicroVMs")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("create")
+                        .arg(arg!(<name> "Name of the volume to create").required(true))
+                        .arg(arg!(--size <MiB> "Size of the volume in MiB"))
+                        .about("Create a new volume"),
+                )
+                .subcommand(Command::new("ls").alias("list").about("List all volumes"))
+                .subcommand(
+                    Command::new("rm")
+                        .arg(arg!(<name> "Name or ID of the volume to delete").required(true))
+                        .about("Delete a volume"),
+                )
+                .subcommand(
+                    Command::new("inspect")
+                        .arg(arg!(<name> "Name or ID of the volume to inspect").required(true))
+                        .about("Inspect the volume details"),
+                ),
+        )
+        .subcommand(
+            Command::new("network")
+                .about("Manage networks Firecracker MicroVMs are attached to")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("create")
+                        .arg(arg!(<name> "Name of the network to create").required(true))
+                        .arg(arg!(--cidr <cidr> "Subnet of the network, e.g. 10.10.0.0/24"))
+                        .arg(arg!(--bridge <name> "Name of the bridge interface"))
+                        .arg(arg!(--gateway <ip> "Gateway address, the first host by default"))
+                        .arg(arg!(--domain <domain> "DNS domain of the MicroVMs"))
+                        .arg(
+                            Arg::new("no-nat")
+                                .long("no-nat")
+                                .help("Do not masquerade the network traffic to the outside")
+                                .action(clap::ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("ipv6")
+                                .long("ipv6")
+                                .help("Enable IPv6 with a random unique local /64 prefix")
+                                .action(clap::ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("ipv6-prefix")
+                                .long("ipv6-prefix")
+                                .value_name("PREFIX")
+                                .help("IPv6 /64 prefix of the network, e.g. fd00:1:2:3::/64"),
+                        )
+                        .about("Create a new network"),
+                )
+                .subcommand(Command::new("ls").alias("list").about("List all networks"))
+                .subcommand(
+                    Command::new("rm")
+                        .arg(arg!(<name> "Name or ID of the network to delete").required(true))
+                        .about("Delete a network"),
+                )
+                .subcommand(
+                    Command::new("inspect")
+                        .arg(arg!(<name> "Name or ID of the network to inspect").required(true))
+                        .about("Inspect the network details"),
+                )
+                .subcommand(
+                    Command::new("prune")
+                        .about("Remove the bridges and firewall rules set up for the networks"),
+                ),
+        )
         .arg(arg!(--debian "Prepare Debian MicroVM").default_value("false"))
         .arg(arg!(--alpine "Prepare Alpine MicroVM").default_value("false"))
         .arg(arg!(--nixos "Prepare NixOS MicroVM").default_value("false"))
@@ -206,6 +417,24 @@ fn cli() -> Command {
         .arg(arg!(--ubuntu "Prepare Ubuntu MicroVM").default_value("true"))
         .arg(arg!(--vcpu <n> "Number of vCPUs"))
         .arg(arg!(--memory <m> "Memory size in MiB"))
+        .arg(
+            Arg::new("disk-size")
+                .long("disk-size")
+                .value_name("MiB")
+                .help("Size in MiB of a persistent disk keeping the VM changes across restarts"),
+        )
+        .arg(arg!(--ip <ip> "Static IPv4 address of the MicroVM"))
+        .arg(arg!(--network <name> "Name of the network to attach the MicroVM to"))
+        .arg(
+            Arg::new("publish")
+                .short('p')
+                .long("publish")
+                .value_name("HOST:GUEST[/PROTO]")
+                .action(clap::ArgAction::Append)
+                .help("Forward a host port to the MicroVM, e.g. 8080:80 or 5353:53/udp"),
+        )
+        .args(limit_args())
+        .arg(restart_arg())
         .arg(arg!(--vmlinux <path> "Path to the kernel image"))
         .arg(arg!(--rootfs <path> "Path to the root filesystem image"))
         .arg(arg!(--bridge <name> "Name of the bridge interface").default_value(BRIDGE_DEV))
@@ -242,10 +471,80 @@ fn cli() -> Command {
         )
 }
 
+fn limit_args() -> [Arg; 4] {
+    [
+        (
+            "net-rx-mbps",
+            "Limit the bandwidth received by the MicroVM, in Mbit/s",
+        ),
+        (
+            "net-tx-mbps",
+            "Limit the bandwidth sent by the MicroVM, in Mbit/s",
+        ),
+        (
+            "disk-iops",
+            "Limit the I/O operations per second of each drive",
+        ),
+        ("disk-mbps", "Limit the bandwidth of each drive, in Mbit/s"),
+    ]
+    .map(|(name, help)| {
+        Arg::new(name)
+            .long(name)
+            .value_name("N")
+            .value_parser(clap::value_parser!(u32))
+            .help(help)
+    })
+}
+
+fn restart_arg() -> Arg {
+    Arg::new("restart")
+        .long("restart")
+        .value_name("POLICY")
+        .value_parser(|policy: &str| policy.parse::<RestartPolicy>().map_err(|e| e.to_string()))
+        .help("Restart the MicroVM when it exits under `fireup serve`: no, on-failure or always")
+}
+
+fn limits(args: &ArgMatches) -> LimitsOptions {
+    let limit = |name: &str| args.get_one::<u32>(name).copied();
+    LimitsOptions {
+        net_rx_mbps: limit("net-rx-mbps"),
+        net_tx_mbps: limit("net-tx-mbps"),
+        disk_iops: limit("disk-iops"),
+        disk_mbps: limit("disk-mbps"),
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let matches = cli().get_matches();
 
+    // runs as root for as long as its MicroVM, away from the state database
+    if let Some(("serial", args)) = matches.subcommand() {
+        let command = args
+            .get_many::<String>("command")
+            .unwrap()
+            .cloned()
+            .collect::<Vec<_>>();
+        return serial(
+            args.get_one::<String>("log").unwrap(),
+            args.get_one::<String>("socket").unwrap(),
+            args.get_one::<String>("owner").map(String::as_str),
+            &command,
+        );
+    }
+
+    // started as root, it drops its privileges itself once its sockets are
+    // bound: keep the reconciler from writing the state database as root
+    if let Some(("netd", _)) = matches.subcommand() {
+        return netd().await;
+    }
+
+    // the state database may be behind Firecracker processes that exited or
+    // crashed since the last command
+    if let Err(e) = firecracker_process::reconcile::run().await {
+        println!("[!] Failed to reconcile MicroVM states: {}", e);
+    }
+
     let pool = firecracker_state::create_connection_pool().await?;
     let vm_name = names::Generator::default().next().unwrap();
     let default_socket = format!("/tmp/firecracker-{}.sock", vm_name);
@@ -266,6 +565,14 @@ async fn main() -> Result<()> {
             let tailscale_auth_key = args.get_one::<String>("tailscale-auth-key").cloned();
             start(&name, tailscale_auth_key).await?;
         }
+        Some(("pause", args)) => {
+            let name = args.get_one::<String>("name").cloned().unwrap();
+            pause(&name).await?;
+        }
+        Some(("resume", args)) => {
+            let name = args.get_one::<String>("name").cloned().unwrap();
+            resume(&name).await?;
+        }
         Some(("restart", args)) => {
             let name = args.get_one::<String>("name").cloned().unwrap();
             let tailscale_auth_key = args.get_one::<String>("tailscale-auth-key").cloned();
@@ -284,6 +591,14 @@ async fn main() -> Result<()> {
             let vmlinux = matches.get_one::<String>("vmlinux").cloned();
             let rootfs = matches.get_one::<String>("rootfs").cloned();
             let bootargs = matches.get_one::<String>("boot-args").cloned();
+            let disk_size = args
+                .get_one::<String>("disk-size")
+                .map(|s| s.parse::<u32>().unwrap());
+            let ip = args.get_one::<String>("ip").cloned();
+            let network = args.get_one::<String>("network").cloned();
+            let ports = args
+                .get_many::<String>("publish")
+                .map(|ports| ports.cloned().collect());
             let bridge = args.get_one::<String>("bridge").cloned().unwrap();
             let tap = args.get_one::<String>("tap").cloned().unwrap();
             let api_socket = args
@@ -320,22 +635,39 @@ async fn main() -> Result<()> {
                 tap,
                 api_socket,
                 mac_address,
-                etcd: None,
                 ssh_keys,
                 tailscale: tailscale_auth_key.map(|key| fire_config::TailscaleOptions {
                     auth_key: Some(key),
                 }),
+                disk_size,
+                volumes: None,
+                jailer: None,
+                ip,
+                network,
+                ports,
+                firewall: None,
+                limits: Some(limits(args)).filter(|limits| *limits != LimitsOptions::default()),
+                nics: None,
+                restart: args.get_one::<RestartPolicy>("restart").copied(),
+                project: None,
+                provision: None,
+                image: None,
             };
             up(options).await?
         }
         Some(("down", _)) => down().await?,
+        Some(("prune", _)) => prune().await?,
         Some(("status", args)) => {
             let name = args.get_one::<String>("name").cloned();
             status(name).await?;
         }
         Some(("logs", args)) => {
+            let name = args.get_one::<String>("name").cloned().unwrap();
+            let source = args.get_one::<String>("source").cloned();
             let follow = args.get_one::<bool>("follow").copied().unwrap_or(false);
-            logs(follow)?;
+            let since = args.get_one::<String>("since").cloned();
+            let tail = args.get_one::<usize>("tail").copied();
+            logs(&name, source.as_deref(), follow, since.as_deref(), tail).await?;
         }
         Some(("ssh", args)) => {
             let name = args.get_one::<String>("name").cloned();
@@ -362,6 +694,14 @@ async fn main() -> Result<()> {
             let name = args.get_one::<String>("name").cloned().unwrap();
             inspect_microvm(&name).await?;
         }
+        Some(("update", args)) => {
+            let name = args.get_one::<String>("name").cloned().unwrap();
+            update(&name, limits(args)).await?;
+        }
+        Some(("port", args)) => {
+            let name = args.get_one::<String>("name").cloned().unwrap();
+            port(&name).await?;
+        }
         Some(("exec", args)) => {
             let name = args.get_one::<String>("name").cloned().unwrap();
             let cmd_args: Vec<String> = args
@@ -371,11 +711,83 @@ async fn main() -> Result<()> {
                 .collect();
             exec(&name, cmd_args).await?;
         }
+        Some(("console", args)) => {
+            let name = args.get_one::<String>("name").cloned().unwrap();
+            let detach_keys = args.get_one::<String>("detach-keys").cloned().unwrap();
+            attach(&name, &detach_keys).await?;
+        }
         Some(("cp", args)) => {
             let source = args.get_one::<String>("source").cloned().unwrap();
             let destination = args.get_one::<String>("destination").cloned().unwrap();
             cp(&source, &destination).await?;
         }
+        Some(("snapshot", args)) => match args.subcommand() {
+            Some(("rm", args)) => {
+                let name = args.get_one::<String>("name").cloned().unwrap();
+                remove_snapshot(&name).await?;
+            }
+            _ => {
+                let name = args.get_one::<String>("name").cloned().unwrap();
+                let snapshot_name = args.get_one::<String>("snapshot").cloned();
+                snapshot(&name, snapshot_name).await?;
+            }
+        },
+        Some(("snapshots", _)) => list_snapshots().await?,
+        Some(("build", args)) => {
+            let file = args.get_one::<String>("file").cloned().unwrap();
+            let tag = args.get_one::<String>("tag").cloned().unwrap();
+            build(&file, &tag).await?;
+        }
+        Some(("images", _)) => list_images().await?,
+        Some(("volume", args)) => match args.subcommand() {
+            Some(("create", args)) => {
+                let name = args.get_one::<String>("name").cloned().unwrap();
+                let size = args
+                    .get_one::<String>("size")
+                    .map(|s| s.parse::<u32>().unwrap());
+                create_volume(&name, size).await?;
+            }
+            Some(("ls", _)) => list_volumes().await?,
+            Some(("rm", args)) => {
+                let name = args.get_one::<String>("name").cloned().unwrap();
+                remove_volume(&name).await?;
+            }
+            Some(("inspect", args)) => {
+                let name = args.get_one::<String>("name").cloned().unwrap();
+                inspect_volume(&name).await?;
+            }
+            _ => unreachable!(),
+        },
+        Some(("network", args)) => match args.subcommand() {
+            Some(("create", args)) => {
+                let name = args.get_one::<String>("name").cloned().unwrap();
+                let options = NetworkOptions {
+                    cidr: args.get_one::<String>("cidr").cloned(),
+                    bridge: args.get_one::<String>("bridge").cloned(),
+                    gateway: args.get_one::<String>("gateway").cloned(),
+                    domain: args.get_one::<String>("domain").cloned(),
+                    nat: !args.get_flag("no-nat"),
+                    ipv6: args.get_flag("ipv6"),
+                    ipv6_prefix: args.get_one::<String>("ipv6-prefix").cloned(),
+                };
+                create_network(&name, options).await?;
+            }
+            Some(("ls", _)) => list_networks().await?,
+            Some(("rm", args)) => {
+                let name = args.get_one::<String>("name").cloned().unwrap();
+                remove_network(&name).await?;
+            }
+            Some(("inspect", args)) => {
+                let name = args.get_one::<String>("name").cloned().unwrap();
+                inspect_network(&name).await?;
+            }
+            Some(("prune", _)) => prune_networks().await?,
+            _ => unreachable!(),
+        },
+        Some(("restore", args)) => {
+            let name = args.get_one::<String>("snapshot").cloned().unwrap();
+            restore(&name).await?;
+        }
         _ => {
             let debian = matches.get_one::<bool>("debian").copied().unwrap_or(false);
             let alpine = matches.get_one::<bool>("alpine").copied().unwrap_or(false);
@@ -421,6 +833,14 @@ async fn main() -> Result<()> {
             let vmlinux = matches.get_one::<String>("vmlinux").cloned();
             let rootfs = matches.get_one::<String>("rootfs").cloned();
             let bootargs = matches.get_one::<String>("boot-args").cloned();
+            let disk_size = matches
+                .get_one::<String>("disk-size")
+                .map(|s| s.parse::<u32>().unwrap());
+            let ip = matches.get_one::<String>("ip").cloned();
+            let network = matches.get_one::<String>("network").cloned();
+            let ports = matches
+                .get_many::<String>("publish")
+                .map(|ports| ports.cloned().collect());
             let bridge = matches.get_one::<String>("bridge").cloned().unwrap();
             let tap = matches.get_one::<String>("tap").cloned().unwrap();
             let api_socket = matches
@@ -458,11 +878,23 @@ async fn main() -> Result<()> {
                 tap,
                 api_socket,
                 mac_address,
-                etcd: None,
                 ssh_keys,
                 tailscale: tailscale_auth_key.map(|key| fire_config::TailscaleOptions {
                     auth_key: Some(key),
                 }),
+                disk_size,
+                volumes: None,
+                jailer: None,
+                ip,
+                network,
+                ports,
+                firewall: None,
+                limits: Some(limits(&matches)).filter(|limits| *limits != LimitsOptions::default()),
+                nics: None,
+                restart: matches.get_one::<RestartPolicy>("restart").copied(),
+                project: None,
+                provision: None,
+                image: None,
             };
             up(options).await?
         }
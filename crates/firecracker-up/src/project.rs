@@ -0,0 +1,39 @@
+use std::path::Path;
+
+use anyhow::{Context, Error};
+use fire_config::{read_config, FireConfig};
+
+/// A `fire.toml` describing several MicroVMs in its `[vms.<name>]` tables.
+pub struct Project {
+    pub name: String,
+    /// Directory of the `fire.toml`, shared by the MicroVMs of the project in
+    /// the state database.
+    pub dir: String,
+    pub config: FireConfig,
+}
+
+/// The multi-VM project of the current directory, if any. An invalid
+/// `fire.toml` is an error rather than no project, so that `up` and `down`
+/// never fall back to acting on a single or on every MicroVM.
+pub fn load() -> Result<Option<Project>, Error> {
+    if !Path::new("fire.toml").exists() {
+        return Ok(None);
+    }
+    let config = read_config().with_context(|| "Failed to parse fire.toml")?;
+    if config.vms.is_empty() {
+        return Ok(None);
+    }
+    let current_dir = std::env::current_dir()?;
+    let name = match &config.project {
+        Some(name) => name.clone(),
+        None => current_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "default".into()),
+    };
+    Ok(Some(Project {
+        name,
+        dir: current_dir.display().to_string(),
+        config,
+    }))
+}
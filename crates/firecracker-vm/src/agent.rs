@@ -0,0 +1,303 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::{fs::PermissionsExt, net::UnixStream},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use fireup_agent::{read_frame, write_frame, Request, Response, Tty, PORT};
+
+use crate::{
+    command::{is_root, run_command},
+    terminal::RawMode,
+};
+
+const CHUNK_SIZE: usize = 32 * 1024;
+
+/// Host end of the vsock device of the MicroVM with the given API socket,
+/// right next to it.
+pub fn socket(api_socket: &str) -> String {
+    format!(
+        "{}.vsock",
+        api_socket.strip_suffix(".sock").unwrap_or(api_socket)
+    )
+}
+
+/// Firecracker runs as root, hand the vsock socket over to the current user
+/// like the API socket.
+pub fn expose(api_socket: &str) -> Result<()> {
+    let socket = socket(api_socket);
+    if !is_root() && Path::new(&socket).exists() {
+        let owner = unsafe { format!("{}:{}", libc::getuid(), libc::getgid()) };
+        run_command("chown", &[&owner, &socket], true)?;
+    }
+    Ok(())
+}
+
+/// Connection to the agent, through the vsock socket of Firecracker.
+struct Connection {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Connection {
+    fn open(api_socket: &str) -> Result<Self> {
+        let socket = socket(api_socket);
+        let mut writer = UnixStream::connect(&socket)
+            .with_context(|| format!("Failed to connect to vsock socket {}", socket))?;
+        writer.set_read_timeout(Some(Duration::from_secs(5)))?;
+        writer.write_all(format!("CONNECT {}\n", PORT).as_bytes())?;
+
+        let mut reader = BufReader::new(writer.try_clone()?);
+        let mut line = String::new();
+        reader.read_line(&mut line).ok();
+        if !line.starts_with("OK ") {
+            return Err(anyhow!("The guest agent is not listening"));
+        }
+        writer.set_read_timeout(None)?;
+        Ok(Connection { reader, writer })
+    }
+
+    fn send(&mut self, request: &Request, payload: &[u8]) -> Result<()> {
+        write_frame(&mut self.writer, request, payload)
+            .with_context(|| "Failed to send request to the guest agent")
+    }
+
+    fn receive(&mut self) -> Result<Response> {
+        match read_frame(&mut self.reader)
+            .with_context(|| "Failed to read response of the guest agent")?
+        {
+            Some(Response::Error { message }) => Err(anyhow!(message)),
+            Some(response) => Ok(response),
+            None => Err(anyhow!("The guest agent closed the connection")),
+        }
+    }
+
+    /// Copies the `size` bytes following the last frame to `output`.
+    fn payload(&mut self, size: u64, output: &mut dyn Write) -> Result<()> {
+        let copied = io::copy(&mut Read::take(&mut self.reader, size), output)?;
+        if copied < size {
+            return Err(anyhow!("The guest agent closed the connection"));
+        }
+        output.flush()?;
+        Ok(())
+    }
+}
+
+fn unexpected(response: Response) -> anyhow::Error {
+    anyhow!("Unexpected response of the guest agent: {:?}", response)
+}
+
+/// Checks that the agent of the MicroVM answers.
+pub fn ping(api_socket: &str) -> Result<()> {
+    let mut connection = Connection::open(api_socket)?;
+    connection.send(&Request::Ping, &[])?;
+    match connection.receive()? {
+        Response::Booted => Ok(()),
+        response => Err(unexpected(response)),
+    }
+}
+
+/// Waits for the agent to report the guest booted. `false` when it did not
+/// within `timeout`, as with images prepared without the agent.
+pub fn wait_until_ready(api_socket: &str, timeout: Duration) -> bool {
+    let started = Instant::now();
+    loop {
+        if ping(api_socket).is_ok() {
+            return true;
+        }
+        if started.elapsed() >= timeout {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+fn terminal() -> Option<Tty> {
+    if unsafe { libc::isatty(0) } == 0 {
+        return None;
+    }
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    unsafe { libc::ioctl(1, libc::TIOCGWINSZ, &mut size) };
+    Some(Tty {
+        rows: size.ws_row.max(1),
+        cols: size.ws_col.max(1),
+        term: std::env::var("TERM").ok(),
+    })
+}
+
+/// Forwards the input of the host to the running command until it ends.
+fn forward_stdin(mut writer: UnixStream) {
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut stdin = io::stdin().lock();
+    loop {
+        match stdin.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                let request = Request::Stdin { size: n as u64 };
+                if write_frame(&mut writer, &request, &buffer[..n]).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+    write_frame(&mut writer, &Request::CloseStdin, &[]).ok();
+}
+
+fn execute(
+    api_socket: &str,
+    command: Option<&str>,
+    stdin: bool,
+    tty: bool,
+    stdout: &mut dyn Write,
+    stderr: &mut dyn Write,
+) -> Result<i32> {
+    let mut connection = Connection::open(api_socket)?;
+    let tty = tty.then(terminal).flatten();
+    connection.send(
+        &Request::Exec {
+            command: command.map(String::from),
+            tty: tty.clone(),
+        },
+        &[],
+    )?;
+    match stdin {
+        true => {
+            let writer = connection.writer.try_clone()?;
+            thread::spawn(move || forward_stdin(writer));
+        }
+        false => connection.send(&Request::CloseStdin, &[])?,
+    }
+
+    let _raw_mode = tty.and_then(|_| RawMode::enable());
+    loop {
+        match connection.receive()? {
+            Response::Stdout { size } => connection.payload(size, stdout)?,
+            Response::Stderr { size } => connection.payload(size, stderr)?,
+            Response::Exit { code } => return Ok(code),
+            response => return Err(unexpected(response)),
+        }
+    }
+}
+
+/// Runs `command` in the guest, or a login shell when it is `None`, with the
+/// input and output of the host, and returns its exit code. `tty` attaches
+/// it to a terminal when the host runs in one.
+pub fn exec(api_socket: &str, command: Option<&str>, tty: bool) -> Result<i32> {
+    execute(
+        api_socket,
+        command,
+        true,
+        tty,
+        &mut io::stdout(),
+        &mut io::stderr(),
+    )
+}
+
+fn check(command: &str, code: i32, stderr: &[u8]) -> Result<()> {
+    if code != 0 {
+        return Err(anyhow!(
+            "Command {} failed in the guest with exit code {}: {}",
+            command,
+            code,
+            String::from_utf8_lossy(stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Runs `command` in the guest and returns its output, failing with its
+/// error output when it exits with a non-zero code.
+pub fn run(api_socket: &str, command: &str) -> Result<Vec<u8>> {
+    let (mut stdout, mut stderr) = (vec![], vec![]);
+    let code = execute(
+        api_socket,
+        Some(command),
+        false,
+        false,
+        &mut stdout,
+        &mut stderr,
+    )?;
+    check(command, code, &stderr)?;
+    Ok(stdout)
+}
+
+/// Like [`run`], streaming the output of `command` to the one of the host.
+pub fn run_with_output(api_socket: &str, command: &str) -> Result<()> {
+    let code = execute(
+        api_socket,
+        Some(command),
+        false,
+        false,
+        &mut io::stdout(),
+        &mut io::stderr(),
+    )?;
+    check(command, code, &[])
+}
+
+/// Copies the local file `from` to `to` in the guest, into it when it is a
+/// directory.
+pub fn copy_to(api_socket: &str, from: &Path, to: &str) -> Result<()> {
+    let metadata =
+        fs::metadata(from).with_context(|| format!("Failed to read {}", from.display()))?;
+    if metadata.is_dir() {
+        return Err(anyhow!("{} is a directory", from.display()));
+    }
+    let file_name = from
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| anyhow!("Invalid file path: {}", from.display()))?;
+    let file =
+        fs::File::open(from).with_context(|| format!("Failed to open {}", from.display()))?;
+
+    let mut connection = Connection::open(api_socket)?;
+    let size = metadata.len();
+    connection.send(
+        &Request::Write {
+            path: to.into(),
+            file_name,
+            mode: metadata.permissions().mode() & 0o7777,
+            size,
+        },
+        &[],
+    )?;
+    let copied = io::copy(&mut file.take(size), &mut connection.writer)?;
+    // a file truncated meanwhile is padded, the agent expects `size` bytes
+    io::copy(
+        &mut io::repeat(0).take(size - copied),
+        &mut connection.writer,
+    )?;
+
+    match connection.receive()? {
+        Response::Written => Ok(()),
+        response => Err(unexpected(response)),
+    }
+}
+
+/// Copies the file `from` of the guest to `to`, into it when it is a local
+/// directory.
+pub fn copy_from(api_socket: &str, from: &str, to: &Path) -> Result<()> {
+    let mut connection = Connection::open(api_socket)?;
+    connection.send(&Request::Read { path: from.into() }, &[])?;
+    let (mode, size) = match connection.receive()? {
+        Response::File { mode, size } => (mode, size),
+        response => return Err(unexpected(response)),
+    };
+
+    let target = match to.is_dir() {
+        true => to.join(
+            Path::new(from)
+                .file_name()
+                .ok_or_else(|| anyhow!("Invalid file path: {}", from))?,
+        ),
+        false => to.to_path_buf(),
+    };
+    let mut file = fs::File::create(&target)
+        .with_context(|| format!("Failed to create {}", target.display()))?;
+    connection.payload(size, &mut file)?;
+    file.set_permissions(fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
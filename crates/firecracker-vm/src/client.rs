@@ -0,0 +1,396 @@
+use std::fmt;
+
+use anyhow::{anyhow, Context, Error, Result};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+};
+
+/// Error returned by Firecracker when it rejects an API request.
+#[derive(Debug, Clone)]
+pub struct FirecrackerError {
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub fault_message: String,
+}
+
+impl fmt::Display for FirecrackerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Firecracker rejected {} {} ({}): {}",
+            self.method, self.path, self.status, self.fault_message
+        )
+    }
+}
+
+impl std::error::Error for FirecrackerError {}
+
+#[derive(Debug, Serialize)]
+pub struct Logger {
+    pub log_path: String,
+    pub level: String,
+    pub show_level: bool,
+    pub show_log_origin: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BootSource {
+    pub kernel_image_path: String,
+    pub boot_args: String,
+}
+
+/// Token bucket refilled with `size` tokens every `refill_time` milliseconds,
+/// a zero size disables it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenBucket {
+    pub size: u64,
+    pub refill_time: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RateLimiter {
+    /// Bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bandwidth: Option<TokenBucket>,
+    /// I/O operations
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ops: Option<TokenBucket>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Drive {
+    pub drive_id: String,
+    pub path_on_host: String,
+    pub is_root_device: bool,
+    pub is_read_only: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limiter: Option<RateLimiter>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PartialDrive {
+    pub drive_id: String,
+    pub rate_limiter: RateLimiter,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NetworkInterface {
+    pub iface_id: String,
+    pub guest_mac: String,
+    pub host_dev_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rx_rate_limiter: Option<RateLimiter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_rate_limiter: Option<RateLimiter>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PartialNetworkInterface {
+    pub iface_id: String,
+    pub rx_rate_limiter: RateLimiter,
+    pub tx_rate_limiter: RateLimiter,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Vsock {
+    pub guest_cid: u32,
+    pub uds_path: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MachineConfig {
+    pub vcpu_count: u16,
+    pub mem_size_mib: u16,
+    pub smt: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub enum ActionType {
+    InstanceStart,
+    SendCtrlAltDel,
+    FlushMetrics,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Action {
+    pub action_type: ActionType,
+}
+
+#[derive(Debug, Serialize)]
+pub enum State {
+    Paused,
+    Resumed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Vm {
+    pub state: State,
+}
+
+#[derive(Debug, Serialize)]
+pub enum SnapshotType {
+    Full,
+    Diff,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotCreateParams {
+    pub snapshot_type: SnapshotType,
+    pub snapshot_path: String,
+    pub mem_file_path: String,
+}
+
+#[derive(Debug, Serialize)]
+pub enum MemoryBackendType {
+    File,
+    Uffd,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemoryBackend {
+    pub backend_type: MemoryBackendType,
+    pub backend_path: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotLoadParams {
+    pub snapshot_path: String,
+    pub mem_backend: MemoryBackend,
+    pub enable_diff_snapshots: bool,
+    pub resume_vm: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Fault {
+    fault_message: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+/// Minimal HTTP/1.1 client for the Firecracker API exposed on a Unix socket.
+#[derive(Debug, Clone)]
+pub struct FirecrackerClient {
+    api_socket: String,
+}
+
+impl FirecrackerClient {
+    pub fn new(api_socket: &str) -> Self {
+        FirecrackerClient {
+            api_socket: api_socket.to_string(),
+        }
+    }
+
+    pub async fn put_logger(&self, logger: &Logger) -> Result<()> {
+        self.put("/logger", logger).await
+    }
+
+    pub async fn put_boot_source(&self, boot_source: &BootSource) -> Result<()> {
+        self.put("/boot-source", boot_source).await
+    }
+
+    pub async fn put_drive(&self, drive: &Drive) -> Result<()> {
+        self.put(&format!("/drives/{}", drive.drive_id), drive)
+            .await
+    }
+
+    pub async fn put_network_interface(&self, iface: &NetworkInterface) -> Result<()> {
+        self.put(&format!("/network-interfaces/{}", iface.iface_id), iface)
+            .await
+    }
+
+    pub async fn patch_drive(&self, drive: &PartialDrive) -> Result<()> {
+        self.send("PATCH", &format!("/drives/{}", drive.drive_id), drive)
+            .await
+    }
+
+    pub async fn patch_network_interface(&self, iface: &PartialNetworkInterface) -> Result<()> {
+        self.send(
+            "PATCH",
+            &format!("/network-interfaces/{}", iface.iface_id),
+            iface,
+        )
+        .await
+    }
+
+    pub async fn put_vsock(&self, vsock: &Vsock) -> Result<()> {
+        self.put("/vsock", vsock).await
+    }
+
+    pub async fn put_machine_config(&self, config: &MachineConfig) -> Result<()> {
+        self.put("/machine-config", config).await
+    }
+
+    pub async fn put_action(&self, action: &Action) -> Result<()> {
+        self.put("/actions", action).await
+    }
+
+    pub async fn patch_vm(&self, vm: &Vm) -> Result<()> {
+        self.send("PATCH", "/vm", vm).await
+    }
+
+    pub async fn put_snapshot_create(&self, params: &SnapshotCreateParams) -> Result<()> {
+        self.put("/snapshot/create", params).await
+    }
+
+    pub async fn put_snapshot_load(&self, params: &SnapshotLoadParams) -> Result<()> {
+        self.put("/snapshot/load", params).await
+    }
+
+    async fn put<T: Serialize>(&self, path: &str, payload: &T) -> Result<()> {
+        self.send("PUT", path, payload).await
+    }
+
+    async fn send<T: Serialize>(&self, method: &str, path: &str, payload: &T) -> Result<()> {
+        let body = serde_json::to_vec(payload)
+            .with_context(|| format!("Failed to serialize payload for {}", path))?;
+        self.request(method, path, Some(&body)).await?;
+        Ok(())
+    }
+
+    /// Sends a request and returns the response, turning any non-2xx status
+    /// into a [`FirecrackerError`] carrying Firecracker's `fault_message`.
+    pub async fn request(&self, method: &str, path: &str, body: Option<&[u8]>) -> Result<Response> {
+        let mut stream = UnixStream::connect(&self.api_socket)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to connect to Firecracker API socket: {}",
+                    self.api_socket
+                )
+            })?;
+
+        let body = body.unwrap_or_default();
+        let mut request = format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nAccept: application/json\r\n",
+            method, path
+        );
+        if !body.is_empty() {
+            request.push_str("Content-Type: application/json\r\n");
+        }
+        request.push_str(&format!("Content-Length: {}\r\n\r\n", body.len()));
+
+        stream.write_all(request.as_bytes()).await?;
+        stream.write_all(body).await?;
+        stream.flush().await?;
+
+        let response = read_response(&mut stream)
+            .await
+            .with_context(|| format!("Failed to read response for {} {}", method, path))?;
+
+        if !(200..300).contains(&response.status) {
+            let fault_message = serde_json::from_slice::<Fault>(&response.body)
+                .map(|fault| fault.fault_message)
+                .unwrap_or_else(|_| String::from_utf8_lossy(&response.body).to_string());
+            return Err(Error::new(FirecrackerError {
+                method: method.to_string(),
+                path: path.to_string(),
+                status: response.status,
+                fault_message,
+            }));
+        }
+
+        Ok(response)
+    }
+}
+
+async fn read_response(stream: &mut UnixStream) -> Result<Response> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        if let Some(expected) = response_len(&buf)? {
+            if buf.len() >= expected {
+                break;
+            }
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    parse_response(&buf)
+}
+
+fn find_header_end(raw: &[u8]) -> Option<usize> {
+    raw.windows(4).position(|w| w == b"\r\n\r\n").map(|i| i + 4)
+}
+
+fn content_length(headers: &str) -> Result<usize> {
+    for line in headers.lines() {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                return value
+                    .trim()
+                    .parse::<usize>()
+                    .with_context(|| format!("Invalid Content-Length header: {}", value));
+            }
+        }
+    }
+    Ok(0)
+}
+
+/// Total size of the response once headers are received, `None` before that.
+fn response_len(raw: &[u8]) -> Result<Option<usize>> {
+    match find_header_end(raw) {
+        Some(end) => {
+            let headers = String::from_utf8_lossy(&raw[..end]);
+            Ok(Some(end + content_length(&headers)?))
+        }
+        None => Ok(None),
+    }
+}
+
+pub fn parse_response(raw: &[u8]) -> Result<Response> {
+    let end = find_header_end(raw).ok_or_else(|| anyhow!("Incomplete HTTP response"))?;
+    let headers = String::from_utf8_lossy(&raw[..end]);
+    let status = headers
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| anyhow!("Invalid HTTP status line: {}", headers))?;
+    let len = content_length(&headers)?;
+    let body = raw[end..].iter().take(len).copied().collect::<Vec<_>>();
+    Ok(Response { status, body })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_no_content_response() {
+        let raw = b"HTTP/1.1 204 \r\nServer: Firecracker API\r\nConnection: keep-alive\r\n\r\n";
+        let response = parse_response(raw).unwrap();
+        assert_eq!(response.status, 204);
+        assert!(response.body.is_empty());
+    }
+
+    #[test]
+    fn test_parse_fault_response() {
+        let body = r#"{"fault_message":"Invalid kernel path"}"#;
+        let raw = format!(
+            "HTTP/1.1 400 \r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let response = parse_response(raw.as_bytes()).unwrap();
+        assert_eq!(response.status, 400);
+        let fault: Fault = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(fault.fault_message, "Invalid kernel path");
+    }
+
+    #[test]
+    fn test_response_len_waits_for_headers() {
+        assert_eq!(response_len(b"HTTP/1.1 200 \r\nContent-Le").unwrap(), None);
+        let raw = b"HTTP/1.1 200 \r\nContent-Length: 2\r\n\r\n";
+        assert_eq!(response_len(raw).unwrap(), Some(raw.len() + 2));
+    }
+}
@@ -0,0 +1,298 @@
+use std::{
+    fs,
+    io::{self, Read, Write},
+    net::Shutdown,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::{
+            fs::PermissionsExt,
+            net::{UnixListener, UnixStream},
+            process::ExitStatusExt,
+        },
+    },
+    process::{Command, Stdio},
+    ptr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{command::run_command, config::get_config_dir, logs::StampedLog, terminal::RawMode};
+
+pub const DEFAULT_DETACH_KEYS: &str = "ctrl-p,ctrl-q";
+
+/// Serial console log of the VM `name`.
+pub fn log_file(name: &str) -> Result<String> {
+    let app_dir = get_config_dir().with_context(|| "Failed to get configuration directory")?;
+    Ok(format!("{}/logs/console-{}.log", app_dir, name))
+}
+
+/// Socket `fireup console` attaches to, next to the API socket of the VM.
+pub fn socket(api_socket: &str) -> String {
+    format!(
+        "{}.console",
+        api_socket.strip_suffix(".sock").unwrap_or(api_socket)
+    )
+}
+
+/// Parses detach keys such as `ctrl-p,ctrl-q` into the bytes the terminal
+/// sends for them.
+pub fn parse_detach_keys(keys: &str) -> Result<Vec<u8>> {
+    keys.split(',')
+        .map(|key| {
+            let key = key.trim();
+            let byte = match key.strip_prefix("ctrl-") {
+                Some(c) if c.len() == 1 => {
+                    let c = c.as_bytes()[0].to_ascii_uppercase();
+                    (b'@'..=b'_').contains(&c).then_some(c & 0x1f)
+                }
+                Some(_) => None,
+                None => (key.len() == 1).then(|| key.as_bytes()[0]),
+            };
+            byte.ok_or_else(|| anyhow!("Invalid detach key: {}", key))
+        })
+        .collect()
+}
+
+/// Spots the detach keys among the keys typed on the console.
+struct Detach {
+    keys: Vec<u8>,
+    matched: usize,
+}
+
+impl Detach {
+    /// Returns the keys to send to the console and whether the detach keys
+    /// were typed. The start of the sequence is held back until it is told
+    /// apart from other keys.
+    fn feed(&mut self, input: &[u8]) -> (Vec<u8>, bool) {
+        if self.keys.is_empty() {
+            return (input.to_vec(), false);
+        }
+        let mut forward = vec![];
+        for &byte in input {
+            if byte != self.keys[self.matched] {
+                forward.extend_from_slice(&self.keys[..self.matched]);
+                self.matched = 0;
+                if byte != self.keys[0] {
+                    forward.push(byte);
+                    continue;
+                }
+            }
+            self.matched += 1;
+            if self.matched == self.keys.len() {
+                self.matched = 0;
+                return (forward, true);
+            }
+        }
+        (forward, false)
+    }
+}
+
+fn openpty() -> io::Result<(OwnedFd, OwnedFd)> {
+    let (mut master, mut slave) = (0, 0);
+    let ret = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            ptr::null_mut(),
+            ptr::null(),
+            ptr::null(),
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let pty = unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+
+    // the bytes of the guest go through untouched, it echoes what is typed
+    unsafe {
+        let mut termios = std::mem::zeroed();
+        if libc::tcgetattr(pty.1.as_raw_fd(), &mut termios) == 0 {
+            libc::cfmakeraw(&mut termios);
+            libc::tcsetattr(pty.1.as_raw_fd(), libc::TCSANOW, &termios);
+        }
+    }
+    Ok(pty)
+}
+
+/// Sends what is typed by an attached client to the console.
+fn forward_input(mut client: UnixStream, console: Arc<Mutex<fs::File>>) {
+    let mut buffer = [0; 1024];
+    while let Ok(n) = client.read(&mut buffer) {
+        if n == 0 {
+            break;
+        }
+        let mut console = console.lock().unwrap_or_else(|e| e.into_inner());
+        if console.write_all(&buffer[..n]).is_err() {
+            break;
+        }
+    }
+}
+
+/// Runs `program` with a pseudo terminal as its serial console, run by
+/// `fireup serial`: what the guest prints is kept in `log` and relayed to the
+/// clients of `socket`, whose keys are typed on the console. Returns the exit
+/// code of `program`.
+pub fn relay(
+    log: &str,
+    socket: &str,
+    owner: Option<&str>,
+    program: &str,
+    args: &[String],
+) -> Result<i32> {
+    // outlive the terminal fireup was started from
+    unsafe {
+        libc::signal(libc::SIGHUP, libc::SIG_IGN);
+        libc::setsid();
+    }
+
+    let (master, slave) = openpty().with_context(|| "Failed to open a pseudo terminal")?;
+    let mut ring =
+        StampedLog::open(log).with_context(|| format!("Failed to open console log: {}", log))?;
+    fs::remove_file(socket).ok();
+    let listener = UnixListener::bind(socket)
+        .with_context(|| format!("Failed to bind console socket: {}", socket))?;
+    fs::set_permissions(socket, fs::Permissions::from_mode(0o600))?;
+    if let Some(owner) = owner {
+        run_command("chown", &[owner, socket, log], false)?;
+    }
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::from(slave.try_clone()?))
+        .stdout(Stdio::from(slave.try_clone()?))
+        .stderr(Stdio::from(slave))
+        .spawn()
+        .with_context(|| format!("Failed to execute {}", program))?;
+
+    let clients: Arc<Mutex<Vec<UnixStream>>> = Arc::default();
+    let console = Arc::new(Mutex::new(fs::File::from(master.try_clone()?)));
+    {
+        let clients = clients.clone();
+        thread::spawn(move || {
+            for client in listener.incoming().flatten() {
+                if let Ok(writer) = client.try_clone() {
+                    // a client that stops reading is dropped, not waited for
+                    writer.set_write_timeout(Some(Duration::from_secs(1))).ok();
+                    clients
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .push(writer);
+                }
+                let console = console.clone();
+                thread::spawn(move || forward_input(client, console));
+            }
+        });
+    }
+
+    let mut output = fs::File::from(master);
+    let mut buffer = [0; 4096];
+    loop {
+        let n = match output.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            // the program exited and closed the console
+            Err(_) => break,
+        };
+        ring.write(&buffer[..n]).ok();
+        clients
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain_mut(|client| client.write_all(&buffer[..n]).is_ok());
+    }
+
+    let status = child.wait()?;
+    fs::remove_file(socket).ok();
+    Ok(status
+        .code()
+        .unwrap_or_else(|| 128 + status.signal().unwrap_or_default()))
+}
+
+/// Attaches the terminal to the serial console of a running MicroVM, until
+/// the `detach_keys` are typed or the MicroVM stops. Returns whether it was
+/// detached.
+pub fn attach(api_socket: &str, detach_keys: &[u8]) -> Result<bool> {
+    let socket = socket(api_socket);
+    let mut stream = UnixStream::connect(&socket).with_context(|| {
+        format!(
+            "Failed to connect to console socket {}, restart the MicroVM to capture its console",
+            socket
+        )
+    })?;
+    let mut writer = stream.try_clone()?;
+    let detached = Arc::new(AtomicBool::new(false));
+
+    let _raw_mode = RawMode::enable();
+    {
+        let detached = detached.clone();
+        let mut detach = Detach {
+            keys: detach_keys.to_vec(),
+            matched: 0,
+        };
+        thread::spawn(move || {
+            let mut buffer = [0; 1024];
+            let mut stdin = io::stdin().lock();
+            while let Ok(n) = stdin.read(&mut buffer) {
+                if n == 0 {
+                    break;
+                }
+                let (keys, done) = detach.feed(&buffer[..n]);
+                if writer.write_all(&keys).is_err() {
+                    break;
+                }
+                if done {
+                    detached.store(true, Ordering::SeqCst);
+                    break;
+                }
+            }
+            // ends the output loop below
+            writer.shutdown(Shutdown::Both).ok();
+        });
+    }
+
+    let mut stdout = io::stdout();
+    let mut buffer = [0; 4096];
+    loop {
+        match stream.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                stdout.write_all(&buffer[..n])?;
+                stdout.flush()?;
+            }
+        }
+    }
+    Ok(detached.load(Ordering::SeqCst))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detach_keys() {
+        assert_eq!(
+            parse_detach_keys("ctrl-p,ctrl-q").unwrap(),
+            vec![0x10, 0x11]
+        );
+        assert_eq!(parse_detach_keys("ctrl-],q").unwrap(), vec![0x1d, b'q']);
+        assert!(parse_detach_keys("ctrl-").is_err());
+        assert!(parse_detach_keys("alt-x").is_err());
+
+        let mut detach = Detach {
+            keys: vec![0x10, 0x11],
+            matched: 0,
+        };
+        assert_eq!(detach.feed(b"ls\r"), (b"ls\r".to_vec(), false));
+        // a lone ctrl-p is held back, then sent along with the next key
+        assert_eq!(detach.feed(&[0x10]), (vec![], false));
+        assert_eq!(detach.feed(b"a"), (vec![0x10, b'a'], false));
+        assert_eq!(detach.feed(&[0x10, 0x10]), (vec![0x10], false));
+        assert_eq!(detach.feed(&[0x11, b'x']), (vec![], true));
+    }
+}
@@ -2,5 +2,17 @@ pub const BRIDGE_DEV: &str = "br0";
 pub const FIRECRACKER_SOCKET: &str = "/tmp/firecracker.sock";
 pub const BRIDGE_IP: &str = "172.16.0.1";
 pub const FC_MAC: &str = "06:00:AC:10:00:02";
-pub const MASK_SHORT: &str = "/16";
+/// Network VMs are attached to when none is specified, on `BRIDGE_DEV`.
+pub const DEFAULT_NETWORK: &str = "default";
+pub const DEFAULT_CIDR: &str = "172.16.0.0/16";
+/// Lease time in seconds handed out by the embedded DHCP server.
+pub const DHCP_LEASE_TIME: u32 = 3600;
+/// Domain answered by the embedded DNS server, `<name>.firecracker`.
+pub const DNS_DOMAIN: &str = "firecracker";
+pub const DNS_TTL: u32 = 30;
 pub const GUEST_IP: &str = "vm0.firecracker.local";
+pub const DEFAULT_DISK_SIZE: u32 = 2048;
+pub const DEFAULT_VOLUME_SIZE: u32 = 1024;
+pub const JAILER_UID_BASE: u32 = 40000;
+/// Memory in MiB allowed on top of the guest memory in the jail cgroup.
+pub const JAILER_MEMORY_OVERHEAD: u64 = 256;
@@ -1,128 +1,594 @@
-use anyhow::Error;
-
-use crate::{command::run_command, constants::BRIDGE_DEV, types::VmOptions};
-
-pub const DHCPD_CONFIG_PATH: &str = "/etc/kea/kea-dhcp4.conf";
-
-pub fn setup_kea_dhcp(_config: &VmOptions) -> Result<(), Error> {
-    println!("[+] Checking if isc-kea-dhcp-server is installed...");
-    if is_kea_dhcp_installed().is_err() {
-        run_command(
-            "apt-get",
-            &[
-                "install",
-                "-y",
-                "kea-dhcp4-server",
-                "kea-admin",
-                "kea-common",
-                "etcd-client",
-                "etcd-server",
-            ],
-            true,
-        )?;
-    }
-
-    const KEA_MQTT_HOOK_SH: &str = include_str!("./scripts/kea-mqtt-hook.sh");
-    println!("[+] Installing kea-mqtt-hook.sh script...");
-    std::fs::write("/tmp/kea-mqtt-hook.sh", KEA_MQTT_HOOK_SH)?;
-    run_command("cp", &["/tmp/kea-mqtt-hook.sh", "/usr/local/bin"], true)?;
-    run_command("chmod", &["a+x", "/usr/local/bin/kea-mqtt-hook.sh"], true)?;
-    run_command("rm", &["/tmp/kea-mqtt-hook.sh"], false)?;
-
-    println!("[+] Setting up AppArmor for kea-dhcp4...");
-    std::fs::write(
-        "/tmp/usr.sbin.kea-dhcp4",
-        include_str!("./apparmor/usr.sbin.kea-dhcp4"),
-    )?;
-    run_command("cp", &["/tmp/usr.sbin.kea-dhcp4", "/etc/apparmor.d/"], true)?;
-    run_command("rm", &["/tmp/usr.sbin.kea-dhcp4"], false)?;
-    run_command(
-        "apparmor_parser",
-        &["-r", "/etc/apparmor.d/usr.sbin.kea-dhcp4"],
-        true,
-    )?;
-
-    let kea_dhcp_config: &str = &format!(
-        r#"
-{{
-  "Dhcp4": {{
-    "valid-lifetime": 4000,
-    "renew-timer": 1000,
-    "rebind-timer": 2000,
-    "interfaces-config": {{
-      "interfaces": [ "{}" ]
-    }},
-    "lease-database": {{
-      "type": "memfile",
-      "lfc-interval": 3600
-    }},
-    "subnet4": [
-      {{
-        "subnet": "172.16.0.0/16",
-        "pools": [ {{ "pool": "172.16.0.2 - 172.16.255.254" }} ],
-        "option-data": [
-          {{ "name": "routers", "data": "172.16.0.1" }},
-          {{ "name": "domain-name-servers", "data": "172.16.0.1" }}
-        ]
-    }}
-    ],
-    "hooks-libraries": [
-      {{
-        "library": "/usr/lib/x86_64-linux-gnu/kea/hooks/libdhcp_run_script.so",
-        "parameters": {{
-          "name": "/usr/local/bin/kea-mqtt-hook.sh"
-         }}
-      }}
-    ],
-    "loggers": [
-      {{
-        "name": "kea-dhcp4",
-        "severity": "DEBUG",
-        "debuglevel": 99
-      }}
-    ]
-  }}
-}}
-"#,
-        BRIDGE_DEV
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, SocketAddrV4, UdpSocket},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use firecracker_state::{
+    entity::{lease::Lease, network::Network},
+    repo,
+};
+use owo_colors::OwoColorize;
+use sqlx::{Pool, Sqlite};
+use tokio::sync::Notify;
+
+use crate::{
+    constants::DHCP_LEASE_TIME,
+    netd::bind_udp,
+    network::{self, Cidr},
+};
+
+const SERVER_PORT: u16 = 67;
+const CLIENT_PORT: u16 = 68;
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+const HEADER_LEN: usize = 236;
+
+const DHCPDISCOVER: u8 = 1;
+const DHCPOFFER: u8 = 2;
+const DHCPREQUEST: u8 = 3;
+const DHCPDECLINE: u8 = 4;
+const DHCPACK: u8 = 5;
+const DHCPNAK: u8 = 6;
+const DHCPRELEASE: u8 = 7;
+const DHCPINFORM: u8 = 8;
+
+const OPT_PAD: u8 = 0;
+const OPT_SUBNET_MASK: u8 = 1;
+const OPT_ROUTER: u8 = 3;
+const OPT_DNS: u8 = 6;
+const OPT_HOSTNAME: u8 = 12;
+const OPT_DOMAIN_NAME: u8 = 15;
+const OPT_REQUESTED_IP: u8 = 50;
+const OPT_LEASE_TIME: u8 = 51;
+const OPT_MESSAGE_TYPE: u8 = 53;
+const OPT_SERVER_ID: u8 = 54;
+const OPT_RENEWAL_TIME: u8 = 58;
+const OPT_REBINDING_TIME: u8 = 59;
+const OPT_END: u8 = 255;
+
+/// Wakes up the waiters of this process whenever a lease is committed. Only
+/// useful when the DHCP server runs in the same process, i.e. under
+/// `fireup serve`: the CLI is served by `fireup netd` and relies on polling.
+fn notifier() -> &'static Notify {
+    static NOTIFY: OnceLock<Notify> = OnceLock::new();
+    NOTIFY.get_or_init(Notify::new)
+}
+
+/// Addressing of the network served by a DHCP server instance.
+#[derive(Debug, Clone)]
+pub struct Scope {
+    pub gateway: Ipv4Addr,
+    pub cidr: Cidr,
+    pub domain: String,
+}
+
+impl Scope {
+    pub fn new(network: &Network) -> Result<Self> {
+        Ok(Scope {
+            gateway: network
+                .gateway
+                .parse()
+                .with_context(|| format!("Invalid gateway address: {}", network.gateway))?,
+            cidr: network::cidr(network)?,
+            domain: network.domain.clone(),
+        })
+    }
+
+    /// Whether `ip` can be handed out to a client.
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        self.cidr.contains(ip)
+            && ip != self.cidr.network()
+            && ip != self.cidr.broadcast()
+            && ip != self.gateway
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub op: u8,
+    pub htype: u8,
+    pub hlen: u8,
+    pub xid: u32,
+    pub flags: u16,
+    pub ciaddr: Ipv4Addr,
+    pub yiaddr: Ipv4Addr,
+    pub siaddr: Ipv4Addr,
+    pub giaddr: Ipv4Addr,
+    pub chaddr: [u8; 16],
+    pub options: Vec<(u8, Vec<u8>)>,
+}
+
+impl Message {
+    pub fn parse(buf: &[u8]) -> Result<Self> {
+        if buf.len() < HEADER_LEN + MAGIC_COOKIE.len() {
+            return Err(anyhow!("DHCP message too short ({} bytes)", buf.len()));
+        }
+        if buf[HEADER_LEN..HEADER_LEN + 4] != MAGIC_COOKIE {
+            return Err(anyhow!("Invalid DHCP magic cookie"));
+        }
+
+        let ip = |at: usize| Ipv4Addr::new(buf[at], buf[at + 1], buf[at + 2], buf[at + 3]);
+        let mut chaddr = [0u8; 16];
+        chaddr.copy_from_slice(&buf[28..44]);
+
+        let mut options = vec![];
+        let mut i = HEADER_LEN + 4;
+        while i < buf.len() {
+            match buf[i] {
+                OPT_PAD => i += 1,
+                OPT_END => break,
+                code => {
+                    let len = *buf
+                        .get(i + 1)
+                        .ok_or_else(|| anyhow!("Truncated DHCP option {}", code))?
+                        as usize;
+                    let data = buf
+                        .get(i + 2..i + 2 + len)
+                        .ok_or_else(|| anyhow!("Truncated DHCP option {}", code))?;
+                    options.push((code, data.to_vec()));
+                    i += 2 + len;
+                }
+            }
+        }
+
+        Ok(Message {
+            op: buf[0],
+            htype: buf[1],
+            hlen: buf[2],
+            xid: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            flags: u16::from_be_bytes([buf[10], buf[11]]),
+            ciaddr: ip(12),
+            yiaddr: ip(16),
+            siaddr: ip(20),
+            giaddr: ip(24),
+            chaddr,
+            options,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![0u8; HEADER_LEN];
+        buf[0] = self.op;
+        buf[1] = self.htype;
+        buf[2] = self.hlen;
+        buf[4..8].copy_from_slice(&self.xid.to_be_bytes());
+        buf[10..12].copy_from_slice(&self.flags.to_be_bytes());
+        buf[12..16].copy_from_slice(&self.ciaddr.octets());
+        buf[16..20].copy_from_slice(&self.yiaddr.octets());
+        buf[20..24].copy_from_slice(&self.siaddr.octets());
+        buf[24..28].copy_from_slice(&self.giaddr.octets());
+        buf[28..44].copy_from_slice(&self.chaddr);
+        buf.extend_from_slice(&MAGIC_COOKIE);
+        for (code, data) in &self.options {
+            buf.push(*code);
+            buf.push(data.len() as u8);
+            buf.extend_from_slice(data);
+        }
+        buf.push(OPT_END);
+        // BOOTP relays expect at least 300 bytes
+        if buf.len() < 300 {
+            buf.resize(300, OPT_PAD);
+        }
+        buf
+    }
+
+    pub fn option(&self, code: u8) -> Option<&[u8]> {
+        self.options
+            .iter()
+            .find(|(c, _)| *c == code)
+            .map(|(_, data)| data.as_slice())
+    }
+
+    pub fn message_type(&self) -> Option<u8> {
+        self.option(OPT_MESSAGE_TYPE)
+            .and_then(|data| data.first().copied())
+    }
+
+    pub fn requested_ip(&self) -> Option<Ipv4Addr> {
+        self.option(OPT_REQUESTED_IP)
+            .and_then(to_ipv4)
+            .or_else(|| (!self.ciaddr.is_unspecified()).then_some(self.ciaddr))
+    }
+
+    pub fn server_id(&self) -> Option<Ipv4Addr> {
+        self.option(OPT_SERVER_ID).and_then(to_ipv4)
+    }
+
+    pub fn hostname(&self) -> Option<String> {
+        self.option(OPT_HOSTNAME)
+            .map(|data| String::from_utf8_lossy(data).to_string())
+    }
+
+    pub fn mac_address(&self) -> String {
+        let len = (self.hlen as usize).min(16);
+        self.chaddr[..len]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    fn reply(&self, message_type: u8, yiaddr: Ipv4Addr, scope: &Scope) -> Message {
+        let server_ip = scope.gateway;
+        let mut options = vec![
+            (OPT_MESSAGE_TYPE, vec![message_type]),
+            (OPT_SERVER_ID, server_ip.octets().to_vec()),
+        ];
+        if message_type != DHCPNAK {
+            if message_type != DHCPACK || !yiaddr.is_unspecified() {
+                options.push((OPT_LEASE_TIME, DHCP_LEASE_TIME.to_be_bytes().to_vec()));
+                options.push((
+                    OPT_RENEWAL_TIME,
+                    (DHCP_LEASE_TIME / 2).to_be_bytes().to_vec(),
+                ));
+                options.push((
+                    OPT_REBINDING_TIME,
+                    (DHCP_LEASE_TIME / 8 * 7).to_be_bytes().to_vec(),
+                ));
+            }
+            options.push((OPT_SUBNET_MASK, scope.cidr.netmask().octets().to_vec()));
+            options.push((OPT_ROUTER, server_ip.octets().to_vec()));
+            options.push((OPT_DNS, server_ip.octets().to_vec()));
+            options.push((OPT_DOMAIN_NAME, scope.domain.as_bytes().to_vec()));
+        }
+
+        Message {
+            op: 2,
+            htype: self.htype,
+            hlen: self.hlen,
+            xid: self.xid,
+            flags: self.flags,
+            ciaddr: match message_type {
+                DHCPNAK => Ipv4Addr::UNSPECIFIED,
+                _ => self.ciaddr,
+            },
+            yiaddr,
+            siaddr: Ipv4Addr::UNSPECIFIED,
+            giaddr: self.giaddr,
+            chaddr: self.chaddr,
+            options,
+        }
+    }
+}
+
+fn to_ipv4(data: &[u8]) -> Option<Ipv4Addr> {
+    (data.len() == 4).then(|| Ipv4Addr::new(data[0], data[1], data[2], data[3]))
+}
+
+/// Binds the DHCP server port on the given bridge, requires root.
+pub fn bind(bridge: &str) -> Result<UdpSocket> {
+    let socket = bind_udp(
+        SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, SERVER_PORT),
+        Some(bridge),
+    )
+    .with_context(|| {
+        format!(
+            "Failed to bind DHCP server on {} port {}, is another DHCP server running?",
+            bridge, SERVER_PORT
+        )
+    })?;
+    socket.set_broadcast(true)?;
+    Ok(socket)
+}
+
+pub async fn serve(socket: UdpSocket, network: Network) -> Result<()> {
+    let socket = tokio::net::UdpSocket::from_std(socket)?;
+    let pool = firecracker_state::create_connection_pool().await?;
+    let scope = Scope::new(&network)?;
+    let mut buf = [0u8; 1500];
+
+    println!(
+        "[+] DHCP server listening on {} for {}",
+        network.bridge, network.cidr
     );
+    loop {
+        let (len, _) = socket.recv_from(&mut buf).await?;
+        let request = match Message::parse(&buf[..len]) {
+            Ok(message) if message.op == 1 => message,
+            Ok(_) => continue,
+            Err(e) => {
+                eprintln!("[!] Ignoring invalid DHCP message: {}", e);
+                continue;
+            }
+        };
 
-    run_command(
-        "sh",
-        &[
-            "-c",
-            &format!("echo '{}' > {}", kea_dhcp_config, DHCPD_CONFIG_PATH),
-        ],
-        true,
-    )?;
+        let reply = match handle(&pool, &scope, &request).await {
+            Ok(Some(reply)) => reply,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!(
+                    "[!] Failed to handle DHCP message from {}: {}",
+                    request.mac_address(),
+                    e
+                );
+                continue;
+            }
+        };
 
-    restart_kea_dhcp()?;
+        let destination = if !request.giaddr.is_unspecified() {
+            SocketAddrV4::new(request.giaddr, SERVER_PORT)
+        } else if !request.ciaddr.is_unspecified() && reply.message_type() != Some(DHCPNAK) {
+            SocketAddrV4::new(request.ciaddr, CLIENT_PORT)
+        } else {
+            SocketAddrV4::new(Ipv4Addr::BROADCAST, CLIENT_PORT)
+        };
+        if let Err(e) = socket.send_to(&reply.to_bytes(), destination).await {
+            eprintln!("[!] Failed to send DHCP reply to {}: {}", destination, e);
+        }
+    }
+}
 
-    Ok(())
+async fn handle(pool: &Pool<Sqlite>, scope: &Scope, request: &Message) -> Result<Option<Message>> {
+    let mac_address = request.mac_address();
+    let server_ip = scope.gateway;
+
+    match request.message_type() {
+        Some(DHCPDISCOVER) => {
+            let ip = allocate(pool, scope, &mac_address, request.requested_ip()).await?;
+            Ok(ip.map(|ip| {
+                println!("[+] DHCPOFFER {} to {}", ip, mac_address);
+                request.reply(DHCPOFFER, ip, scope)
+            }))
+        }
+        Some(DHCPREQUEST) => {
+            if request.server_id().is_some_and(|id| id != server_ip) {
+                // the client picked another server
+                return Ok(None);
+            }
+            let requested = match request.requested_ip() {
+                Some(ip) => ip,
+                None => return Ok(Some(request.reply(DHCPNAK, Ipv4Addr::UNSPECIFIED, scope))),
+            };
+            if !is_available(pool, scope, &mac_address, requested).await? {
+                println!("[!] DHCPNAK {} to {}", requested, mac_address);
+                return Ok(Some(request.reply(DHCPNAK, Ipv4Addr::UNSPECIFIED, scope)));
+            }
+
+            let now = Utc::now();
+            repo::lease::upsert(
+                pool,
+                Lease {
+                    mac_address: mac_address.clone(),
+                    ip_address: requested.to_string(),
+                    hostname: request.hostname(),
+                    reserved: false,
+                    expires_at: now + chrono::Duration::seconds(DHCP_LEASE_TIME as i64),
+                    updated_at: now,
+                },
+            )
+            .await?;
+            repo::virtual_machine::update_ip_address(pool, &mac_address, &requested.to_string())
+                .await?;
+            notifier().notify_waiters();
+
+            println!("[+] DHCPACK {} to {}", requested, mac_address);
+            Ok(Some(request.reply(DHCPACK, requested, scope)))
+        }
+        Some(DHCPRELEASE) | Some(DHCPDECLINE) => {
+            repo::lease::release(pool, &mac_address).await?;
+            Ok(None)
+        }
+        Some(DHCPINFORM) => Ok(Some(request.reply(DHCPACK, Ipv4Addr::UNSPECIFIED, scope))),
+        _ => Ok(None),
+    }
+}
+
+async fn is_available(
+    pool: &Pool<Sqlite>,
+    scope: &Scope,
+    mac_address: &str,
+    ip: Ipv4Addr,
+) -> Result<bool> {
+    if !scope.contains(ip) {
+        return Ok(false);
+    }
+    if let Some(lease) = repo::lease::find_by_mac(pool, mac_address).await? {
+        if lease.reserved && lease.ip_address != ip.to_string() {
+            return Ok(false);
+        }
+    }
+    Ok(
+        match repo::lease::find_by_ip(pool, &ip.to_string()).await? {
+            Some(lease) => {
+                lease.mac_address == mac_address
+                    || (!lease.reserved && lease.expires_at < Utc::now())
+            }
+            None => true,
+        },
+    )
+}
+
+/// Picks the address to offer: the client's current lease, then the address
+/// it asked for, then the first free address of the range.
+async fn allocate(
+    pool: &Pool<Sqlite>,
+    scope: &Scope,
+    mac_address: &str,
+    requested: Option<Ipv4Addr>,
+) -> Result<Option<Ipv4Addr>> {
+    if let Some(lease) = repo::lease::find_by_mac(pool, mac_address).await? {
+        let ip: Ipv4Addr = lease.ip_address.parse()?;
+        if is_available(pool, scope, mac_address, ip).await? {
+            return Ok(Some(ip));
+        }
+    }
+
+    if let Some(ip) = requested {
+        if is_available(pool, scope, mac_address, ip).await? {
+            return Ok(Some(ip));
+        }
+    }
+
+    let now = Utc::now();
+    let taken = repo::lease::all(pool)
+        .await?
+        .into_iter()
+        .filter(|lease| lease.reserved || lease.expires_at >= now)
+        .map(|lease| (lease.ip_address, lease.mac_address))
+        .collect::<HashMap<_, _>>();
+
+    let start = u32::from(scope.cidr.first_host());
+    let end = u32::from(scope.cidr.last_host());
+    Ok((start..=end)
+        .map(Ipv4Addr::from)
+        .find(|ip| scope.contains(*ip) && !taken.contains_key(&ip.to_string())))
 }
 
-pub fn restart_kea_dhcp() -> Result<(), Error> {
-    println!("[+] Starting kea-dhcp4-server...");
+/// Reserves `ip_address` for the VM `name`, making sure no other VM is
+/// pinned to it or currently holding it.
+pub async fn reserve(
+    name: &str,
+    mac_address: &str,
+    ip_address: &str,
+    network: &Network,
+) -> Result<()> {
+    let ip: Ipv4Addr = ip_address
+        .parse()
+        .with_context(|| format!("Invalid IPv4 address: {}", ip_address))?;
+    let scope = Scope::new(network)?;
+    if !scope.contains(ip) {
+        return Err(anyhow!(
+            "IP address {} is not a host of network '{}' ({}) or is its gateway",
+            ip,
+            network.name,
+            network.cidr
+        ));
+    }
 
-    let dummy_is_up = run_command("ip", &["link", "show", "dummy0"], false)
-        .map(|output| output.status.success())
-        .unwrap_or(false);
-    if !dummy_is_up {
-        println!("[+] Creating dummy0 interface...");
-        run_command("ip", &["link", "add", "dummy0", "type", "dummy"], true)?;
-        run_command("ip", &["link", "set", "dummy0", "up"], true)?;
-        run_command("ip", &["link", "set", "dummy0", "master", BRIDGE_DEV], true)?;
+    let pool = firecracker_state::create_connection_pool().await?;
+    let vms = repo::virtual_machine::all(&pool).await?;
+    if let Some(vm) = vms.iter().find(|vm| {
+        vm.name != name
+            && (vm.static_ip.as_deref() == Some(ip_address)
+                || (vm.ipv4_address().as_deref() == Some(ip_address)
+                    && (vm.status == "RUNNING" || vm.status == "PAUSED")))
+    }) {
+        return Err(anyhow!(
+            "IP address {} is already used by MicroVM {}",
+            ip,
+            vm.name.cyan()
+        ));
+    }
+    // the VM may come back with a new MAC address, drop its previous lease
+    if let Some(vm) = vms
+        .iter()
+        .find(|vm| vm.name == name && !vm.mac_address.eq_ignore_ascii_case(mac_address))
+    {
+        repo::lease::delete_by_mac(&pool, &vm.mac_address).await?;
+    }
+    if !is_available(&pool, &scope, &mac_address.to_lowercase(), ip).await? {
+        return Err(anyhow!("IP address {} is already leased", ip));
     }
 
-    run_command("systemctl", &["enable", "kea-dhcp4-server"], true)?;
-    run_command("systemctl", &["daemon-reload"], true)?;
-    run_command("systemctl", &["stop", "kea-dhcp4-server"], true)?;
-    run_command("systemctl", &["start", "kea-dhcp4-server"], true)?;
-    println!("[✓] kea-dhcp4-server started successfully.");
+    repo::lease::reserve(&pool, mac_address, ip_address, name).await?;
+    println!("[+] Reserved IP address {} for {}", ip, name.cyan());
     Ok(())
 }
 
-pub fn is_kea_dhcp_installed() -> Result<bool, Error> {
-    let output = run_command("which", &["kea-dhcp4"], false)?;
-    Ok(output.status.success())
+/// Reserves the next free address of `network` for the extra interface
+/// `mac_address` of the VM `name`, keeping the one it already holds.
+pub async fn assign(name: &str, mac_address: &str, network: &Network) -> Result<String> {
+    let scope = Scope::new(network)?;
+    let pool = firecracker_state::create_connection_pool().await?;
+    let ip = allocate(&pool, &scope, &mac_address.to_lowercase(), None)
+        .await?
+        .ok_or_else(|| anyhow!("No IP address left in network '{}'", network.name))?;
+    repo::lease::reserve(&pool, mac_address, &ip.to_string(), name).await?;
+    Ok(ip.to_string())
+}
+
+/// Waits until the DHCP server commits a lease for `mac_address` after `since`
+/// and returns the leased IP address.
+pub async fn wait_for_lease(mac_address: &str, since: DateTime<Utc>) -> Result<String> {
+    println!("[*] Waiting for VM to obtain an IP address...");
+    let pool = firecracker_state::create_connection_pool().await?;
+
+    for _ in 0..120 {
+        if let Some(lease) = repo::lease::find_by_mac(&pool, mac_address).await? {
+            if lease.updated_at >= since {
+                println!("[+] VM obtained IP address {}", lease.ip_address);
+                return Ok(lease.ip_address);
+            }
+        }
+        // polling alone when the DHCP server runs in another process
+        tokio::select! {
+            _ = notifier().notified() => {}
+            _ = tokio::time::sleep(Duration::from_millis(500)) => {}
+        }
+    }
+
+    Err(anyhow!(
+        "Timed out waiting for {} to obtain an IP address",
+        mac_address
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn discover() -> Message {
+        let mut chaddr = [0u8; 16];
+        chaddr[..6].copy_from_slice(&[0x06, 0x00, 0xac, 0x10, 0x00, 0x02]);
+        Message {
+            op: 1,
+            htype: 1,
+            hlen: 6,
+            xid: 0xdeadbeef,
+            flags: 0x8000,
+            ciaddr: Ipv4Addr::UNSPECIFIED,
+            yiaddr: Ipv4Addr::UNSPECIFIED,
+            siaddr: Ipv4Addr::UNSPECIFIED,
+            giaddr: Ipv4Addr::UNSPECIFIED,
+            chaddr,
+            options: vec![
+                (OPT_MESSAGE_TYPE, vec![DHCPDISCOVER]),
+                (OPT_REQUESTED_IP, vec![172, 16, 0, 42]),
+                (OPT_HOSTNAME, b"ubuntu".to_vec()),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_message_roundtrip() {
+        let message = discover();
+        let bytes = message.to_bytes();
+        assert_eq!(bytes.len(), 300);
+
+        let parsed = Message::parse(&bytes).unwrap();
+        assert_eq!(parsed, message);
+        assert_eq!(parsed.message_type(), Some(DHCPDISCOVER));
+        assert_eq!(parsed.mac_address(), "06:00:ac:10:00:02");
+        assert_eq!(parsed.requested_ip(), Some(Ipv4Addr::new(172, 16, 0, 42)));
+        assert_eq!(parsed.hostname().as_deref(), Some("ubuntu"));
+    }
+
+    #[test]
+    fn test_reply_offer() {
+        let request = discover();
+        let scope = Scope::new(&network::default_network()).unwrap();
+        let reply = request.reply(DHCPOFFER, Ipv4Addr::new(172, 16, 0, 2), &scope);
+        let reply = Message::parse(&reply.to_bytes()).unwrap();
+        assert_eq!(reply.op, 2);
+        assert_eq!(reply.xid, request.xid);
+        assert_eq!(reply.message_type(), Some(DHCPOFFER));
+        assert_eq!(reply.yiaddr, Ipv4Addr::new(172, 16, 0, 2));
+        assert_eq!(reply.server_id(), Some(Ipv4Addr::new(172, 16, 0, 1)));
+        assert_eq!(reply.option(OPT_DOMAIN_NAME), Some(&b"firecracker"[..]));
+        assert_eq!(
+            reply.option(OPT_LEASE_TIME),
+            Some(&DHCP_LEASE_TIME.to_be_bytes()[..])
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_cookie() {
+        let mut bytes = discover().to_bytes();
+        bytes[HEADER_LEN] = 0;
+        assert!(Message::parse(&bytes).is_err());
+        assert!(Message::parse(&bytes[..100]).is_err());
+    }
 }
@@ -0,0 +1,60 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use firecracker_prepare::rootfs::create_ext4_filesystem;
+use owo_colors::OwoColorize;
+
+use crate::config::get_config_dir;
+
+pub fn disk_path(name: &str) -> Result<String> {
+    let app_dir = get_config_dir().with_context(|| "Failed to get configuration directory")?;
+    Ok(format!("{}/disks/{}.img", app_dir, name))
+}
+
+/// Creates the writable ext4 overlay disk of a MicroVM, an existing disk is
+/// reused so the data survives restarts.
+pub fn setup_disk(name: &str, size: u32) -> Result<String> {
+    let disk = disk_path(name)?;
+    if Path::new(&disk).exists() {
+        println!("[+] Using persistent disk {}", disk.cyan());
+        return Ok(disk);
+    }
+
+    let app_dir = get_config_dir().with_context(|| "Failed to get configuration directory")?;
+    fs::create_dir_all(format!("{}/disks", app_dir))
+        .with_context(|| format!("Failed to create disks directory: {}", app_dir))?;
+
+    println!(
+        "[+] Creating persistent disk {} ({} MiB)...",
+        disk.cyan(),
+        size
+    );
+    create_image(&disk, size)
+        .with_context(|| format!("Failed to create persistent disk: {}", disk))?;
+
+    Ok(disk)
+}
+
+/// Formats an empty ext4 image of `size` MiB at `path`.
+pub(crate) fn create_image(path: &str, size: u32) -> Result<()> {
+    let name = Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let staging_dir = format!("/tmp/fireup-disk-{}", name);
+    fs::create_dir_all(&staging_dir)
+        .with_context(|| format!("Failed to create directory: {}", staging_dir))?;
+
+    let result = create_ext4_filesystem(&staging_dir, path, size as usize);
+    let _ = crate::command::run_command("rm", &["-rf", &staging_dir], true);
+    result
+}
+
+pub fn remove_disk(disk: &str) -> Result<()> {
+    if !Path::new(disk).exists() {
+        return Ok(());
+    }
+    fs::remove_file(disk).with_context(|| format!("Failed to remove disk: {}", disk))?;
+    println!("[+] Persistent disk {} has been removed.", disk.cyan());
+    Ok(())
+}
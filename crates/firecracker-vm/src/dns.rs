@@ -0,0 +1,361 @@
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use firecracker_state::{entity::network::Network, repo};
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    command::run_command,
+    constants::{DEFAULT_NETWORK, DNS_TTL},
+    netd::bind_udp,
+};
+
+const DNS_PORT: u16 = 53;
+const HEADER_LEN: usize = 12;
+const FALLBACK_UPSTREAMS: [&str; 2] = ["1.1.1.1", "8.8.8.8"];
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+
+const RCODE_NOERROR: u8 = 0;
+const RCODE_FORMERR: u8 = 1;
+const RCODE_SERVFAIL: u8 = 2;
+const RCODE_NXDOMAIN: u8 = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub id: u16,
+    pub flags: u16,
+    pub name: String,
+    pub qtype: u16,
+    pub qclass: u16,
+    /// Raw question section, echoed back in the response.
+    pub question: Vec<u8>,
+}
+
+impl Query {
+    pub fn parse(buf: &[u8]) -> Result<Self> {
+        if buf.len() < HEADER_LEN {
+            return Err(anyhow!("DNS message too short ({} bytes)", buf.len()));
+        }
+        let qdcount = u16::from_be_bytes([buf[4], buf[5]]);
+        if qdcount != 1 {
+            return Err(anyhow!("Unsupported question count: {}", qdcount));
+        }
+
+        let mut labels = vec![];
+        let mut i = HEADER_LEN;
+        loop {
+            let len = *buf
+                .get(i)
+                .ok_or_else(|| anyhow!("Truncated DNS question"))? as usize;
+            i += 1;
+            if len == 0 {
+                break;
+            }
+            if len & 0xc0 != 0 {
+                return Err(anyhow!("Compressed names are not supported in questions"));
+            }
+            let label = buf
+                .get(i..i + len)
+                .ok_or_else(|| anyhow!("Truncated DNS question"))?;
+            labels.push(String::from_utf8_lossy(label).to_lowercase());
+            i += len;
+        }
+        let fixed = buf
+            .get(i..i + 4)
+            .ok_or_else(|| anyhow!("Truncated DNS question"))?;
+
+        Ok(Query {
+            id: u16::from_be_bytes([buf[0], buf[1]]),
+            flags: u16::from_be_bytes([buf[2], buf[3]]),
+            name: labels.join("."),
+            qtype: u16::from_be_bytes([fixed[0], fixed[1]]),
+            qclass: u16::from_be_bytes([fixed[2], fixed[3]]),
+            question: buf[HEADER_LEN..i + 4].to_vec(),
+        })
+    }
+
+    /// Name of the MicroVM for queries under `domain`.
+    pub fn vm_name(&self, domain: &str) -> Option<&str> {
+        self.name
+            .strip_suffix(domain)
+            .and_then(|name| name.strip_suffix('.'))
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Response with an A or AAAA record per address.
+    pub fn answer(&self, rcode: u8, addrs: &[IpAddr]) -> Vec<u8> {
+        // QR, AA and RA set, opcode and RD copied from the query
+        let flags = 0x8000 | (self.flags & 0x7900) | 0x0400 | 0x0080 | rcode as u16;
+
+        let mut buf = Vec::with_capacity(HEADER_LEN + self.question.len() + addrs.len() * 28);
+        buf.extend_from_slice(&self.id.to_be_bytes());
+        buf.extend_from_slice(&flags.to_be_bytes());
+        buf.extend_from_slice(&1u16.to_be_bytes());
+        buf.extend_from_slice(&(addrs.len() as u16).to_be_bytes());
+        buf.extend_from_slice(&[0, 0, 0, 0]);
+        buf.extend_from_slice(&self.question);
+        for addr in addrs {
+            // pointer to the name in the question section
+            buf.extend_from_slice(&[0xc0, HEADER_LEN as u8]);
+            let (rtype, rdata) = match addr {
+                IpAddr::V4(addr) => (TYPE_A, addr.octets().to_vec()),
+                IpAddr::V6(addr) => (TYPE_AAAA, addr.octets().to_vec()),
+            };
+            buf.extend_from_slice(&rtype.to_be_bytes());
+            buf.extend_from_slice(&CLASS_IN.to_be_bytes());
+            buf.extend_from_slice(&DNS_TTL.to_be_bytes());
+            buf.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            buf.extend_from_slice(&rdata);
+        }
+        buf
+    }
+}
+
+/// Binds the DNS server on the gateway address of `network`, requires root.
+/// The address does not need to exist yet, the bridge may be created afterwards.
+pub fn bind(network: &Network) -> Result<UdpSocket> {
+    let addr: Ipv4Addr = network
+        .gateway
+        .parse()
+        .with_context(|| format!("Invalid gateway address: {}", network.gateway))?;
+    bind_udp(SocketAddrV4::new(addr, DNS_PORT), None).with_context(|| {
+        format!(
+            "Failed to bind DNS server on {}:{}, is another DNS server running?",
+            addr, DNS_PORT
+        )
+    })
+}
+
+pub async fn serve(socket: UdpSocket, network: Network) -> Result<()> {
+    let socket = Arc::new(tokio::net::UdpSocket::from_std(socket)?);
+    let pool = firecracker_state::create_connection_pool().await?;
+    let upstreams = upstreams(&network);
+    let mut buf = [0u8; 1500];
+
+    println!(
+        "[+] DNS server listening on {}:{} for *.{}",
+        network.gateway, DNS_PORT, network.domain
+    );
+    loop {
+        let (len, peer) = socket.recv_from(&mut buf).await?;
+        let packet = buf[..len].to_vec();
+        let query = match Query::parse(&packet) {
+            Ok(query) => query,
+            Err(e) => {
+                eprintln!("[!] Ignoring invalid DNS query from {}: {}", peer, e);
+                if len >= 3 && packet[2] & 0x80 == 0 {
+                    let mut reply = packet[..2].to_vec();
+                    reply.extend_from_slice(&[0x80, RCODE_FORMERR, 0, 0, 0, 0, 0, 0, 0, 0]);
+                    socket.send_to(&reply, peer).await.ok();
+                }
+                continue;
+            }
+        };
+
+        if query.name == network.domain || query.name.ends_with(&format!(".{}", network.domain)) {
+            let reply = match resolve(&pool, &network, &query).await {
+                Ok(reply) => reply,
+                Err(e) => {
+                    eprintln!("[!] Failed to resolve {}: {}", query.name, e);
+                    query.answer(RCODE_SERVFAIL, &[])
+                }
+            };
+            if let Err(e) = socket.send_to(&reply, peer).await {
+                eprintln!("[!] Failed to send DNS reply to {}: {}", peer, e);
+            }
+            continue;
+        }
+
+        let socket = socket.clone();
+        let upstreams = upstreams.clone();
+        tokio::spawn(async move {
+            let reply = match forward(&packet, &upstreams).await {
+                Ok(reply) => reply,
+                Err(e) => {
+                    eprintln!("[!] Failed to forward DNS query for {}: {}", query.name, e);
+                    query.answer(RCODE_SERVFAIL, &[])
+                }
+            };
+            socket.send_to(&reply, peer).await.ok();
+        });
+    }
+}
+
+/// Answers `<name>.<domain>` from the VMs of `network` currently running, so
+/// stopped or removed VMs disappear from DNS as soon as the state database says so.
+/// A VM with an extra interface on `network` answers with its addresses there.
+async fn resolve(pool: &Pool<Sqlite>, network: &Network, query: &Query) -> Result<Vec<u8>> {
+    let vm_name = query.vm_name(&network.domain);
+    let vm = match vm_name {
+        Some(name) => repo::virtual_machine::find(pool, name)
+            .await?
+            .filter(|vm| vm.name == name)
+            .filter(|vm| vm.status == "RUNNING" || vm.status == "PAUSED"),
+        None => None,
+    };
+    let addrs = match &vm {
+        Some(vm) if vm.network.as_deref().unwrap_or(DEFAULT_NETWORK) == network.name => {
+            Some(vm.ip_addresses())
+        }
+        Some(vm) => repo::vm_interface::find_by_vm(pool, &vm.id)
+            .await?
+            .into_iter()
+            .find(|interface| interface.network == network.name)
+            .map(|interface| interface.ip_addresses()),
+        None => None,
+    };
+    let addrs = match addrs {
+        Some(addrs) if !addrs.is_empty() => addrs
+            .iter()
+            .map(|addr| addr.parse::<IpAddr>())
+            .collect::<Result<Vec<_>, _>>()?,
+        _ if vm_name.is_none() => return Ok(query.answer(RCODE_NOERROR, &[])),
+        _ => return Ok(query.answer(RCODE_NXDOMAIN, &[])),
+    };
+
+    let addrs = addrs
+        .into_iter()
+        .filter(|addr| match (query.qclass, query.qtype) {
+            (CLASS_IN, TYPE_A) => addr.is_ipv4(),
+            (CLASS_IN, TYPE_AAAA) => addr.is_ipv6(),
+            (CLASS_IN, TYPE_ANY) => true,
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+    Ok(query.answer(RCODE_NOERROR, &addrs))
+}
+
+async fn forward(packet: &[u8], upstreams: &[SocketAddr]) -> Result<Vec<u8>> {
+    let socket = tokio::net::UdpSocket::bind("0.0.0.0:0").await?;
+    let mut buf = [0u8; 4096];
+    for upstream in upstreams {
+        socket.send_to(packet, upstream).await?;
+        // late answers of the previous upstreams and spoofed ones are dropped
+        let reply = tokio::time::timeout(Duration::from_secs(3), async {
+            loop {
+                let (len, from) = socket.recv_from(&mut buf).await?;
+                if from == *upstream && len >= 2 && buf[..2] == packet[..2] {
+                    return Ok::<_, std::io::Error>(len);
+                }
+            }
+        })
+        .await;
+        if let Ok(Ok(len)) = reply {
+            return Ok(buf[..len].to_vec());
+        }
+    }
+    Err(anyhow!("No upstream DNS server answered"))
+}
+
+/// Upstream servers from the host `/etc/resolv.conf`, skipping ourselves.
+fn upstreams(network: &Network) -> Vec<SocketAddr> {
+    let mut upstreams = fs::read_to_string("/etc/resolv.conf")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|addr| addr.trim().parse::<std::net::IpAddr>().ok())
+        .filter(|addr| addr.to_string() != network.gateway)
+        .map(|addr| SocketAddr::new(addr, DNS_PORT))
+        .collect::<Vec<_>>();
+    if upstreams.is_empty() {
+        upstreams = FALLBACK_UPSTREAMS
+            .iter()
+            .map(|addr| SocketAddr::new(addr.parse().unwrap(), DNS_PORT))
+            .collect();
+    }
+    upstreams
+}
+
+/// Routes lookups of the host under the network domain to the embedded DNS
+/// server when systemd-resolved is available, so `fireup ssh <name>` resolves
+/// out of the box.
+pub fn configure_host_resolver(network: &Network) -> Result<()> {
+    if run_command("which", &["resolvectl"], false).is_err() {
+        return Ok(());
+    }
+    let domain = format!("~{}", network.domain);
+    run_command(
+        "resolvectl",
+        &["dns", &network.bridge, &network.gateway],
+        true,
+    )?;
+    run_command("resolvectl", &["domain", &network.bridge, &domain], true)?;
+    run_command(
+        "resolvectl",
+        &["default-route", &network.bridge, "false"],
+        true,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(name: &str, qtype: u16) -> Vec<u8> {
+        let mut buf = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        for label in name.split('.') {
+            buf.push(label.len() as u8);
+            buf.extend_from_slice(label.as_bytes());
+        }
+        buf.push(0);
+        buf.extend_from_slice(&qtype.to_be_bytes());
+        buf.extend_from_slice(&CLASS_IN.to_be_bytes());
+        buf
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = Query::parse(&packet("Web.Firecracker", TYPE_A)).unwrap();
+        assert_eq!(query.id, 0x1234);
+        assert_eq!(query.name, "web.firecracker");
+        assert_eq!(query.qtype, TYPE_A);
+        assert_eq!(query.vm_name("firecracker"), Some("web"));
+        assert_eq!(query.vm_name("team.firecracker"), None);
+
+        let query = Query::parse(&packet("example.com", TYPE_A)).unwrap();
+        assert_eq!(query.vm_name("firecracker"), None);
+    }
+
+    #[test]
+    fn test_answer() {
+        let packet = packet("web.firecracker", TYPE_A);
+        let query = Query::parse(&packet).unwrap();
+        let reply = query.answer(RCODE_NOERROR, &[Ipv4Addr::new(172, 16, 0, 2).into()]);
+
+        assert_eq!(&reply[..2], &[0x12, 0x34]);
+        // response, authoritative, recursion desired and available
+        assert_eq!(u16::from_be_bytes([reply[2], reply[3]]), 0x8580);
+        assert_eq!(u16::from_be_bytes([reply[6], reply[7]]), 1);
+        assert_eq!(&reply[HEADER_LEN..packet.len()], &packet[HEADER_LEN..]);
+        assert_eq!(&reply[reply.len() - 4..], &[172, 16, 0, 2]);
+    }
+
+    #[test]
+    fn test_answer_aaaa() {
+        let packet = packet("web.firecracker", TYPE_AAAA);
+        let query = Query::parse(&packet).unwrap();
+        let addr: std::net::Ipv6Addr = "fd12:3456:789a::1".parse().unwrap();
+        let reply = query.answer(RCODE_NOERROR, &[addr.into()]);
+
+        let record = &reply[packet.len()..];
+        assert_eq!(u16::from_be_bytes([record[2], record[3]]), TYPE_AAAA);
+        assert_eq!(u16::from_be_bytes([record[10], record[11]]), 16);
+        assert_eq!(&record[12..], &addr.octets());
+    }
+
+    #[test]
+    fn test_parse_rejects_truncated_question() {
+        let packet = packet("web.firecracker", TYPE_A);
+        assert!(Query::parse(&packet[..packet.len() - 3]).is_err());
+    }
+}
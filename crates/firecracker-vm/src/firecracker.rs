@@ -1,192 +1,265 @@
-use crate::types::VmOptions;
-use anyhow::Result;
-use serde_json::json;
-use std::thread::sleep;
+use crate::client::{
+    Action, ActionType, BootSource, Drive, FirecrackerClient, Logger, MachineConfig,
+    NetworkInterface, RateLimiter, Vsock,
+};
+use crate::{agent, command::run_command, jailer, limits, nic::Nic, types::VmOptions};
+use anyhow::{Context, Result};
+use firecracker_state::entity::volume::Volume;
 use std::time::Duration;
+use tokio::time::sleep;
 
-use crate::command::run_command;
-
-pub fn configure(
+pub async fn configure(
     logfile: &str,
     kernel: &str,
     rootfs: &str,
-    arch: &str,
+    disk: Option<&str>,
+    volumes: &[Volume],
+    nics: &[Nic],
     options: &VmOptions,
 ) -> Result<()> {
-    configure_logger(logfile, options)?;
-    setup_boot_source(kernel, arch, &options)?;
-    setup_rootfs(rootfs, options)?;
-    setup_network_interface(options)?;
-    setup_vcpu_and_memory(options.vcpu, options.memory, &options.api_socket)?;
+    let client = FirecrackerClient::new(&options.api_socket);
+    configure_logger(&client, logfile).await?;
+    setup_boot_source(&client, kernel, disk.is_some(), nics.len(), options).await?;
+    let rate_limiter = options.limits.as_ref().and_then(limits::drive_rate_limiter);
+    setup_rootfs(&client, rootfs, &rate_limiter).await?;
+    if let Some(disk) = disk {
+        setup_disk(&client, disk, &rate_limiter).await?;
+    }
+    for volume in volumes {
+        setup_volume(&client, volume, &rate_limiter).await?;
+    }
+    for nic in nics {
+        setup_network_interface(&client, nic, options).await?;
+    }
+    setup_vsock(&client, options).await?;
+    setup_vcpu_and_memory(&client, options.vcpu, options.memory).await?;
 
     // Wait before starting instance
-    sleep(Duration::from_millis(15));
+    sleep(Duration::from_millis(15)).await;
 
-    start_microvm(options)?;
+    start_microvm(&client).await?;
 
     // Wait for VM to boot
-    sleep(Duration::from_secs(2));
-    Ok(())
+    sleep(Duration::from_secs(2)).await;
+    agent::expose(&options.api_socket)
 }
 
-fn configure_logger(logfile: &str, options: &VmOptions) -> Result<()> {
+pub(crate) async fn configure_logger(client: &FirecrackerClient, logfile: &str) -> Result<()> {
     println!("[+] Configuring logger...");
-    let payload = json!({
-        "log_path": logfile,
-        "level": "Debug",
-        "show_level": true,
-        "show_log_origin": true
-    });
-    run_command(
-        "curl",
-        &[
-            "-s",
-            "-X",
-            "PUT",
-            "--unix-socket",
-            &options.api_socket,
-            "--data",
-            &payload.to_string(),
-            "http://localhost/logger",
-        ],
-        true,
-    )?;
-    Ok(())
+    client
+        .put_logger(&Logger {
+            log_path: logfile.into(),
+            level: "Debug".into(),
+            show_level: true,
+            show_log_origin: true,
+        })
+        .await
+        .with_context(|| "Failed to configure logger")
 }
 
-fn setup_boot_source(kernel: &str, arch: &str, options: &VmOptions) -> Result<String> {
+async fn setup_boot_source(
+    client: &FirecrackerClient,
+    kernel: &str,
+    persistent: bool,
+    interfaces: usize,
+    options: &VmOptions,
+) -> Result<String> {
     println!("[+] Setting boot source...");
     let mut boot_args =
         "console=ttyS0 reboot=k panic=1 pci=off ip=dhcp selinux=0 enforcing=0 init=/sbin/overlay-init overlay_root=ram"
             .to_string();
-    if arch == "aarch64" {
+    if persistent {
+        // the overlay disk is attached right after the rootfs
+        boot_args = boot_args.replace("overlay_root=ram", "overlay_root=vdb");
+    }
+    if interfaces > 1 {
+        // the kernel would otherwise ask for a lease on every interface
+        boot_args = boot_args.replace("ip=dhcp", "ip=:::::eth0:dhcp");
+    }
+    let arch = run_command("uname", &["-m"], false)?.stdout;
+    if String::from_utf8_lossy(&arch).trim() == "aarch64" {
         boot_args = format!("keep_bootcon {}", boot_args);
     }
 
     if let Some(args) = &options.bootargs {
-        boot_args = args.clone();
+        boot_args = match persistent {
+            // the overlay would otherwise stay in RAM, losing what is written
+            true => with_overlay_root(args, "vdb"),
+            false => args.clone(),
+        };
     }
 
-    let payload = json!({
-        "kernel_image_path": match &options.vmlinux {
+    let boot_source = BootSource {
+        kernel_image_path: match &options.vmlinux {
             Some(path) => path.clone(),
             None => kernel.into(),
         },
-        "boot_args": boot_args
-    });
-    println!("{}", payload.to_string());
-    run_command(
-        "curl",
-        &[
-            "-s",
-            "-X",
-            "PUT",
-            "--unix-socket",
-            &options.api_socket,
-            "--data",
-            &payload.to_string(),
-            "http://localhost/boot-source",
-        ],
-        true,
-    )?;
+        boot_args: boot_args.clone(),
+    };
+    println!("{}", serde_json::to_string(&boot_source)?);
+    client
+        .put_boot_source(&boot_source)
+        .await
+        .with_context(|| "Failed to set boot source")?;
     Ok(boot_args)
 }
 
-fn setup_rootfs(rootfs: &str, options: &VmOptions) -> Result<()> {
+/// `args` with `overlay_root=` set to `root`, replaced or appended.
+fn with_overlay_root(args: &str, root: &str) -> String {
+    let overlay_root = format!("overlay_root={}", root);
+    let mut replaced = false;
+    let mut args = args
+        .split_whitespace()
+        .map(|arg| match arg.starts_with("overlay_root=") {
+            true => {
+                replaced = true;
+                overlay_root.as_str()
+            }
+            false => arg,
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    if !replaced {
+        args = format!("{} {}", args, overlay_root).trim().to_string();
+    }
+    args
+}
+
+async fn setup_rootfs(
+    client: &FirecrackerClient,
+    rootfs: &str,
+    rate_limiter: &Option<RateLimiter>,
+) -> Result<()> {
     println!("[+] Setting rootfs...");
-    let payload = json!({
-        "drive_id": "rootfs",
-        "path_on_host": rootfs,
-        "is_root_device": true,
-        "is_read_only": true
-    });
-    run_command(
-        "curl",
-        &[
-            "-s",
-            "-X",
-            "PUT",
-            "--unix-socket",
-            &options.api_socket,
-            "--data",
-            &payload.to_string(),
-            "http://localhost/drives/rootfs",
-        ],
-        true,
-    )?;
-    Ok(())
+    client
+        .put_drive(&Drive {
+            drive_id: "rootfs".into(),
+            path_on_host: rootfs.into(),
+            is_root_device: true,
+            is_read_only: true,
+            rate_limiter: rate_limiter.clone(),
+        })
+        .await
+        .with_context(|| "Failed to set rootfs")
+}
+
+async fn setup_disk(
+    client: &FirecrackerClient,
+    disk: &str,
+    rate_limiter: &Option<RateLimiter>,
+) -> Result<()> {
+    println!("[+] Setting persistent disk...");
+    client
+        .put_drive(&Drive {
+            drive_id: "overlay".into(),
+            path_on_host: disk.into(),
+            is_root_device: false,
+            is_read_only: false,
+            rate_limiter: rate_limiter.clone(),
+        })
+        .await
+        .with_context(|| "Failed to set persistent disk")
+}
+
+async fn setup_volume(
+    client: &FirecrackerClient,
+    volume: &Volume,
+    rate_limiter: &Option<RateLimiter>,
+) -> Result<()> {
+    println!("[+] Attaching volume {}...", volume.name);
+    client
+        .put_drive(&Drive {
+            drive_id: format!("vol-{}", volume.name),
+            path_on_host: volume.path.clone(),
+            is_root_device: false,
+            is_read_only: false,
+            rate_limiter: rate_limiter.clone(),
+        })
+        .await
+        .with_context(|| format!("Failed to attach volume {}", volume.name))
+}
+
+async fn setup_network_interface(
+    client: &FirecrackerClient,
+    nic: &Nic,
+    options: &VmOptions,
+) -> Result<()> {
+    println!("[+] Setting network interface {}...", nic.iface_id);
+    let limits = options.limits.as_ref();
+    let iface = NetworkInterface {
+        iface_id: nic.iface_id.clone(),
+        guest_mac: nic.mac_address.clone(),
+        host_dev_name: nic.tap.clone(),
+        rx_rate_limiter: limits::net_rate_limiter(limits.and_then(|limits| limits.net_rx_mbps)),
+        tx_rate_limiter: limits::net_rate_limiter(limits.and_then(|limits| limits.net_tx_mbps)),
+    };
+
+    println!("{}", serde_json::to_string(&iface)?);
+    client
+        .put_network_interface(&iface)
+        .await
+        .with_context(|| format!("Failed to set network interface {}", nic.iface_id))
 }
 
-fn setup_network_interface(options: &VmOptions) -> Result<()> {
-    println!("[+] Setting network interface...");
-    let iface = "eth0";
-    let payload = json!({
-        "iface_id": iface,
-        "guest_mac": &options.mac_address,
-        "host_dev_name": &options.tap
-    });
-
-    println!("{}", payload.to_string());
-    run_command(
-        "curl",
-        &[
-            "-s",
-            "-X",
-            "PUT",
-            "--unix-socket",
-            &options.api_socket,
-            "--data",
-            &payload.to_string(),
-            &format!("http://localhost/network-interfaces/{}", iface),
-        ],
-        true,
-    )?;
-    Ok(())
+/// Vsock device the host talks to the guest agent through.
+async fn setup_vsock(client: &FirecrackerClient, options: &VmOptions) -> Result<()> {
+    println!("[+] Setting vsock device...");
+    let socket = agent::socket(&options.api_socket);
+    let uds_path = match options.jailer.unwrap_or(false) {
+        true => jailer::VSOCK_SOCKET.to_string(),
+        false => {
+            // Firecracker refuses to bind over a socket left behind
+            run_command("rm", &["-f", &socket], true)?;
+            socket
+        }
+    };
+    client
+        .put_vsock(&Vsock {
+            guest_cid: fireup_agent::GUEST_CID,
+            uds_path,
+        })
+        .await
+        .with_context(|| "Failed to set vsock device")
 }
 
-fn start_microvm(options: &VmOptions) -> Result<()> {
+async fn start_microvm(client: &FirecrackerClient) -> Result<()> {
     println!("[+] Starting microVM...");
-    let payload = json!({
-        "action_type": "InstanceStart"
-    });
-    run_command(
-        "curl",
-        &[
-            "-s",
-            "-X",
-            "PUT",
-            "--unix-socket",
-            &options.api_socket,
-            "--data",
-            &payload.to_string(),
-            "http://localhost/actions",
-        ],
-        true,
-    )?;
-    Ok(())
+    client
+        .put_action(&Action {
+            action_type: ActionType::InstanceStart,
+        })
+        .await
+        .with_context(|| "Failed to start microVM")
 }
 
-fn setup_vcpu_and_memory(n: u16, memory: u16, api_socket: &str) -> Result<()> {
+async fn setup_vcpu_and_memory(client: &FirecrackerClient, n: u16, memory: u16) -> Result<()> {
     println!("[+] Setting vCPU and memory...");
-    let payload = json!({
-        "vcpu_count": n,
-        "mem_size_mib": memory,
-        "smt": false,
-    });
-    println!("{}", payload.to_string());
-    run_command(
-        "curl",
-        &[
-            "-s",
-            "-X",
-            "PUT",
-            "--unix-socket",
-            api_socket,
-            "--data",
-            &payload.to_string(),
-            "http://localhost/machine-config",
-        ],
-        true,
-    )?;
-    Ok(())
+    let config = MachineConfig {
+        vcpu_count: n,
+        mem_size_mib: memory,
+        smt: false,
+    };
+    println!("{}", serde_json::to_string(&config)?);
+    client
+        .put_machine_config(&config)
+        .await
+        .with_context(|| "Failed to set vCPU and memory")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_overlay_root() {
+        assert_eq!(
+            with_overlay_root("console=ttyS0 overlay_root=ram quiet", "vdb"),
+            "console=ttyS0 overlay_root=vdb quiet"
+        );
+        assert_eq!(
+            with_overlay_root("console=ttyS0 init=/sbin/overlay-init", "vdb"),
+            "console=ttyS0 init=/sbin/overlay-init overlay_root=vdb"
+        );
+        assert_eq!(with_overlay_root("", "vdb"), "overlay_root=vdb");
+    }
 }
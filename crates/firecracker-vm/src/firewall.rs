@@ -0,0 +1,1003 @@
+use std::{
+    fs,
+    net::{IpAddr, ToSocketAddrs},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context, Result};
+use fire_config::FirewallOptions;
+use firecracker_state::{
+    entity::{network::Network, virtual_machine::VirtualMachine, vm_interface::VmInterface},
+    repo,
+};
+
+use crate::{
+    command::run_command,
+    network::{self, Cidr},
+    nic::Nic,
+    ports::{self, PortMapping},
+    types::VmOptions,
+};
+
+/// Name of the nftables table, and prefix of the iptables chains, holding
+/// every rule fireup installs.
+const NAME: &str = "fireup";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rule {
+    /// Keeps two networks from reaching each other through the host.
+    Isolate { from: String, to: String },
+    /// Forwards the traffic of a bridge, and the replies to it.
+    Forward { bridge: String },
+    /// Masquerades `subnet` behind the host interface `iface`.
+    Masquerade { subnet: String, iface: String },
+    /// Forwards a host port to a guest.
+    PortForward {
+        guest_ip: String,
+        mapping: PortMapping,
+    },
+    /// Drops the traffic of the MicroVM with `mac` on `bridge`, to the host
+    /// and to the outside, except DHCP, DNS and the `allow` destinations.
+    Egress {
+        bridge: String,
+        mac: String,
+        allow: Vec<(String, Option<u16>)>,
+    },
+    /// Drops the traffic bridged between `port` and the other bridge ports.
+    IsolatePort { port: String },
+}
+
+/// Destination of an egress `allow` entry: an address, a subnet or a domain
+/// name, with an optional port, e.g. `10.0.0.0/8:5432` or `github.com:443`.
+/// IPv6 addresses take a port when written in brackets, `[fd00::/8]:443`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Destination {
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl FromStr for Destination {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (host, port) = match s.strip_prefix('[') {
+            Some(rest) => match rest.split_once(']') {
+                Some((host, "")) => (host, None),
+                Some((host, port)) => (host, port.strip_prefix(':').or(Some(""))),
+                None => return Err(anyhow!("Invalid destination '{}'", s)),
+            },
+            // more than one colon is an IPv6 address without a port
+            None => match s.split_once(':') {
+                Some((host, port)) if !port.contains(':') => (host, Some(port)),
+                _ => (s, None),
+            },
+        };
+        let port = port
+            .map(|port| {
+                port.parse::<u16>()
+                    .ok()
+                    .filter(|port| *port > 0)
+                    .ok_or_else(|| anyhow!("Invalid port '{}' in destination '{}'", port, s))
+            })
+            .transpose()?;
+        if host.is_empty() {
+            return Err(anyhow!("Invalid destination '{}'", s));
+        }
+        if let Some((addr, prefix)) = host.split_once('/') {
+            let valid = match addr.parse::<IpAddr>() {
+                Ok(IpAddr::V4(_)) => host.parse::<Cidr>().is_ok(),
+                Ok(IpAddr::V6(_)) => prefix.parse::<u8>().is_ok_and(|prefix| prefix <= 128),
+                Err(_) => false,
+            };
+            if !valid {
+                return Err(anyhow!("Invalid subnet '{}' in destination '{}'", host, s));
+            }
+        }
+        Ok(Destination {
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+impl Destination {
+    /// Addresses or subnets the destination stands for, domain names are
+    /// resolved when the rules are generated.
+    pub fn resolve(&self) -> Result<Vec<String>> {
+        if self.host.contains('/') || self.host.parse::<IpAddr>().is_ok() {
+            return Ok(vec![self.host.clone()]);
+        }
+        let mut addrs = (self.host.as_str(), 0)
+            .to_socket_addrs()
+            .with_context(|| format!("Failed to resolve {}", self.host))?
+            .map(|addr| addr.ip().to_string())
+            .collect::<Vec<_>>();
+        addrs.sort();
+        addrs.dedup();
+        Ok(addrs)
+    }
+}
+
+/// Firewall policy of a MicroVM, from the `[vm.firewall]` section.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Policy {
+    pub deny_egress: bool,
+    pub allow: Vec<Destination>,
+    pub isolate: bool,
+}
+
+impl TryFrom<&FirewallOptions> for Policy {
+    type Error = anyhow::Error;
+
+    fn try_from(options: &FirewallOptions) -> Result<Self> {
+        let deny_egress = match options.egress.as_deref() {
+            None | Some("allow") => false,
+            Some("deny") => true,
+            Some(egress) => {
+                return Err(anyhow!(
+                    "Invalid egress policy '{}', expected allow or deny",
+                    egress
+                ))
+            }
+        };
+        let allow = options
+            .allow
+            .iter()
+            .flatten()
+            .map(|destination| destination.parse::<Destination>())
+            .collect::<Result<Vec<_>>>()?;
+        if !deny_egress && !allow.is_empty() {
+            return Err(anyhow!(
+                "The firewall allow list only applies with egress = \"deny\""
+            ));
+        }
+        Ok(Policy {
+            deny_egress,
+            allow,
+            isolate: options.isolate.unwrap_or(false),
+        })
+    }
+}
+
+impl Policy {
+    /// Rules enforcing the policy on the bridge port `port` of the MicroVM
+    /// with `mac` on `bridge`. Destinations that fail to resolve are left out,
+    /// the MicroVM cannot reach them.
+    pub fn rules(&self, bridge: &str, port: &str, mac: &str) -> Vec<Rule> {
+        let mut rules = vec![];
+        if self.isolate {
+            rules.push(Rule::IsolatePort { port: port.into() });
+        }
+        if self.deny_egress {
+            let mut allow = vec![];
+            for destination in &self.allow {
+                match destination.resolve() {
+                    Ok(addrs) => {
+                        allow.extend(addrs.into_iter().map(|addr| (addr, destination.port)))
+                    }
+                    Err(e) => println!("[!] {:#}, not allowing it.", e),
+                }
+            }
+            rules.push(Rule::Egress {
+                bridge: bridge.into(),
+                mac: mac.to_lowercase(),
+                allow,
+            });
+        }
+        rules
+    }
+}
+
+/// Serializes the firewall options of a VM as JSON.
+pub fn to_column(options: &FirewallOptions) -> String {
+    serde_json::to_string(options).unwrap_or_default()
+}
+
+pub fn from_column(column: &str) -> Option<FirewallOptions> {
+    serde_json::from_str(column).ok()
+}
+
+pub trait Firewall {
+    /// Replaces the fireup rules with `rules`.
+    fn apply(&self, rules: &[Rule]) -> Result<()>;
+    /// Removes every fireup rule.
+    fn prune(&self) -> Result<()>;
+}
+
+/// Dedicated `inet fireup` table, replaced atomically on every change.
+pub struct Nftables;
+
+/// Legacy `filter` chains [`Nftables`] may jump to `FIREUP-FORWARD` from.
+const LEGACY_FORWARD_PARENTS: [&str; 2] = ["DOCKER-USER", "FORWARD"];
+
+impl Nftables {
+    /// Legacy `filter` chain dropping forwarded traffic, if any: `DOCKER-USER`
+    /// on Docker hosts, `FORWARD` when its policy is DROP. A packet dropped by
+    /// any table hooked on forward is dropped, whatever the `fireup` table
+    /// accepted.
+    fn legacy_forward_parent(binary: &str) -> Option<&'static str> {
+        if run_command(binary, &["-t", "filter", "-L", "DOCKER-USER", "-n"], true).is_ok() {
+            return Some("DOCKER-USER");
+        }
+        let output = run_command(binary, &["-t", "filter", "-S", "FORWARD"], true).ok()?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .any(|line| line.trim() == "-P FORWARD DROP")
+            .then_some("FORWARD")
+    }
+
+    fn prune_legacy_forward(binary: &str) -> Result<()> {
+        let chain = Iptables::chain("FORWARD");
+        for parent in LEGACY_FORWARD_PARENTS {
+            while run_command(binary, &["-t", "filter", "-D", parent, "-j", &chain], true).is_ok() {
+            }
+        }
+        if run_command(binary, &["-t", "filter", "-L", &chain, "-n"], true).is_ok() {
+            run_command(binary, &["-t", "filter", "-F", &chain], true)?;
+            run_command(binary, &["-t", "filter", "-X", &chain], true)?;
+        }
+        Ok(())
+    }
+
+    /// Accepts the traffic of the bridges and of the forwarded ports in the
+    /// legacy `filter` table of hosts dropping forwarded packets there, the
+    /// drops of the `fireup` table still apply.
+    fn apply_legacy_forward(rules: &[Rule]) -> Result<()> {
+        let forward = rules
+            .iter()
+            .filter(|rule| matches!(rule, Rule::Forward { .. } | Rule::PortForward { .. }))
+            .cloned()
+            .collect::<Vec<_>>();
+        for (binary, ipv6) in Iptables::binaries() {
+            Self::prune_legacy_forward(binary)?;
+            let Some(parent) = Self::legacy_forward_parent(binary) else {
+                continue;
+            };
+            if forward.is_empty() {
+                continue;
+            }
+            let chain = Iptables::chain("FORWARD");
+            run_command(binary, &["-t", "filter", "-N", &chain], true)?;
+            run_command(binary, &["-t", "filter", "-I", parent, "-j", &chain], true)?;
+            for (table, _, args) in Iptables::render(&forward, ipv6)
+                .into_iter()
+                .filter(|(_, builtin, _)| *builtin == "FORWARD")
+            {
+                let args = [
+                    &["-t".to_string(), table.into(), "-A".into(), chain.clone()],
+                    args.as_slice(),
+                ]
+                .concat();
+                let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+                run_command(binary, &args, true)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn render(rules: &[Rule]) -> String {
+        let mut input = vec![];
+        let mut forward = vec![];
+        let mut prerouting = vec![];
+        let mut postrouting = vec![];
+        let mut bridged = vec![];
+        for rule in rules {
+            match rule {
+                Rule::Isolate { from, to } => {
+                    forward.push(format!("iifname \"{}\" oifname \"{}\" drop", from, to))
+                }
+                Rule::Forward { bridge } => {
+                    forward.push(format!("iifname \"{}\" accept", bridge));
+                    forward.push(format!(
+                        "oifname \"{}\" ct state established,related accept",
+                        bridge
+                    ));
+                }
+                Rule::Masquerade { subnet, iface } => postrouting.push(format!(
+                    "{} saddr {} oifname \"{}\" masquerade",
+                    family(subnet),
+                    subnet,
+                    iface
+                )),
+                Rule::PortForward { guest_ip, mapping } => {
+                    prerouting.push(format!(
+                        "meta nfproto ipv4 fib daddr type local {} dport {} dnat ip to {}:{}",
+                        mapping.protocol, mapping.host_port, guest_ip, mapping.guest_port
+                    ));
+                    postrouting.push(format!(
+                        "ip saddr 127.0.0.0/8 ip daddr {} {} dport {} masquerade",
+                        guest_ip, mapping.protocol, mapping.guest_port
+                    ));
+                    forward.push(format!(
+                        "ip daddr {} {} dport {} ct status dnat accept",
+                        guest_ip, mapping.protocol, mapping.guest_port
+                    ));
+                }
+                Rule::Egress { bridge, mac, allow } => {
+                    let vm = format!("iifname \"{}\" ether saddr {}", bridge, mac);
+                    let established = format!("{} ct state established,related accept", vm);
+                    let allowed = allow
+                        .iter()
+                        .map(|(addr, port)| match port {
+                            Some(port) => format!(
+                                "{} {} daddr {} meta l4proto {{ tcp, udp }} th dport {} accept",
+                                vm,
+                                family(addr),
+                                addr,
+                                port
+                            ),
+                            None => format!("{} {} daddr {} accept", vm, family(addr), addr),
+                        })
+                        .collect::<Vec<_>>();
+
+                    input.push(established.clone());
+                    input.push(format!("{} udp dport {{ 53, 67 }} accept", vm));
+                    input.push(format!("{} tcp dport 53 accept", vm));
+                    // router solicitations and neighbor discovery
+                    input.push(format!("{} meta l4proto ipv6-icmp accept", vm));
+                    input.extend(allowed.clone());
+                    input.push(format!("{} drop", vm));
+
+                    forward.push(established);
+                    forward.extend(allowed);
+                    forward.push(format!("{} drop", vm));
+                }
+                Rule::IsolatePort { port } => {
+                    bridged.push(format!("iifname \"{}\" drop", port));
+                    bridged.push(format!("oifname \"{}\" drop", port));
+                }
+            }
+        }
+
+        let chain = |name: &str, hook: &str, rules: &[String]| {
+            let mut chain = format!("\tchain {} {{\n\t\t{}; policy accept;\n", name, hook);
+            for rule in rules {
+                chain.push_str(&format!("\t\t{}\n", rule));
+            }
+            chain.push_str("\t}\n");
+            chain
+        };
+        // declaring the tables before deleting them makes the deletes succeed
+        // on the first run, the whole file is applied as one transaction
+        format!(
+            "table inet {0}\ndelete table inet {0}\ntable bridge {0}\ndelete table bridge {0}\n\
+             table inet {0} {{\n{1}{2}{3}{4}{5}}}\ntable bridge {0} {{\n{6}}}\n",
+            NAME,
+            chain("input", "type filter hook input priority filter", &input),
+            chain(
+                "forward",
+                "type filter hook forward priority filter",
+                &forward
+            ),
+            chain(
+                "prerouting",
+                "type nat hook prerouting priority dstnat",
+                &prerouting
+            ),
+            chain("output", "type nat hook output priority -100", &prerouting),
+            chain(
+                "postrouting",
+                "type nat hook postrouting priority srcnat",
+                &postrouting
+            ),
+            chain(
+                "forward",
+                "type filter hook forward priority filter",
+                &bridged
+            ),
+        )
+    }
+}
+
+/// nftables address family keyword of an address or subnet.
+fn family(addr: &str) -> &'static str {
+    match addr.contains(':') {
+        true => "ip6",
+        false => "ip",
+    }
+}
+
+impl Firewall for Nftables {
+    fn apply(&self, rules: &[Rule]) -> Result<()> {
+        let path = std::env::temp_dir().join(format!("{}-{}.nft", NAME, std::process::id()));
+        let path = path.display().to_string();
+        fs::write(&path, Self::render(rules))
+            .with_context(|| format!("Failed to write nftables ruleset: {}", path))?;
+        let result = run_command("nft", &["-f", &path], true);
+        fs::remove_file(&path).ok();
+        result.with_context(|| "Failed to apply nftables ruleset")?;
+        Self::apply_legacy_forward(rules)
+            .with_context(|| "Failed to accept the bridges in the iptables FORWARD chain")
+    }
+
+    fn prune(&self) -> Result<()> {
+        for family in ["inet", "bridge"] {
+            if run_command("nft", &["list", "table", family, NAME], true).is_ok() {
+                run_command("nft", &["delete", "table", family, NAME], true)?;
+            }
+        }
+        for (binary, _) in Iptables::binaries() {
+            Self::prune_legacy_forward(binary)?;
+        }
+        Ok(())
+    }
+}
+
+/// Fallback for hosts without nft: `FIREUP-*` chains jumped to from the
+/// built-in ones, flushed and refilled on every change.
+pub struct Iptables;
+
+const IPTABLES_CHAINS: [(&str, &str); 5] = [
+    ("filter", "INPUT"),
+    ("filter", "FORWARD"),
+    ("nat", "PREROUTING"),
+    ("nat", "OUTPUT"),
+    ("nat", "POSTROUTING"),
+];
+
+impl Iptables {
+    fn chain(builtin: &str) -> String {
+        format!("{}-{}", NAME.to_uppercase(), builtin)
+    }
+
+    /// `(table, builtin chain, arguments)` of each rule for `iptables` or
+    /// `ip6tables`.
+    pub fn render(rules: &[Rule], ipv6: bool) -> Vec<(&'static str, &'static str, Vec<String>)> {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let mut rendered = vec![];
+        for rule in rules {
+            match rule {
+                Rule::Isolate { from, to } => rendered.push((
+                    "filter",
+                    "FORWARD",
+                    args(&["-i", from, "-o", to, "-j", "DROP"]),
+                )),
+                Rule::Forward { bridge } => {
+                    rendered.push(("filter", "FORWARD", args(&["-i", bridge, "-j", "ACCEPT"])));
+                    rendered.push((
+                        "filter",
+                        "FORWARD",
+                        args(&[
+                            "-o",
+                            bridge,
+                            "-m",
+                            "conntrack",
+                            "--ctstate",
+                            "RELATED,ESTABLISHED",
+                            "-j",
+                            "ACCEPT",
+                        ]),
+                    ));
+                }
+                Rule::Masquerade { subnet, iface } if subnet.contains(':') == ipv6 => rendered
+                    .push((
+                        "nat",
+                        "POSTROUTING",
+                        args(&["-s", subnet, "-o", iface, "-j", "MASQUERADE"]),
+                    )),
+                Rule::PortForward { guest_ip, mapping } if !ipv6 => {
+                    let protocol = mapping.protocol.to_string();
+                    let host_port = mapping.host_port.to_string();
+                    let guest_port = mapping.guest_port.to_string();
+                    let destination = format!("{}:{}", guest_ip, guest_port);
+                    let dnat = args(&[
+                        "-p",
+                        &protocol,
+                        "-m",
+                        "addrtype",
+                        "--dst-type",
+                        "LOCAL",
+                        "--dport",
+                        &host_port,
+                        "-j",
+                        "DNAT",
+                        "--to-destination",
+                        &destination,
+                    ]);
+                    rendered.push(("nat", "PREROUTING", dnat.clone()));
+                    rendered.push(("nat", "OUTPUT", dnat));
+                    rendered.push((
+                        "nat",
+                        "POSTROUTING",
+                        args(&[
+                            "-s",
+                            "127.0.0.0/8",
+                            "-d",
+                            guest_ip,
+                            "-p",
+                            &protocol,
+                            "--dport",
+                            &guest_port,
+                            "-j",
+                            "MASQUERADE",
+                        ]),
+                    ));
+                    rendered.push((
+                        "filter",
+                        "FORWARD",
+                        args(&[
+                            "-d",
+                            guest_ip,
+                            "-p",
+                            &protocol,
+                            "--dport",
+                            &guest_port,
+                            "-m",
+                            "conntrack",
+                            "--ctstate",
+                            "DNAT",
+                            "-j",
+                            "ACCEPT",
+                        ]),
+                    ));
+                }
+                Rule::Egress { bridge, mac, allow } => {
+                    let vm = |rule: &[&str]| {
+                        let source = ["-i", bridge.as_str(), "-m", "mac", "--mac-source", mac];
+                        args(&[source.as_slice(), rule].concat())
+                    };
+                    let established = vm(&[
+                        "-m",
+                        "conntrack",
+                        "--ctstate",
+                        "RELATED,ESTABLISHED",
+                        "-j",
+                        "ACCEPT",
+                    ]);
+                    let mut allowed = vec![];
+                    for (addr, port) in allow.iter().filter(|(addr, _)| addr.contains(':') == ipv6)
+                    {
+                        match port {
+                            Some(port) => {
+                                let port = port.to_string();
+                                for protocol in ["tcp", "udp"] {
+                                    allowed.push(vm(&[
+                                        "-d", addr, "-p", protocol, "--dport", &port, "-j",
+                                        "ACCEPT",
+                                    ]));
+                                }
+                            }
+                            None => allowed.push(vm(&["-d", addr, "-j", "ACCEPT"])),
+                        }
+                    }
+                    let drop = vm(&["-j", "DROP"]);
+
+                    rendered.push(("filter", "INPUT", established.clone()));
+                    rendered.push((
+                        "filter",
+                        "INPUT",
+                        vm(&[
+                            "-p",
+                            "udp",
+                            "-m",
+                            "multiport",
+                            "--dports",
+                            "53,67",
+                            "-j",
+                            "ACCEPT",
+                        ]),
+                    ));
+                    rendered.push((
+                        "filter",
+                        "INPUT",
+                        vm(&["-p", "tcp", "--dport", "53", "-j", "ACCEPT"]),
+                    ));
+                    if ipv6 {
+                        rendered.push((
+                            "filter",
+                            "INPUT",
+                            vm(&["-p", "ipv6-icmp", "-j", "ACCEPT"]),
+                        ));
+                    }
+                    for rule in &allowed {
+                        rendered.push(("filter", "INPUT", rule.clone()));
+                    }
+                    rendered.push(("filter", "INPUT", drop.clone()));
+
+                    rendered.push(("filter", "FORWARD", established));
+                    for rule in allowed {
+                        rendered.push(("filter", "FORWARD", rule));
+                    }
+                    rendered.push(("filter", "FORWARD", drop));
+                }
+                Rule::IsolatePort { port } => {
+                    for direction in ["--physdev-in", "--physdev-out"] {
+                        rendered.push((
+                            "filter",
+                            "FORWARD",
+                            args(&[
+                                "-m",
+                                "physdev",
+                                "--physdev-is-bridged",
+                                direction,
+                                port,
+                                "-j",
+                                "DROP",
+                            ]),
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+        rendered
+    }
+
+    fn binaries() -> Vec<(&'static str, bool)> {
+        [("iptables", false), ("ip6tables", true)]
+            .into_iter()
+            .filter(|(binary, _)| run_command("which", &[binary], false).is_ok())
+            .collect()
+    }
+}
+
+impl Firewall for Iptables {
+    fn apply(&self, rules: &[Rule]) -> Result<()> {
+        // bridged traffic only goes through iptables with br_netfilter
+        if rules
+            .iter()
+            .any(|rule| matches!(rule, Rule::IsolatePort { .. }))
+            && run_command("modprobe", &["br_netfilter"], true).is_err()
+        {
+            println!("[!] Failed to load br_netfilter, MicroVMs will not be isolated.");
+        }
+        for (binary, ipv6) in Self::binaries() {
+            for (table, builtin) in IPTABLES_CHAINS {
+                let chain = Self::chain(builtin);
+                if run_command(binary, &["-t", table, "-L", &chain, "-n"], true).is_err() {
+                    run_command(binary, &["-t", table, "-N", &chain], true)?;
+                }
+                run_command(binary, &["-t", table, "-F", &chain], true)?;
+                if run_command(binary, &["-t", table, "-C", builtin, "-j", &chain], true).is_err() {
+                    run_command(binary, &["-t", table, "-I", builtin, "-j", &chain], true)?;
+                }
+            }
+            for (table, builtin, args) in Self::render(rules, ipv6) {
+                let chain = Self::chain(builtin);
+                let args = [
+                    &["-t".to_string(), table.into(), "-A".into(), chain],
+                    args.as_slice(),
+                ]
+                .concat();
+                let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+                run_command(binary, &args, true)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn prune(&self) -> Result<()> {
+        for (binary, _) in Self::binaries() {
+            for (table, builtin) in IPTABLES_CHAINS {
+                let chain = Self::chain(builtin);
+                while run_command(binary, &["-t", table, "-D", builtin, "-j", &chain], true).is_ok()
+                {
+                }
+                if run_command(binary, &["-t", table, "-L", &chain, "-n"], true).is_ok() {
+                    run_command(binary, &["-t", table, "-F", &chain], true)?;
+                    run_command(binary, &["-t", table, "-X", &chain], true)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// nftables when the host has `nft`, iptables otherwise.
+pub fn backend() -> Box<dyn Firewall> {
+    match run_command("which", &["nft"], false).is_ok() {
+        true => Box::new(Nftables),
+        false => Box::new(Iptables),
+    }
+}
+
+/// Rules enforcing the firewall policy of a MicroVM recorded in the state
+/// database, on each of its network `interfaces`.
+fn vm_rules(vm: &VirtualMachine, interfaces: &[VmInterface], networks: &[Network]) -> Vec<Rule> {
+    let Some(options) = vm.firewall.as_deref().and_then(from_column) else {
+        return vec![];
+    };
+    match Policy::try_from(&options) {
+        Ok(policy) if interfaces.is_empty() => policy.rules(
+            &vm.bridge,
+            &network::host_port(&vm.tap, vm.jailer_uid.is_some()),
+            &vm.mac_address,
+        ),
+        Ok(policy) => interfaces
+            .iter()
+            .filter_map(|interface| {
+                let network = networks
+                    .iter()
+                    .find(|network| network.name == interface.network)?;
+                Some(policy.rules(
+                    &network.bridge,
+                    &network::host_port(&interface.tap, vm.jailer_uid.is_some()),
+                    &interface.mac_address,
+                ))
+            })
+            .flatten()
+            .collect(),
+        Err(e) => {
+            println!("[!] Ignoring the firewall policy of {}: {}", vm.name, e);
+            vec![]
+        }
+    }
+}
+
+/// Rules for every network whose bridge is up and every running MicroVM, as
+/// recorded in the state database, plus the `pending` rules of the MicroVM
+/// with the given MAC address being started.
+async fn rules(pending: Option<(&str, Vec<Rule>)>) -> Result<Vec<Rule>> {
+    let networks = network::all()
+        .await?
+        .into_iter()
+        .filter(|network| network::bridge_exists(&network.bridge))
+        .collect::<Vec<_>>();
+    let host_iface = network::host_interface().ok();
+    let host_iface6 = network::host_interface6().ok();
+
+    let mut rules = vec![];
+    for network in &networks {
+        for other in networks
+            .iter()
+            .filter(|other| other.bridge != network.bridge)
+        {
+            rules.push(Rule::Isolate {
+                from: network.bridge.clone(),
+                to: other.bridge.clone(),
+            });
+        }
+    }
+
+    let pool = firecracker_state::create_connection_pool().await?;
+    let vms = repo::virtual_machine::all(&pool)
+        .await?
+        .into_iter()
+        .filter(|vm| vm.status == "RUNNING" || vm.status == "PAUSED")
+        .collect::<Vec<_>>();
+    let interfaces = repo::vm_interface::all(&pool).await?;
+    // the policies of the MicroVMs have to come before the bridges are accepted
+    let pending_mac = pending.as_ref().map(|(mac, _)| mac.to_lowercase());
+    for vm in &vms {
+        if Some(vm.mac_address.to_lowercase()) != pending_mac {
+            let interfaces = interfaces
+                .iter()
+                .filter(|interface| interface.vm_id == vm.id)
+                .cloned()
+                .collect::<Vec<_>>();
+            rules.extend(vm_rules(vm, &interfaces, &networks));
+        }
+    }
+    if let Some((_, pending)) = pending {
+        rules.extend(pending);
+    }
+
+    for network in &networks {
+        rules.push(Rule::Forward {
+            bridge: network.bridge.clone(),
+        });
+        if !network.nat {
+            continue;
+        }
+        if let Some(iface) = &host_iface {
+            rules.push(Rule::Masquerade {
+                subnet: network.cidr.clone(),
+                iface: iface.clone(),
+            });
+        }
+        if let (Some(prefix), Some(iface)) = (&network.ipv6_prefix, &host_iface6) {
+            rules.push(Rule::Masquerade {
+                subnet: prefix.clone(),
+                iface: iface.clone(),
+            });
+        }
+    }
+
+    for vm in &vms {
+        let (Some(column), Some(guest_ip)) = (&vm.ports, vm.ipv4_address()) else {
+            continue;
+        };
+        for mapping in ports::from_column(column) {
+            rules.push(Rule::PortForward {
+                guest_ip: guest_ip.clone(),
+                mapping,
+            });
+        }
+    }
+    Ok(rules)
+}
+
+/// Brings the host firewall in line with the state database.
+pub async fn sync() -> Result<()> {
+    let rules = rules(None).await?;
+    backend().apply(&rules)
+}
+
+/// Same as [`sync`], also enforcing the policy of the MicroVM being started
+/// with `config` on each of its `nics`, before it boots.
+pub async fn sync_vm(config: &VmOptions, nics: &[Nic]) -> Result<()> {
+    let policy = config
+        .firewall
+        .as_ref()
+        .map(Policy::try_from)
+        .transpose()?
+        .unwrap_or_default();
+    let jailed = config.jailer.unwrap_or(false);
+    let port = network::host_port(&config.tap, jailed);
+    if policy.deny_egress {
+        println!(
+            "[+] Denying egress traffic of {} except {} destination(s)...",
+            port,
+            policy.allow.len()
+        );
+    }
+    if policy.isolate {
+        println!("[+] Isolating {} from the other MicroVMs...", port);
+    }
+    let pending = nics
+        .iter()
+        .flat_map(|nic| {
+            policy.rules(
+                &nic.network.bridge,
+                &network::host_port(&nic.tap, jailed),
+                &nic.mac_address,
+            )
+        })
+        .collect();
+    let rules = rules(Some((&config.mac_address, pending))).await?;
+    backend().apply(&rules)
+}
+
+pub fn prune() -> Result<()> {
+    backend().prune()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> Vec<Rule> {
+        vec![
+            Rule::Isolate {
+                from: "br0".into(),
+                to: "fc-dev".into(),
+            },
+            Rule::Forward {
+                bridge: "br0".into(),
+            },
+            Rule::Masquerade {
+                subnet: "172.16.0.0/16".into(),
+                iface: "eth0".into(),
+            },
+            Rule::Masquerade {
+                subnet: "fd12:3456:789a::/64".into(),
+                iface: "eth0".into(),
+            },
+            Rule::PortForward {
+                guest_ip: "172.16.0.2".into(),
+                mapping: "8080:80".parse().unwrap(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_render_nftables() {
+        let ruleset = Nftables::render(&rules());
+        assert!(ruleset.starts_with("table inet fireup\ndelete table inet fireup\n"));
+        assert!(ruleset.contains("\t\tiifname \"br0\" oifname \"fc-dev\" drop\n"));
+        assert!(ruleset.contains("\t\tip saddr 172.16.0.0/16 oifname \"eth0\" masquerade\n"));
+        assert!(ruleset.contains("\t\tip6 saddr fd12:3456:789a::/64 oifname \"eth0\" masquerade\n"));
+        assert!(ruleset.contains("fib daddr type local tcp dport 8080 dnat ip to 172.16.0.2:80\n"));
+        assert!(ruleset.contains("\t\tip daddr 172.16.0.2 tcp dport 80 ct status dnat accept\n"));
+        // the drop has to come before the bridge is accepted
+        assert!(ruleset.find("drop").unwrap() < ruleset.find("accept\n").unwrap());
+    }
+
+    #[test]
+    fn test_render_iptables() {
+        let rules = rules();
+        let ipv4 = Iptables::render(&rules, false);
+        let ipv6 = Iptables::render(&rules, true);
+        assert_eq!(ipv4.len(), 8);
+        assert_eq!(ipv6.len(), 4);
+        assert!(ipv6
+            .iter()
+            .all(|(_, _, args)| !args.contains(&"172.16.0.0/16".to_string())));
+        assert_eq!(ipv4[0].2, ["-i", "br0", "-o", "fc-dev", "-j", "DROP"]);
+        assert_eq!(
+            ipv4[7],
+            (
+                "filter",
+                "FORWARD",
+                [
+                    "-d",
+                    "172.16.0.2",
+                    "-p",
+                    "tcp",
+                    "--dport",
+                    "80",
+                    "-m",
+                    "conntrack",
+                    "--ctstate",
+                    "DNAT",
+                    "-j",
+                    "ACCEPT"
+                ]
+                .map(String::from)
+                .to_vec()
+            )
+        );
+    }
+
+    #[test]
+    fn test_destination() {
+        let destination = "10.0.0.0/8:5432".parse::<Destination>().unwrap();
+        assert_eq!(destination.host, "10.0.0.0/8");
+        assert_eq!(destination.port, Some(5432));
+
+        let destination = "github.com:443".parse::<Destination>().unwrap();
+        assert_eq!(destination.host, "github.com");
+        assert_eq!(destination.port, Some(443));
+
+        let destination = "fd00::/8".parse::<Destination>().unwrap();
+        assert_eq!(
+            (destination.host.as_str(), destination.port),
+            ("fd00::/8", None)
+        );
+
+        let destination = "[fd00::/8]:443".parse::<Destination>().unwrap();
+        assert_eq!(
+            (destination.host.as_str(), destination.port),
+            ("fd00::/8", Some(443))
+        );
+
+        assert!("10.0.0.0/33".parse::<Destination>().is_err());
+        assert!("github.com:https".parse::<Destination>().is_err());
+        assert!(":443".parse::<Destination>().is_err());
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = Policy::try_from(&FirewallOptions {
+            egress: Some("deny".into()),
+            allow: Some(vec!["10.0.0.0/8:5432".into()]),
+            isolate: Some(true),
+        })
+        .unwrap();
+        let rules = policy.rules("br0", "tap0", "06:00:AC:10:00:02");
+        assert_eq!(
+            rules,
+            [
+                Rule::IsolatePort {
+                    port: "tap0".into()
+                },
+                Rule::Egress {
+                    bridge: "br0".into(),
+                    mac: "06:00:ac:10:00:02".into(),
+                    allow: vec![("10.0.0.0/8".into(), Some(5432))],
+                },
+            ]
+        );
+
+        let ruleset = Nftables::render(&rules);
+        assert!(ruleset.contains(
+            "iifname \"br0\" ether saddr 06:00:ac:10:00:02 ip daddr 10.0.0.0/8 \
+             meta l4proto { tcp, udp } th dport 5432 accept\n"
+        ));
+        assert!(ruleset.contains("\t\tiifname \"br0\" ether saddr 06:00:ac:10:00:02 drop\n"));
+        assert!(ruleset.contains("table bridge fireup {\n"));
+        assert!(ruleset.contains("\t\toifname \"tap0\" drop\n"));
+
+        assert!(Policy::try_from(&FirewallOptions {
+            egress: Some("block".into()),
+            ..Default::default()
+        })
+        .is_err());
+        assert!(Policy::try_from(&FirewallOptions {
+            allow: Some(vec!["github.com:443".into()]),
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
@@ -1,44 +1,190 @@
-use crate::{command::run_command, constants::BRIDGE_IP};
-use anyhow::Result;
+use std::{fs, path::Path, time::Duration};
 
-pub fn configure_guest_network(key_path: &str, guest_ip: &str, is_nixos: bool) -> Result<()> {
-    println!("[+] Configuring network in guest...");
-    const MAX_RETRIES: u32 = 500;
-    let mut retries = 0;
-    loop {
-        if run_command(
-            "ssh",
-            &[
-                "-i",
-                key_path,
-                "-o",
-                "StrictHostKeyChecking=no",
-                "-o",
-                "UserKnownHostsFile=/dev/null",
-                &format!("root@{}", guest_ip),
-                &match is_nixos {
-                    true => "uname -a".into(),
-                    false => format!("echo 'nameserver {}' > /etc/resolv.conf", BRIDGE_IP),
-                },
-            ],
-            false,
-        )
-        .is_ok()
-            || retries >= MAX_RETRIES
-        {
+use crate::{agent, command::run_command};
+use anyhow::{anyhow, Result};
+use firecracker_prepare::command::run_command_with_stdout_inherit;
+
+/// The agent starts before the init system of the guest, it answers long
+/// before the guest gets its DHCP lease.
+const AGENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How commands reach a booted guest: through its agent over vsock, or over
+/// SSH for rootfs images prepared without the agent.
+pub enum Guest {
+    Agent { api_socket: String },
+    Ssh { key_path: String, guest_ip: String },
+}
+
+impl Guest {
+    /// Waits for the guest to be ready to run commands, through its agent
+    /// when it has one.
+    pub fn connect(api_socket: &str, key_path: Option<&str>, guest_ip: &str) -> Result<Guest> {
+        if agent::wait_until_ready(api_socket, AGENT_TIMEOUT) {
+            println!("[+] Guest agent is ready.");
+            return Ok(Guest::Agent {
+                api_socket: api_socket.into(),
+            });
+        }
+
+        let key_path = key_path.ok_or_else(|| {
+            anyhow!("The guest agent is not available and no SSH key file was found")
+        })?;
+        println!("[!] Guest agent is not available, falling back to SSH...");
+        let guest = Guest::Ssh {
+            key_path: key_path.into(),
+            guest_ip: guest_ip.into(),
+        };
+        const MAX_RETRIES: u32 = 500;
+        let mut retries = 0;
+        while guest.run("true").is_err() {
             if retries >= MAX_RETRIES {
                 println!(
-                    "[-] Max retries reached. Failed to configure network in guest. {}",
+                    "[-] Max retries reached. Failed to reach {} over SSH.",
                     guest_ip
                 );
-            } else {
-                println!("[+] Network configured in guest.");
+                break;
+            }
+            println!("[-] Waiting for ssh to be available...");
+            std::thread::sleep(Duration::from_millis(100));
+            retries += 1;
+        }
+        Ok(guest)
+    }
+
+    fn ssh_args<'a>(key_path: &'a str, destination: &'a str, command: &'a str) -> [&'a str; 8] {
+        [
+            "-i",
+            key_path,
+            "-o",
+            "StrictHostKeyChecking=no",
+            "-o",
+            "UserKnownHostsFile=/dev/null",
+            destination,
+            command,
+        ]
+    }
+
+    /// Runs `command` in the guest, failing when it exits with a non-zero code.
+    pub fn run(&self, command: &str) -> Result<()> {
+        match self {
+            Guest::Agent { api_socket } => {
+                agent::run(api_socket, command)?;
+            }
+            Guest::Ssh { key_path, guest_ip } => {
+                let destination = format!("root@{}", guest_ip);
+                run_command(
+                    "ssh",
+                    &Self::ssh_args(key_path, &destination, command),
+                    false,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`Guest::run`], showing the output of `command`.
+    pub fn run_with_output(&self, command: &str) -> Result<()> {
+        match self {
+            Guest::Agent { api_socket } => agent::run_with_output(api_socket, command)?,
+            Guest::Ssh { key_path, guest_ip } => {
+                let destination = format!("root@{}", guest_ip);
+                run_command_with_stdout_inherit(
+                    "ssh",
+                    &Self::ssh_args(key_path, &destination, command),
+                    false,
+                )?;
             }
-            break;
         }
-        println!("[-] Waiting for ssh to be available...");
-        std::thread::sleep(std::time::Duration::from_millis(100));
-        retries += 1;
+        Ok(())
+    }
+
+    /// Copies the local file or directory `src` to `dst` in the guest.
+    pub fn copy(&self, src: &Path, dst: &str) -> Result<()> {
+        match self {
+            Guest::Agent { api_socket } => {
+                if !src.is_dir() {
+                    return agent::copy_to(api_socket, src, dst);
+                }
+                self.run(&format!("mkdir -p '{}'", dst))?;
+                for entry in fs::read_dir(src)? {
+                    let entry = entry?;
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    self.copy(
+                        &entry.path(),
+                        &format!("{}/{}", dst.trim_end_matches('/'), name),
+                    )?;
+                }
+                Ok(())
+            }
+            Guest::Ssh { key_path, guest_ip } => {
+                let src = src.display().to_string();
+                let destination = format!("root@{}:{}", guest_ip, dst);
+                run_command(
+                    "scp",
+                    &[
+                        "-r",
+                        "-p",
+                        "-i",
+                        key_path,
+                        "-o",
+                        "StrictHostKeyChecking=no",
+                        "-o",
+                        "UserKnownHostsFile=/dev/null",
+                        &src,
+                        &destination,
+                    ],
+                    false,
+                )?;
+                Ok(())
+            }
+        }
+    }
+}
+
+pub fn configure_guest_network(guest: &Guest, nameserver: &str, is_nixos: bool) -> Result<()> {
+    println!("[+] Configuring network in guest...");
+    if !is_nixos {
+        guest.run(&format!(
+            "echo 'nameserver {}' > /etc/resolv.conf",
+            nameserver
+        ))?;
+    }
+    println!("[+] Network configured in guest.");
+    Ok(())
+}
+
+/// Pins the SLAAC address and default route of the guest, in case its network
+/// manager does not use EUI-64 addresses.
+pub fn configure_guest_ipv6(guest: &Guest, ipv6_addr: &str, gateway: &str) -> Result<()> {
+    println!("[+] Configuring IPv6 address {} in guest...", ipv6_addr);
+    guest.run(&format!(
+        "ip -6 addr replace {}/64 dev eth0 && ip -6 route replace default via {} dev eth0",
+        ipv6_addr, gateway
+    ))
+}
+
+/// Brings up the extra interface `iface` of the guest with its reserved
+/// addresses, the default route stays on `eth0`.
+pub fn configure_guest_interface(guest: &Guest, iface: &str, addresses: &[String]) -> Result<()> {
+    println!(
+        "[+] Configuring {} with {} in guest...",
+        iface,
+        addresses.join(", ")
+    );
+    let mut script = format!("ip link set {} up", iface);
+    for address in addresses {
+        script.push_str(&format!(" && ip addr replace {} dev {}", address, iface));
+    }
+    guest.run(&script)
+}
+
+pub fn mount_volumes(guest: &Guest, mounts: &[(String, String)]) -> Result<()> {
+    for (device, mount) in mounts {
+        println!("[+] Mounting /dev/{} on {} in guest...", device, mount);
+        guest.run(&format!(
+            "mkdir -p '{0}' && (mountpoint -q '{0}' || mount /dev/{1} '{0}')",
+            mount, device
+        ))?;
     }
     Ok(())
 }
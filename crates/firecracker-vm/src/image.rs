@@ -0,0 +1,104 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use firecracker_prepare::{
+    firefile::{self, Firefile},
+    Distro,
+};
+use firecracker_state::{entity::image::Image, repo};
+use owo_colors::OwoColorize;
+use sqlx::{Pool, Sqlite};
+
+use crate::{config::get_config_dir, distro_rootfs, types::VmOptions};
+
+/// Builds the Firefile `file` into the image `tag` of the local image
+/// store, replacing the image previously built with that tag.
+pub async fn build(file: &Path, tag: &str) -> Result<Image> {
+    if tag.is_empty()
+        || !tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        return Err(anyhow!(
+            "Invalid image tag '{}', only letters, digits, '-', '_' and '.' are allowed",
+            tag
+        ));
+    }
+    if tag.parse::<Distro>().is_ok() {
+        return Err(anyhow!(
+            "Invalid image tag '{}', it is the name of a distribution",
+            tag
+        ));
+    }
+
+    let firefile = Firefile::load(file)?;
+    let context = fs::canonicalize(file)
+        .with_context(|| format!("Failed to resolve absolute path for {}", file.display()))?
+        .parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| anyhow!("Failed to get the directory of {}", file.display()))?;
+
+    // the base rootfs, prepared the same way `fireup up` does
+    firecracker_prepare::prepare(firefile.from, None, None)?;
+
+    let app_dir = get_config_dir().with_context(|| "Failed to get configuration directory")?;
+    fs::create_dir_all(format!("{}/images", app_dir))
+        .with_context(|| format!("Failed to create images directory: {}", app_dir))?;
+
+    let base = distro_rootfs(&app_dir, firefile.from);
+    let path = format!("{}/images/{}.img", app_dir, tag);
+    println!(
+        "[+] Building image {} from {}...",
+        tag.cyan(),
+        firefile.from.to_string().cyan()
+    );
+    firefile::build(&firefile, &context, &base, &path)
+        .with_context(|| format!("Failed to build image '{}'", tag))?;
+
+    let pool = firecracker_state::create_connection_pool().await?;
+    repo::image::save(
+        &pool,
+        Image {
+            name: tag.into(),
+            distro: firefile.from.to_string(),
+            path,
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    repo::image::find(&pool, tag)
+        .await?
+        .ok_or_else(|| anyhow!("Failed to retrieve the built image"))
+}
+
+/// Sets the distribution of `options` to the one of its image, if any.
+pub async fn resolve(options: &mut VmOptions) -> Result<()> {
+    let Some(tag) = &options.image else {
+        return Ok(());
+    };
+
+    let pool = firecracker_state::create_connection_pool().await?;
+    let image = find(&pool, tag).await?;
+    *options = options.clone().with_distro(image.distro.parse()?);
+    Ok(())
+}
+
+/// The image `tag`, which must have been built and still be on disk.
+pub async fn find(pool: &Pool<Sqlite>, tag: &str) -> Result<Image> {
+    let image = repo::image::find(pool, tag).await?.ok_or_else(|| {
+        anyhow!(
+            "No image found with the name '{}', build it with `fireup build -t {}`",
+            tag,
+            tag
+        )
+    })?;
+    if !Path::new(&image.path).exists() {
+        return Err(anyhow!(
+            "The file of image '{}' is missing: {}",
+            tag,
+            image.path
+        ));
+    }
+    Ok(image)
+}
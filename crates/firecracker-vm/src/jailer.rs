@@ -0,0 +1,192 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use firecracker_state::repo;
+use owo_colors::OwoColorize;
+
+use crate::{
+    command::run_command,
+    config::get_config_dir,
+    constants::{JAILER_MEMORY_OVERHEAD, JAILER_UID_BASE},
+    types::VmOptions,
+    volume,
+};
+
+/// Path of the API socket inside the jail.
+const API_SOCKET: &str = "/firecracker.socket";
+
+/// Path of the vsock socket inside the jail.
+pub const VSOCK_SOCKET: &str = "/vsock.socket";
+
+pub fn chroot_base_dir() -> Result<String> {
+    let app_dir = get_config_dir().with_context(|| "Failed to get configuration directory")?;
+    Ok(format!("{}/jailer", app_dir))
+}
+
+/// Root directory of the jail, as created by the jailer for the given VM.
+pub fn chroot_dir(name: &str) -> Result<String> {
+    Ok(format!("{}/firecracker/{}/root", chroot_base_dir()?, name))
+}
+
+pub fn netns(name: &str) -> String {
+    format!("fireup-{}", name)
+}
+
+/// Returns the uid/gid the jailed Firecracker of `name` runs as, reusing the
+/// one already assigned to the VM or picking the first free one.
+pub async fn uid(name: &str) -> Result<u32> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let vms = repo::virtual_machine::all(&pool).await?;
+
+    if let Some(uid) = vms
+        .iter()
+        .find(|vm| vm.name == name)
+        .and_then(|vm| vm.jailer_uid)
+    {
+        return Ok(uid);
+    }
+
+    let mut uid = JAILER_UID_BASE;
+    while vms.iter().any(|vm| vm.jailer_uid == Some(uid)) {
+        uid += 1;
+    }
+    Ok(uid)
+}
+
+pub fn setup_netns(name: &str) -> Result<()> {
+    let netns = netns(name);
+    if Path::new(&format!("/var/run/netns/{}", netns)).exists() {
+        return Ok(());
+    }
+    println!("[+] Creating network namespace {}...", netns.cyan());
+    run_command("ip", &["netns", "add", &netns], true)?;
+    Ok(())
+}
+
+/// Arguments to launch Firecracker through the jailer, the API socket ends up
+/// in the chroot and is exposed at `options.api_socket` by [`link_api_socket`].
+pub fn args(name: &str, uid: u32, options: &VmOptions) -> Result<Vec<String>> {
+    let output = run_command("which", &["firecracker"], false)
+        .with_context(|| "Failed to find the firecracker binary")?;
+    let exec_file = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    let chroot_base_dir = chroot_base_dir()?;
+    std::fs::create_dir_all(&chroot_base_dir).with_context(|| {
+        format!(
+            "Failed to create jailer chroot base directory: {}",
+            chroot_base_dir
+        )
+    })?;
+
+    let memory_max = (options.memory as u64 + JAILER_MEMORY_OVERHEAD) * 1024 * 1024;
+
+    Ok(vec![
+        "--id".into(),
+        name.into(),
+        "--exec-file".into(),
+        exec_file,
+        "--uid".into(),
+        uid.to_string(),
+        "--gid".into(),
+        uid.to_string(),
+        "--chroot-base-dir".into(),
+        chroot_base_dir,
+        "--netns".into(),
+        format!("/var/run/netns/{}", netns(name)),
+        "--cgroup-version".into(),
+        "2".into(),
+        "--parent-cgroup".into(),
+        "fireup".into(),
+        "--cgroup".into(),
+        format!("memory.max={}", memory_max),
+        "--".into(),
+        "--api-sock".into(),
+        API_SOCKET.into(),
+    ])
+}
+
+pub fn link_api_socket(name: &str, api_socket: &str) -> Result<()> {
+    let socket = format!("{}{}", chroot_dir(name)?, API_SOCKET);
+    run_command("ln", &["-sfn", &socket, api_socket], true)?;
+    Ok(())
+}
+
+/// Exposes the vsock socket of the jailed Firecracker at `socket`.
+pub fn link_vsock_socket(name: &str, socket: &str) -> Result<()> {
+    let target = format!("{}{}", chroot_dir(name)?, VSOCK_SOCKET);
+    run_command("ln", &["-sfn", &target, socket], true)?;
+    Ok(())
+}
+
+/// Hard-links `file` into the jail of `name` and returns its path as seen by
+/// the jailed Firecracker. Writable files are handed over to the jail uid.
+pub fn link(name: &str, uid: u32, file: &str, target: &str, writable: bool) -> Result<String> {
+    let chroot_dir = chroot_dir(name)?;
+    let target_path = format!("{}/{}", chroot_dir, target.trim_start_matches('/'));
+    if let Some(parent) = Path::new(&target_path).parent() {
+        run_command("mkdir", &["-p", &parent.display().to_string()], true)?;
+    }
+
+    run_command("ln", &["-f", file, &target_path], true).with_context(|| {
+        format!(
+            "Failed to hard-link {} into the jail, it must be on the same filesystem as {}",
+            file, chroot_dir
+        )
+    })?;
+
+    if writable {
+        let owner = format!("{}:{}", uid, uid);
+        run_command("chown", &[&owner, &target_path], true)?;
+    }
+
+    Ok(format!("/{}", target.trim_start_matches('/')))
+}
+
+/// Removes the jail of `name`: hands the linked disks back to the current
+/// user, then deletes the chroot, the cgroup and the network namespace.
+pub async fn cleanup(name: &str) -> Result<()> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let vm = repo::virtual_machine::find(&pool, name).await?;
+
+    let jail_dir = format!("{}/firecracker/{}", chroot_base_dir()?, name);
+    let netns = netns(name);
+    let netns_exists = Path::new(&format!("/var/run/netns/{}", netns)).exists();
+    if !Path::new(&jail_dir).exists() && !netns_exists {
+        return Ok(());
+    }
+
+    println!("[+] Cleaning up jail of {}...", name.cyan());
+
+    let app_dir = get_config_dir().with_context(|| "Failed to get configuration directory")?;
+    let mut files = vec![format!("{}/logs/firecracker-{}.log", app_dir, name)];
+    if let Some(vm) = &vm {
+        files.extend(vm.disk.clone());
+        if let Some(mounts) = &vm.volumes {
+            for mount in volume::from_column(mounts) {
+                if let Some(volume) = repo::volume::find(&pool, &mount.name).await? {
+                    files.push(volume.path);
+                }
+            }
+        }
+    }
+
+    let owner = unsafe { format!("{}:{}", libc::getuid(), libc::getgid()) };
+    for file in files.iter().filter(|file| Path::new(file).exists()) {
+        run_command("chown", &[&owner, file], true)?;
+    }
+
+    run_command("rm", &["-rf", &jail_dir], true)
+        .with_context(|| format!("Failed to remove jail directory: {}", jail_dir))?;
+
+    let cgroup = format!("/sys/fs/cgroup/fireup/{}", name);
+    if Path::new(&cgroup).exists() {
+        run_command("rmdir", &[&cgroup], true).ok();
+    }
+
+    if netns_exists {
+        run_command("ip", &["netns", "del", &netns], true)
+            .map_err(|e| anyhow!("Failed to delete network namespace {}: {}", netns, e))?;
+    }
+
+    Ok(())
+}
@@ -1,24 +1,71 @@
 use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
 use firecracker_prepare::Distro;
-use firecracker_state::{entity::virtual_machine::VirtualMachine, repo};
+use firecracker_state::{
+    entity::{
+        network::Network, virtual_machine::VirtualMachine, vm_interface::VmInterface,
+        volume::Volume,
+    },
+    repo,
+};
 use owo_colors::OwoColorize;
 use std::fs;
 
 use crate::{config::get_config_dir, types::VmOptions};
 
+pub mod agent;
+pub mod client;
 mod command;
 mod config;
+pub mod console;
 pub mod constants;
-mod coredns;
-mod dhcpd;
+pub mod dhcpd;
+pub mod disk;
+pub mod dns;
 mod firecracker;
+pub mod firewall;
 mod guest;
+pub mod image;
+pub mod jailer;
+pub mod limits;
+pub mod logs;
 pub mod mac;
-mod mosquitto;
-mod mqttc;
-mod network;
+pub mod netd;
+pub mod network;
+pub mod nic;
+pub mod ports;
+pub mod provision;
+pub mod radvd;
+pub mod snapshot;
 mod tailscale;
+pub mod teardown;
+mod terminal;
 pub mod types;
+pub mod volume;
+
+/// Log file of the Firecracker process of the VM `name`.
+pub fn log_file(name: &str) -> Result<String> {
+    let app_dir = get_config_dir().with_context(|| "Failed to get configuration directory")?;
+    Ok(format!("{}/logs/firecracker-{}.log", app_dir, name))
+}
+
+/// Rootfs prepared by `firecracker_prepare::prepare` for `distro`.
+pub(crate) fn distro_rootfs(app_dir: &str, distro: Distro) -> String {
+    match distro {
+        Distro::Debian => format!("{}/debian-rootfs.img", app_dir),
+        Distro::Alpine => format!("{}/alpine-rootfs.img", app_dir),
+        Distro::NixOS => format!("{}/nixos-rootfs.img", app_dir),
+        Distro::Ubuntu => format!("{}/ubuntu-rootfs.img", app_dir),
+        Distro::Fedora => format!("{}/fedora-rootfs.img", app_dir),
+        Distro::Gentoo => format!("{}/gentoo-rootfs.img", app_dir),
+        Distro::Slackware => format!("{}/slackware-rootfs.img", app_dir),
+        Distro::Opensuse => format!("{}/opensuse-rootfs.img", app_dir),
+        Distro::OpensuseTumbleweed => format!("{}/opensuse-tumbleweed-rootfs.img", app_dir),
+        Distro::Almalinux => format!("{}/almalinux-rootfs.img", app_dir),
+        Distro::RockyLinux => format!("{}/rockylinux-rootfs.img", app_dir),
+        Distro::Archlinux => format!("{}/archlinux-rootfs.img", app_dir),
+    }
+}
 
 pub async fn setup(
     options: &VmOptions,
@@ -27,6 +74,13 @@ pub async fn setup(
     kernel_file: &str,
 ) -> Result<String> {
     let distro: Distro = options.clone().into();
+    let options = &nic::with_primary(options);
+    let network = network::for_vm(options).await?;
+    let options = &VmOptions {
+        bridge: network.bridge.clone(),
+        network: Some(network.name.clone()),
+        ..options.clone()
+    };
     let app_dir = get_config_dir().with_context(|| "Failed to get configuration directory")?;
 
     let name = options
@@ -44,8 +98,48 @@ pub async fn setup(
 
     fs::create_dir_all(format!("{}/logs", app_dir))
         .with_context(|| format!("Failed to create logs directory: {}", app_dir))?;
+    let capture = logs::capture_setup(&name)?;
+    let result = configure(options, distro, &network, &name, pid, vm_id, kernel_file).await;
+    if let (Some(capture), Err(error)) = (capture, &result) {
+        // printed by the caller once the capture is over, keep it in the log too
+        capture.fail(error);
+    }
+    result
+}
 
-    let logfile = format!("{}/logs/firecracker-{}.log", app_dir, name);
+/// Configures the VM `name` run by the Firecracker process `pid` and records it.
+async fn configure(
+    options: &VmOptions,
+    distro: Distro,
+    network: &Network,
+    name: &str,
+    pid: u32,
+    vm_id: Option<String>,
+    kernel_file: &str,
+) -> Result<String> {
+    let name = name.to_string();
+    let app_dir = get_config_dir().with_context(|| "Failed to get configuration directory")?;
+    let ports = ports::parse(options.ports.as_deref().unwrap_or_default())?;
+    ports::check_available(&name, &ports).await?;
+    if let Some(options) = &options.firewall {
+        firewall::Policy::try_from(options)?;
+    }
+    let existing = match &vm_id {
+        Some(id) => {
+            let pool = firecracker_state::create_connection_pool().await?;
+            repo::vm_interface::find_by_vm(&pool, id).await?
+        }
+        None => vec![],
+    };
+    let nics = nic::resolve(options, network, &existing).await?;
+    if nics.len() > 1 && options.jailer.unwrap_or(false) {
+        return Err(anyhow!(
+            "Jailed MicroVMs support a single network interface, {} were given",
+            nics.len()
+        ));
+    }
+
+    let logfile = log_file(&name)?;
     fs::File::create(&logfile)
         .with_context(|| format!("Failed to create log file: {}", logfile))?;
 
@@ -60,19 +154,12 @@ pub async fn setup(
         .to_string();
 
     // readonly rootfs (squashfs)
-    let img_file = match distro {
-        Distro::Debian => format!("{}/debian-rootfs.img", app_dir),
-        Distro::Alpine => format!("{}/alpine-rootfs.img", app_dir),
-        Distro::NixOS => format!("{}/nixos-rootfs.img", app_dir),
-        Distro::Ubuntu => format!("{}/ubuntu-rootfs.img", app_dir),
-        Distro::Fedora => format!("{}/fedora-rootfs.img", app_dir),
-        Distro::Gentoo => format!("{}/gentoo-rootfs.img", app_dir),
-        Distro::Slackware => format!("{}/slackware-rootfs.img", app_dir),
-        Distro::Opensuse => format!("{}/opensuse-rootfs.img", app_dir),
-        Distro::OpensuseTumbleweed => format!("{}/opensuse-tumbleweed-rootfs.img", app_dir),
-        Distro::Almalinux => format!("{}/almalinux-rootfs.img", app_dir),
-        Distro::RockyLinux => format!("{}/rockylinux-rootfs.img", app_dir),
-        Distro::Archlinux => format!("{}/archlinux-rootfs.img", app_dir),
+    let img_file = match &options.image {
+        Some(tag) => {
+            let pool = firecracker_state::create_connection_pool().await?;
+            image::find(&pool, tag).await?.path
+        }
+        None => distro_rootfs(&app_dir, distro),
     };
 
     let rootfs = fs::canonicalize(&img_file)
@@ -80,61 +167,193 @@ pub async fn setup(
         .display()
         .to_string();
 
+    // only needed to reach guests prepared without the agent
     let key_name = glob::glob(format!("{}/id_rsa", app_dir).as_str())
         .with_context(|| "Failed to glob ssh key files")?
         .last()
-        .ok_or_else(|| anyhow!("No SSH key file found"))?
-        .with_context(|| "Failed to get SSH key path")?;
-    let key_name = fs::canonicalize(&key_name)
-        .with_context(|| {
-            format!(
-                "Failed to resolve absolute path for SSH key: {}",
-                key_name.display()
-            )
-        })?
-        .display()
-        .to_string();
-    let arch = command::run_command("uname", &["-m"], false)?.stdout;
-    let arch = String::from_utf8_lossy(&arch).trim().to_string();
-    network::setup_network(options)?;
-    mosquitto::setup_mosquitto(options)?;
-    coredns::setup_coredns(options)?;
-    dhcpd::setup_kea_dhcp(options)?;
+        .transpose()
+        .with_context(|| "Failed to get SSH key path")?
+        .map(|key_name| {
+            fs::canonicalize(&key_name).with_context(|| {
+                format!(
+                    "Failed to resolve absolute path for SSH key: {}",
+                    key_name.display()
+                )
+            })
+        })
+        .transpose()?
+        .map(|key_name| key_name.display().to_string());
+    let jailer_uid = match options.jailer.unwrap_or(false) {
+        true => Some(jailer::uid(&name).await?),
+        false => None,
+    };
+    match jailer_uid {
+        Some(uid) => {
+            network::setup_jailed_network(options, network, &jailer::netns(&name), uid).await?
+        }
+        None => network::setup_network(options, network).await?,
+    }
+    netd::ensure_running(network)?;
+    match &options.ip {
+        Some(ip) => dhcpd::reserve(&name, &options.mac_address, ip, network).await?,
+        None => {
+            let pool = firecracker_state::create_connection_pool().await?;
+            repo::lease::unreserve(&pool, &options.mac_address).await?;
+        }
+    }
+    dns::configure_host_resolver(network)?;
 
-    firecracker::configure(&logfile, &kernel, &rootfs, &arch, &options)?;
+    let mut extra_addresses = vec![];
+    for nic in nics.iter().skip(1) {
+        network::setup_network(&nic.options(options), &nic.network).await?;
+        netd::ensure_running(&nic.network)?;
+        extra_addresses.push(dhcpd::assign(&name, &nic.mac_address, &nic.network).await?);
+    }
+    firewall::sync_vm(options, &nics).await?;
 
-    let guest_ip = format!("{}.firecracker", name);
-    guest::configure_guest_network(&key_name, &guest_ip, distro == Distro::NixOS)?;
+    let disk = match options.disk_size {
+        Some(size) => Some(disk::setup_disk(&name, size)?),
+        None => None,
+    };
 
-    tailscale::setup_tailscale(&name, options)?;
+    let volumes = match &options.volumes {
+        Some(mounts) => volume::attach(&name, mounts).await?,
+        None => vec![],
+    };
+    let attached = volumes
+        .iter()
+        .map(|(volume, _)| volume.clone())
+        .collect::<Vec<_>>();
 
-    let pool = firecracker_state::create_connection_pool().await?;
+    let booted_at = Utc::now();
+    match jailer_uid {
+        Some(uid) => {
+            // the jailed Firecracker only sees the files linked into its chroot
+            let kernel_file = options.vmlinux.clone().unwrap_or_else(|| kernel.clone());
+            let jailed_options = VmOptions {
+                vmlinux: Some(jailer::link(&name, uid, &kernel_file, "vmlinux", false)?),
+                ..options.clone()
+            };
+            let jailed_disk = disk
+                .as_deref()
+                .map(|disk| jailer::link(&name, uid, disk, "disk.img", true))
+                .transpose()?;
+            let jailed_volumes = attached
+                .iter()
+                .map(|volume| {
+                    let target = format!("volumes/{}.img", volume.name);
+                    Ok(Volume {
+                        path: jailer::link(&name, uid, &volume.path, &target, true)?,
+                        ..volume.clone()
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            jailer::link_vsock_socket(&name, &agent::socket(&options.api_socket))?;
+            firecracker::configure(
+                &jailer::link(&name, uid, &logfile, "firecracker.log", true)?,
+                &kernel,
+                &jailer::link(&name, uid, &rootfs, "rootfs.img", false)?,
+                jailed_disk.as_deref(),
+                &jailed_volumes,
+                &nics,
+                &jailed_options,
+            )
+            .await?;
+        }
+        None => {
+            firecracker::configure(
+                &logfile,
+                &kernel,
+                &rootfs,
+                disk.as_deref(),
+                &attached,
+                &nics,
+                options,
+            )
+            .await?;
+        }
+    }
 
-    let ip_file = format!("/tmp/firecracker-{}.ip", name);
+    let ip_addr = dhcpd::wait_for_lease(&options.mac_address, booted_at).await?;
 
-    // loop until the IP file is created
-    let mut attempts = 0;
-    while attempts < 30 {
-        println!("[*] Waiting for VM to obtain an IP address...");
-        if fs::metadata(&ip_file).is_ok() {
-            break;
+    let guest = guest::Guest::connect(&options.api_socket, key_name.as_deref(), &ip_addr)?;
+    guest::configure_guest_network(&guest, &network.gateway, distro == Distro::NixOS)?;
+
+    let mut ip_addresses = vec![ip_addr.clone()];
+    if let Some(prefix) = network::ipv6_prefix(network)? {
+        let ipv6_addr = network::eui64(prefix, &options.mac_address)?;
+        guest::configure_guest_ipv6(
+            &guest,
+            &ipv6_addr.to_string(),
+            &network::ipv6_gateway(prefix).to_string(),
+        )?;
+        ip_addresses.push(ipv6_addr.to_string());
+    }
+
+    let mut interfaces = vec![VmInterface {
+        iface_id: nics[0].iface_id.clone(),
+        network: network.name.clone(),
+        tap: options.tap.clone(),
+        mac_address: options.mac_address.clone(),
+        ip_address: Some(ip_addresses.join(",")),
+        ..Default::default()
+    }];
+    for (nic, ipv4_addr) in nics.iter().skip(1).zip(&extra_addresses) {
+        let mut addresses = vec![format!(
+            "{}/{}",
+            ipv4_addr,
+            network::cidr(&nic.network)?.prefix
+        )];
+        let mut nic_addresses = vec![ipv4_addr.clone()];
+        if let Some(prefix) = network::ipv6_prefix(&nic.network)? {
+            let ipv6_addr = network::eui64(prefix, &nic.mac_address)?.to_string();
+            addresses.push(format!("{}/64", ipv6_addr));
+            nic_addresses.push(ipv6_addr);
         }
-        std::thread::sleep(std::time::Duration::from_millis(500));
-        attempts += 1;
+        guest::configure_guest_interface(&guest, &nic.iface_id, &addresses)?;
+        interfaces.push(VmInterface {
+            iface_id: nic.iface_id.clone(),
+            network: nic.network.name.clone(),
+            tap: nic.tap.clone(),
+            mac_address: nic.mac_address.clone(),
+            ip_address: Some(nic_addresses.join(",")),
+            ..Default::default()
+        });
     }
 
-    let ip_addr = fs::read_to_string(&ip_file)
-        .with_context(|| format!("Failed to read IP address from file: {}", ip_file))?
-        .trim()
-        .to_string();
+    // drives show up in the guest in the order they were attached, right
+    // after the rootfs (vda) and the persistent disk
+    let first_device = if disk.is_some() { 2 } else { 1 };
+    let mounts = volumes
+        .iter()
+        .enumerate()
+        .map(|(i, (_, mount))| {
+            (
+                format!("vd{}", (b'a' + (first_device + i) as u8) as char),
+                mount.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    guest::mount_volumes(&guest, &mounts)?;
 
-    fs::remove_file(&ip_file)
-        .with_context(|| format!("Failed to remove IP address file: {}", ip_file))?;
+    tailscale::setup_tailscale(&name, &guest, options)?;
 
+    let pool = firecracker_state::create_connection_pool().await?;
+
+    let existing_vm = match &vm_id {
+        Some(id) => repo::virtual_machine::find(&pool, id).await?,
+        None => None,
+    };
+    // a MicroVM started away from its project stays part of it
     let project_dir = match fs::metadata("fire.toml").is_ok() {
         true => Some(std::env::current_dir()?.display().to_string()),
-        false => None,
+        false => existing_vm.as_ref().and_then(|vm| vm.project_dir.clone()),
     };
+    let project = options
+        .project
+        .clone()
+        .or_else(|| existing_vm.and_then(|vm| vm.project));
 
     let kernel = match &options.vmlinux {
         Some(path) => path.clone(),
@@ -161,13 +380,30 @@ pub async fn setup(
                     name: name.clone(),
                     pid: Some(pid),
                     distro: distro.to_string(),
-                    ip_address: Some(ip_addr.clone()),
+                    ip_address: Some(ip_addresses.join(",")),
                     status: "RUNNING".into(),
                     project_dir,
                     vmlinux: Some(kernel),
                     rootfs: Some(rootfs),
                     bootargs: options.bootargs.clone(),
                     ssh_keys: options.ssh_keys.as_ref().map(|keys| keys.join(",")),
+                    disk: disk.clone(),
+                    disk_size: options.disk_size,
+                    volumes: options
+                        .volumes
+                        .as_ref()
+                        .filter(|mounts| !mounts.is_empty())
+                        .map(|mounts| volume::to_column(mounts)),
+                    jailer_uid,
+                    static_ip: options.ip.clone(),
+                    network: options.network.clone(),
+                    ports: Some(ports::to_column(&ports)).filter(|ports| !ports.is_empty()),
+                    firewall: options.firewall.as_ref().map(firewall::to_column),
+                    limits: options.limits.as_ref().and_then(limits::to_column),
+                    restart: options.restart.map(|policy| policy.to_string()),
+                    project: project.clone(),
+                    image: options.image.clone(),
+                    provision: options.provision.as_ref().map(provision::to_column),
                     ..Default::default()
                 },
             )
@@ -187,13 +423,30 @@ pub async fn setup(
                     name: name.clone(),
                     pid: Some(pid),
                     distro: distro.to_string(),
-                    ip_address: Some(ip_addr.clone()),
+                    ip_address: Some(ip_addresses.join(",")),
                     status: "RUNNING".into(),
                     project_dir,
                     vmlinux: Some(kernel),
                     rootfs: Some(rootfs),
                     bootargs: options.bootargs.clone(),
                     ssh_keys: options.ssh_keys.as_ref().map(|keys| keys.join(",")),
+                    disk: disk.clone(),
+                    disk_size: options.disk_size,
+                    volumes: options
+                        .volumes
+                        .as_ref()
+                        .filter(|mounts| !mounts.is_empty())
+                        .map(|mounts| volume::to_column(mounts)),
+                    jailer_uid,
+                    static_ip: options.ip.clone(),
+                    network: options.network.clone(),
+                    ports: Some(ports::to_column(&ports)).filter(|ports| !ports.is_empty()),
+                    firewall: options.firewall.as_ref().map(firewall::to_column),
+                    limits: options.limits.as_ref().and_then(limits::to_column),
+                    restart: options.restart.map(|policy| policy.to_string()),
+                    project: project.clone(),
+                    image: options.image.clone(),
+                    provision: options.provision.as_ref().map(provision::to_column),
                     ..Default::default()
                 },
             )
@@ -201,6 +454,10 @@ pub async fn setup(
         }
     };
 
+    repo::vm_interface::replace(&pool, &vm_id, &interfaces).await?;
+    ports::publish(&network.bridge, &ip_addr, &ports).await?;
+    provision::provision(&vm_id, &name, &guest, options).await?;
+
     println!("[✓] MicroVM booted and network is configured 🎉");
 
     println!("SSH into the VM using the following command:");
@@ -0,0 +1,205 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use fire_config::LimitsOptions;
+use firecracker_state::repo;
+use owo_colors::OwoColorize;
+
+use crate::{
+    client::{FirecrackerClient, PartialDrive, PartialNetworkInterface, RateLimiter, TokenBucket},
+    volume,
+};
+
+/// Refill period of the token buckets, in milliseconds.
+const REFILL_TIME: u64 = 1000;
+
+fn bucket(per_second: u64) -> TokenBucket {
+    TokenBucket {
+        size: per_second,
+        refill_time: REFILL_TIME,
+    }
+}
+
+fn bandwidth(mbps: u32) -> TokenBucket {
+    bucket(mbps as u64 * 1_000_000 / 8)
+}
+
+/// Rate limiter of a network interface, `None` or 0 leaves it unlimited.
+pub fn net_rate_limiter(mbps: Option<u32>) -> Option<RateLimiter> {
+    mbps.filter(|mbps| *mbps > 0).map(|mbps| RateLimiter {
+        bandwidth: Some(bandwidth(mbps)),
+        ops: None,
+    })
+}
+
+/// Rate limiter applied to each drive of a MicroVM.
+pub fn drive_rate_limiter(limits: &LimitsOptions) -> Option<RateLimiter> {
+    let bandwidth = limits.disk_mbps.filter(|mbps| *mbps > 0).map(bandwidth);
+    let ops = limits
+        .disk_iops
+        .filter(|iops| *iops > 0)
+        .map(|iops| bucket(iops as u64));
+    (bandwidth.is_some() || ops.is_some()).then_some(RateLimiter { bandwidth, ops })
+}
+
+/// A PATCH replaces the whole rate limiter, unset buckets are sent with a
+/// zero size to lift their limit.
+fn patch(rate_limiter: Option<RateLimiter>) -> RateLimiter {
+    let unlimited = || TokenBucket {
+        size: 0,
+        refill_time: 0,
+    };
+    let rate_limiter = rate_limiter.unwrap_or(RateLimiter {
+        bandwidth: None,
+        ops: None,
+    });
+    RateLimiter {
+        bandwidth: rate_limiter.bandwidth.or_else(|| Some(unlimited())),
+        ops: rate_limiter.ops.or_else(|| Some(unlimited())),
+    }
+}
+
+/// Applies `changes` on top of `limits`, a 0 removes the limit.
+pub fn merge(limits: &LimitsOptions, changes: &LimitsOptions) -> LimitsOptions {
+    let merge =
+        |current: Option<u32>, change: Option<u32>| change.or(current).filter(|value| *value > 0);
+    LimitsOptions {
+        net_rx_mbps: merge(limits.net_rx_mbps, changes.net_rx_mbps),
+        net_tx_mbps: merge(limits.net_tx_mbps, changes.net_tx_mbps),
+        disk_iops: merge(limits.disk_iops, changes.disk_iops),
+        disk_mbps: merge(limits.disk_mbps, changes.disk_mbps),
+    }
+}
+
+/// Serializes the limits of a VM as JSON, `None` when there are none.
+pub fn to_column(limits: &LimitsOptions) -> Option<String> {
+    match *limits == LimitsOptions::default() {
+        true => None,
+        false => serde_json::to_string(limits).ok(),
+    }
+}
+
+pub fn from_column(column: &str) -> Option<LimitsOptions> {
+    serde_json::from_str(column).ok()
+}
+
+/// Changes the rate limits of a MicroVM, live through the Firecracker API
+/// when it is running. Returns the limits now in effect.
+pub async fn update(name: &str, changes: &LimitsOptions) -> Result<LimitsOptions> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let vm = repo::virtual_machine::find(&pool, name)
+        .await?
+        .ok_or_else(|| anyhow!("No virtual machine found with name or id '{}'", name))?;
+
+    let current = vm
+        .limits
+        .as_deref()
+        .and_then(from_column)
+        .unwrap_or_default();
+    let limits = merge(&current, changes);
+
+    let running =
+        (vm.status == "RUNNING" || vm.status == "PAUSED") && Path::new(&vm.api_socket).exists();
+    if running {
+        let client = FirecrackerClient::new(&vm.api_socket);
+        // the limits apply to every interface, as when the VM was set up
+        let mut ifaces = repo::vm_interface::find_by_vm(&pool, &vm.id)
+            .await?
+            .into_iter()
+            .map(|iface| iface.iface_id)
+            .collect::<Vec<_>>();
+        if ifaces.is_empty() {
+            ifaces.push("eth0".into());
+        }
+        println!("[+] Updating network rate limits of {}...", vm.name.cyan());
+        for iface in ifaces {
+            client
+                .patch_network_interface(&PartialNetworkInterface {
+                    iface_id: iface.clone(),
+                    rx_rate_limiter: patch(net_rate_limiter(limits.net_rx_mbps)),
+                    tx_rate_limiter: patch(net_rate_limiter(limits.net_tx_mbps)),
+                })
+                .await
+                .with_context(|| format!("Failed to update network rate limits of {}", iface))?;
+        }
+
+        let mut drives = vec!["rootfs".to_string()];
+        if vm.disk.is_some() {
+            drives.push("overlay".into());
+        }
+        for mount in vm
+            .volumes
+            .as_deref()
+            .map(volume::from_column)
+            .unwrap_or_default()
+        {
+            drives.push(format!("vol-{}", mount.name));
+        }
+        println!("[+] Updating drive rate limits of {}...", vm.name.cyan());
+        for drive in drives {
+            client
+                .patch_drive(&PartialDrive {
+                    drive_id: drive.clone(),
+                    rate_limiter: patch(drive_rate_limiter(&limits)),
+                })
+                .await
+                .with_context(|| format!("Failed to update rate limits of drive {}", drive))?;
+        }
+    }
+
+    repo::virtual_machine::update_limits(&pool, &vm.id, to_column(&limits)).await?;
+    Ok(limits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiters() {
+        let limiter = net_rate_limiter(Some(100)).unwrap();
+        assert_eq!(limiter.bandwidth, Some(bucket(12_500_000)));
+        assert_eq!(limiter.ops, None);
+        assert_eq!(net_rate_limiter(Some(0)), None);
+
+        let limiter = drive_rate_limiter(&LimitsOptions {
+            disk_iops: Some(500),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(limiter.bandwidth, None);
+        assert_eq!(limiter.ops, Some(bucket(500)));
+        assert_eq!(drive_rate_limiter(&LimitsOptions::default()), None);
+
+        let patched = patch(Some(limiter));
+        assert_eq!(patched.bandwidth.map(|bucket| bucket.size), Some(0));
+        assert_eq!(patched.ops, Some(bucket(500)));
+    }
+
+    #[test]
+    fn test_merge() {
+        let current = LimitsOptions {
+            net_rx_mbps: Some(100),
+            disk_iops: Some(500),
+            ..Default::default()
+        };
+        let merged = merge(
+            &current,
+            &LimitsOptions {
+                net_tx_mbps: Some(50),
+                disk_iops: Some(0),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            merged,
+            LimitsOptions {
+                net_rx_mbps: Some(100),
+                net_tx_mbps: Some(50),
+                disk_iops: None,
+                disk_mbps: None,
+            }
+        );
+        assert_eq!(to_column(&LimitsOptions::default()), None);
+    }
+}
@@ -0,0 +1,562 @@
+use std::{
+    fmt, fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime};
+
+use crate::{config::get_config_dir, console};
+
+/// Logs written by fireup are trimmed down to their most recent half past
+/// that size.
+pub(crate) const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+/// Format of the timestamps starting the lines of every log, the one of
+/// Firecracker.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.9f";
+
+/// Whether setups copy the output of the process to their log, see
+/// [`capture_setups`].
+static CAPTURE_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Set while the output of a setup is captured, only one at a time can be.
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// Log of the Firecracker process.
+    Vmm,
+    /// Serial console of the guest.
+    Console,
+    /// Output of the CLI while it sets the MicroVM up, see [`capture_setups`].
+    Setup,
+}
+
+impl Source {
+    pub const ALL: [Source; 3] = [Source::Vmm, Source::Console, Source::Setup];
+
+    pub fn log_file(&self, name: &str) -> Result<String> {
+        match self {
+            Source::Vmm => crate::log_file(name),
+            Source::Console => console::log_file(name),
+            Source::Setup => {
+                let app_dir =
+                    get_config_dir().with_context(|| "Failed to get configuration directory")?;
+                Ok(format!("{}/logs/setup-{}.log", app_dir, name))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Vmm => write!(f, "vmm"),
+            Source::Console => write!(f, "console"),
+            Source::Setup => write!(f, "setup"),
+        }
+    }
+}
+
+impl FromStr for Source {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "vmm" | "firecracker" => Ok(Source::Vmm),
+            "console" => Ok(Source::Console),
+            "setup" => Ok(Source::Setup),
+            _ => Err(anyhow!(
+                "Invalid log source: {}, expected vmm, console or setup",
+                s
+            )),
+        }
+    }
+}
+
+/// Log file which keeps only its most recent half once it grows past `max`
+/// bytes.
+pub(crate) struct RingLog {
+    path: String,
+    file: fs::File,
+    size: u64,
+    max: u64,
+}
+
+impl RingLog {
+    pub(crate) fn open(path: &str, max: u64) -> io::Result<Self> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        let size = file.metadata()?.len();
+        Ok(RingLog {
+            path: path.into(),
+            file,
+            size,
+            max,
+        })
+    }
+
+    pub(crate) fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        let data = &data[data.len().saturating_sub(self.max as usize)..];
+        if self.size + data.len() as u64 > self.max {
+            let content = fs::read(&self.path)?;
+            let tail = &content[content.len().saturating_sub((self.max / 2) as usize)..];
+            // start on a whole line when there is one
+            let tail = match tail.iter().position(|&b| b == b'\n') {
+                Some(i) if i + 1 < tail.len() => &tail[i + 1..],
+                _ => tail,
+            };
+            // the file is opened in append mode, writes go on after the tail
+            fs::write(&self.path, tail)?;
+            self.size = tail.len() as u64;
+        }
+        self.file.write_all(data)?;
+        self.size += data.len() as u64;
+        Ok(())
+    }
+}
+
+/// [`RingLog`] starting every line with the time it was written at.
+pub(crate) struct StampedLog {
+    ring: RingLog,
+    line_start: bool,
+}
+
+impl StampedLog {
+    pub(crate) fn open(path: &str) -> io::Result<Self> {
+        Ok(StampedLog {
+            ring: RingLog::open(path, MAX_LOG_SIZE)?,
+            line_start: true,
+        })
+    }
+
+    pub(crate) fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        let timestamp = Local::now().format(TIMESTAMP_FORMAT).to_string();
+        let mut stamped = Vec::with_capacity(data.len() + timestamp.len() + 1);
+        for line in data.split_inclusive(|&b| b == b'\n') {
+            if self.line_start {
+                stamped.extend_from_slice(timestamp.as_bytes());
+                stamped.push(b' ');
+            }
+            stamped.extend_from_slice(line);
+            self.line_start = line.ends_with(b"\n");
+        }
+        self.ring.write(&stamped)
+    }
+}
+
+/// Drops the color codes of the output of fireup.
+fn strip_ansi(data: &[u8]) -> Vec<u8> {
+    let mut stripped = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte != 0x1b {
+            stripped.push(byte);
+            continue;
+        }
+        // CSI sequences end with a byte in the @ to ~ range
+        if bytes.next() == Some(&b'[') {
+            for &byte in bytes.by_ref() {
+                if (b'@'..=b'~').contains(&byte) {
+                    break;
+                }
+            }
+        }
+    }
+    stripped
+}
+
+/// Output of fireup copied to the setup log of a MicroVM, until dropped.
+pub(crate) struct Capture {
+    stdout: OwnedFd,
+    relay: Option<JoinHandle<()>>,
+    logfile: String,
+}
+
+/// Lets the setups of this process copy its output to their log. Only meant
+/// for the CLI, which sets a single MicroVM up at a time: stdout is shared
+/// by the whole process, so the log of a VM set up by `fireup serve` would
+/// also get the output of the server and of the other setups.
+pub fn capture_setups() {
+    CAPTURE_ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Copies what fireup prints to the setup log of the VM `name` while the
+/// returned [`Capture`] lives. `None` unless [`capture_setups`] was called,
+/// or when another setup is already being captured.
+pub(crate) fn capture_setup(name: &str) -> Result<Option<Capture>> {
+    if !CAPTURE_ENABLED.load(Ordering::SeqCst) || CAPTURING.swap(true, Ordering::SeqCst) {
+        return Ok(None);
+    }
+    let capture = start_capture(name);
+    if capture.is_err() {
+        CAPTURING.store(false, Ordering::SeqCst);
+    }
+    capture.map(Some)
+}
+
+fn start_capture(name: &str) -> Result<Capture> {
+    let logfile = Source::Setup.log_file(name)?;
+    let mut log = StampedLog::open(&logfile)
+        .with_context(|| format!("Failed to open setup log: {}", logfile))?;
+
+    io::stdout().flush()?;
+    let mut fds = [0; 2];
+    let (stdout, reader) = unsafe {
+        if libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) < 0 {
+            return Err(io::Error::last_os_error()).with_context(|| "Failed to create a pipe");
+        }
+        let (reader, writer) = (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1]));
+        let stdout = libc::fcntl(1, libc::F_DUPFD_CLOEXEC, 0);
+        if stdout < 0 || libc::dup2(writer.as_raw_fd(), 1) < 0 {
+            return Err(io::Error::last_os_error()).with_context(|| "Failed to capture stdout");
+        }
+        (OwnedFd::from_raw_fd(stdout), reader)
+    };
+
+    let mut terminal = fs::File::from(stdout.try_clone()?);
+    let relay = thread::spawn(move || {
+        let mut reader = fs::File::from(reader);
+        let mut buffer = [0; 4096];
+        loop {
+            let n = match reader.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            terminal.write_all(&buffer[..n]).ok();
+            log.write(&strip_ansi(&buffer[..n])).ok();
+        }
+    });
+    Ok(Capture {
+        stdout,
+        relay: Some(relay),
+        logfile,
+    })
+}
+
+impl Capture {
+    /// Ends the capture and records the `error` the setup failed with,
+    /// printed afterwards by the caller.
+    pub(crate) fn fail(self, error: &anyhow::Error) {
+        let logfile = self.logfile.clone();
+        drop(self);
+        if let Ok(mut log) = StampedLog::open(&logfile) {
+            log.write(format!("Error: {:#}\n", error).as_bytes()).ok();
+        }
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        io::stdout().flush().ok();
+        // closes the last write end of the pipe, which ends the relay
+        unsafe { libc::dup2(self.stdout.as_raw_fd(), 1) };
+        if let Some(relay) = self.relay.take() {
+            relay.join().ok();
+        }
+        CAPTURING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Parses `--since` values: a duration back from `now` such as `10m` or
+/// `1h30m`, or a local date and time.
+pub fn parse_since(since: &str, now: NaiveDateTime) -> Result<NaiveDateTime> {
+    let since = since.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(since) {
+        return Ok(time.with_timezone(&Local).naive_local());
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(since, format) {
+            return Ok(time);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(since, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap());
+    }
+
+    let invalid = || {
+        anyhow!(
+            "Invalid --since value: {}, expected e.g. 10m or a date",
+            since
+        )
+    };
+    let mut total = Duration::zero();
+    let mut digits = String::new();
+    for c in since.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let value = digits.parse::<i64>().map_err(|_| invalid())?;
+        digits.clear();
+        total += match c {
+            's' => Duration::seconds(value),
+            'm' => Duration::minutes(value),
+            'h' => Duration::hours(value),
+            'd' => Duration::days(value),
+            _ => return Err(invalid()),
+        };
+    }
+    if !digits.is_empty() || total.is_zero() {
+        return Err(invalid());
+    }
+    Ok(now - total)
+}
+
+fn timestamp(line: &str) -> Option<NaiveDateTime> {
+    let token = line.split_whitespace().next()?;
+    NaiveDateTime::parse_from_str(token, "%Y-%m-%dT%H:%M:%S%.f").ok()
+}
+
+/// A line of a log. Lines not starting with a timestamp, as the ones of a
+/// message spanning several, are given the one of the line before.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub source: Source,
+    pub timestamp: Option<NaiveDateTime>,
+    pub text: String,
+}
+
+struct LogFile {
+    source: Source,
+    path: String,
+    position: u64,
+    partial: Vec<u8>,
+    last: Option<NaiveDateTime>,
+}
+
+impl LogFile {
+    fn line(&mut self, bytes: &[u8]) -> Line {
+        let text = String::from_utf8_lossy(bytes)
+            .trim_end_matches(['\r', '\n'])
+            .to_string();
+        if let Some(timestamp) = timestamp(&text) {
+            self.last = Some(timestamp);
+        }
+        Line {
+            source: self.source,
+            timestamp: self.last,
+            text,
+        }
+    }
+
+    fn read(&mut self) -> Result<Vec<Line>> {
+        let mut file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to open log file: {}", self.path))
+            }
+        };
+        // truncated when the MicroVM restarted, or trimmed
+        if file.metadata()?.len() < self.position {
+            self.position = 0;
+            self.partial.clear();
+        }
+        file.seek(SeekFrom::Start(self.position))?;
+        let mut content = std::mem::take(&mut self.partial);
+        let n = file.read_to_end(&mut content)?;
+        self.position += n as u64;
+
+        let complete = content
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        self.partial = content.split_off(complete);
+        Ok(content
+            .split_inclusive(|&b| b == b'\n')
+            .map(|line| self.line(line))
+            .collect())
+    }
+}
+
+/// Logs of a MicroVM, read as they grow.
+pub struct Logs {
+    files: Vec<LogFile>,
+}
+
+impl Logs {
+    pub fn open(name: &str, sources: &[Source]) -> Result<Self> {
+        let files = sources
+            .iter()
+            .map(|source| {
+                Ok(LogFile {
+                    source: *source,
+                    path: source.log_file(name)?,
+                    position: 0,
+                    partial: vec![],
+                    last: None,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Logs { files })
+    }
+
+    /// The lines written since the last call, the whole logs on the first.
+    pub fn next_lines(&mut self) -> Result<Vec<Line>> {
+        let mut lines = vec![];
+        for file in &mut self.files {
+            lines.extend(file.read()?);
+        }
+        Ok(lines)
+    }
+
+    /// The lines still being written, such as a login prompt.
+    pub fn partial_lines(&mut self) -> Vec<Line> {
+        self.files
+            .iter_mut()
+            .filter(|file| !file.partial.is_empty())
+            .map(|file| {
+                let partial = std::mem::take(&mut file.partial);
+                file.line(&partial)
+            })
+            .collect()
+    }
+}
+
+/// Interleaves the lines of several logs by time, then keeps the ones from
+/// `since` on and the last `tail` of them.
+pub fn select(
+    mut lines: Vec<Line>,
+    since: Option<NaiveDateTime>,
+    tail: Option<usize>,
+) -> Vec<Line> {
+    lines.sort_by_key(|line| line.timestamp);
+    if let Some(since) = since {
+        lines.retain(|line| line.timestamp.is_some_and(|timestamp| timestamp >= since));
+    }
+    if let Some(tail) = tail {
+        lines.drain(..lines.len().saturating_sub(tail));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn test_ring_log() {
+        let path = std::env::temp_dir().join(format!("fireup-ring-{}.log", std::process::id()));
+        let path = path.display().to_string();
+        fs::remove_file(&path).ok();
+
+        let mut log = RingLog::open(&path, 10).unwrap();
+        log.write(b"0123456").unwrap();
+        log.write(b"789").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"0123456789");
+        log.write(b"ab").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"56789ab");
+        log.write(b"abcdefghijklmnop").unwrap();
+        assert!(fs::read(&path).unwrap().ends_with(b"ghijklmnop"));
+
+        fs::write(&path, b"").unwrap();
+        let mut log = RingLog::open(&path, 10).unwrap();
+        log.write(b"01\n345\n78").unwrap();
+        log.write(b"9\n").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"789\n");
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let now = at("2026-10-18 12:00:00");
+        assert_eq!(parse_since("10m", now).unwrap(), at("2026-10-18 11:50:00"));
+        assert_eq!(
+            parse_since("1h30m", now).unwrap(),
+            at("2026-10-18 10:30:00")
+        );
+        assert_eq!(parse_since("2d", now).unwrap(), at("2026-10-16 12:00:00"));
+        assert_eq!(
+            parse_since("2026-10-18 09:15:00", now).unwrap(),
+            at("2026-10-18 09:15:00")
+        );
+        assert_eq!(
+            parse_since("2026-10-17", now).unwrap(),
+            at("2026-10-17 00:00:00")
+        );
+        assert!(parse_since("10", now).is_err());
+        assert!(parse_since("10w", now).is_err());
+        assert!(parse_since("yesterday", now).is_err());
+    }
+
+    #[test]
+    fn test_logs() {
+        let dir = std::env::temp_dir();
+        let path = |source: &str| {
+            dir.join(format!("fireup-{}-{}.log", source, std::process::id()))
+                .display()
+                .to_string()
+        };
+        let (vmm, console) = (path("vmm"), path("console"));
+        fs::write(
+            &vmm,
+            "2026-10-18T12:00:00.000000001 [fc:main] Running Firecracker\n\
+             2026-10-18T12:00:02.000000000 [fc:main] Paused\n",
+        )
+        .unwrap();
+        fs::write(
+            &console,
+            "2026-10-18T12:00:01.000000000 [    0.000000] Linux version\r\n\
+             continued\r\nlogin: ",
+        )
+        .unwrap();
+
+        let mut logs = Logs {
+            files: [(Source::Vmm, &vmm), (Source::Console, &console)]
+                .into_iter()
+                .map(|(source, path)| LogFile {
+                    source,
+                    path: path.clone(),
+                    position: 0,
+                    partial: vec![],
+                    last: None,
+                })
+                .collect(),
+        };
+        let lines = select(logs.next_lines().unwrap(), None, None);
+        let texts = lines
+            .iter()
+            .map(|line| (line.source, line.text.split(' ').next_back().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            vec![
+                (Source::Vmm, "Firecracker"),
+                (Source::Console, "version"),
+                (Source::Console, "continued"),
+                (Source::Vmm, "Paused"),
+            ]
+        );
+        assert_eq!(lines[2].timestamp, Some(at("2026-10-18 12:00:01")));
+
+        let since = Some(at("2026-10-18 12:00:01"));
+        assert_eq!(select(lines.clone(), since, None).len(), 3);
+        assert!(select(lines, since, Some(1))[0].text.ends_with("Paused"));
+
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&console)
+            .unwrap()
+            .write_all(b"root\r\n")
+            .unwrap();
+        let lines = logs.next_lines().unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].text, "login: root");
+        assert!(logs.next_lines().unwrap().is_empty());
+
+        fs::remove_file(&vmm).ok();
+        fs::remove_file(&console).ok();
+    }
+}
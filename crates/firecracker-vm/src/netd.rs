@@ -0,0 +1,438 @@
+use std::{
+    fs,
+    net::{SocketAddrV4, UdpSocket},
+    os::fd::{AsRawFd, FromRawFd},
+    path::Path,
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Error, Result};
+use firecracker_state::entity::network::Network;
+use owo_colors::OwoColorize;
+use tokio::task::JoinSet;
+
+use crate::{
+    command::{is_root, run_command},
+    config::get_config_dir,
+    dhcpd, dns, network, radvd,
+};
+
+/// Set when the network services run inside the current process (`fireup serve`).
+static IN_PROCESS: AtomicBool = AtomicBool::new(false);
+
+/// Bridges served by the in-process network services.
+static SERVED: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// Holds the pid of the process serving the networks, followed by a `serve`
+/// line when it is `fireup serve` rather than `fireup netd`.
+fn pid_file() -> Result<String> {
+    let app_dir = get_config_dir().with_context(|| "Failed to get configuration directory")?;
+    Ok(format!("{}/netd.pid", app_dir))
+}
+
+/// Lists the bridges served by the running `fireup netd` or `fireup serve`,
+/// one per line.
+fn bridges_file() -> Result<String> {
+    let app_dir = get_config_dir().with_context(|| "Failed to get configuration directory")?;
+    Ok(format!("{}/netd.bridges", app_dir))
+}
+
+/// Pid of the process serving the networks, and whether it is `fireup serve`.
+fn daemon() -> Option<(String, bool)> {
+    let content = fs::read_to_string(pid_file().ok()?).ok()?;
+    let mut lines = content.lines();
+    let pid = lines.next()?.trim().to_string();
+    let serve = lines.next().map(str::trim) == Some("serve");
+    Path::new(&format!("/proc/{}", pid))
+        .exists()
+        .then_some((pid, serve))
+}
+
+fn daemon_pid() -> Option<String> {
+    daemon().map(|(pid, _)| pid)
+}
+
+/// Writes the pid and bridges files, owned by the user that invoked sudo so
+/// the `fireup netd` they start later can replace them.
+fn publish(serve: bool, bridges: &[String]) -> Result<()> {
+    let pid = match serve {
+        true => format!("{}\nserve\n", std::process::id()),
+        false => std::process::id().to_string(),
+    };
+    let files = [
+        (
+            bridges_file()?,
+            bridges
+                .iter()
+                .map(|bridge| format!("{}\n", bridge))
+                .collect::<String>(),
+        ),
+        (pid_file()?, pid),
+    ];
+    let owner = match (std::env::var("SUDO_UID"), std::env::var("SUDO_GID")) {
+        (Ok(uid), Ok(gid)) if is_root() => Some((uid.parse::<u32>()?, gid.parse::<u32>()?)),
+        _ => None,
+    };
+    for (file, content) in files {
+        fs::write(&file, content).with_context(|| format!("Failed to write {}", file))?;
+        if let Some((uid, gid)) = owner {
+            std::os::unix::fs::chown(&file, Some(uid), Some(gid))
+                .with_context(|| format!("Failed to change the owner of {}", file))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn is_running() -> bool {
+    IN_PROCESS.load(Ordering::SeqCst) || daemon_pid().is_some()
+}
+
+fn is_serving(bridge: &str) -> bool {
+    if IN_PROCESS.load(Ordering::SeqCst) {
+        return SERVED.lock().unwrap().iter().any(|b| b == bridge);
+    }
+    daemon_pid().is_some()
+        && bridges_file()
+            .ok()
+            .and_then(|file| fs::read_to_string(file).ok())
+            .map(|bridges| bridges.lines().any(|b| b == bridge))
+            .unwrap_or(false)
+}
+
+/// Sets a socket option from a plain value.
+pub(crate) fn setsockopt<T>(
+    socket: &impl AsRawFd,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: &T,
+) -> Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            value as *const T as *const libc::c_void,
+            std::mem::size_of::<T>() as libc::socklen_t,
+        )
+    };
+    match ret {
+        0 => Ok(()),
+        _ => Err(Error::new(std::io::Error::last_os_error())),
+    }
+}
+
+/// Restricts `socket` to the traffic of `device`.
+pub(crate) fn bind_to_device(socket: &impl AsRawFd, device: &str) -> Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_BINDTODEVICE,
+            device.as_ptr() as *const libc::c_void,
+            device.len() as libc::socklen_t,
+        )
+    };
+    match ret {
+        0 => Ok(()),
+        _ => Err(Error::new(std::io::Error::last_os_error()))
+            .with_context(|| format!("Failed to bind to device {}", device)),
+    }
+}
+
+/// Binds a UDP socket, optionally restricted to `device`. `IP_FREEBIND` lets
+/// it bind an address the bridge does not carry yet.
+pub(crate) fn bind_udp(addr: SocketAddrV4, device: Option<&str>) -> Result<UdpSocket> {
+    let check = |ret: libc::c_int| match ret {
+        0 => Ok(()),
+        _ => Err(Error::new(std::io::Error::last_os_error())),
+    };
+    let enable: libc::c_int = 1;
+
+    unsafe {
+        let fd = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(Error::new(std::io::Error::last_os_error()));
+        }
+        let socket = UdpSocket::from_raw_fd(fd);
+        let fd = socket.as_raw_fd();
+
+        setsockopt(&socket, libc::SOL_SOCKET, libc::SO_REUSEADDR, &enable)?;
+        setsockopt(&socket, libc::IPPROTO_IP, libc::IP_FREEBIND, &enable)?;
+        if let Some(device) = device {
+            bind_to_device(&socket, device)?;
+        }
+
+        let sockaddr = libc::sockaddr_in {
+            sin_family: libc::AF_INET as libc::sa_family_t,
+            sin_port: addr.port().to_be(),
+            sin_addr: libc::in_addr {
+                s_addr: u32::from(*addr.ip()).to_be(),
+            },
+            sin_zero: [0; 8],
+        };
+        check(libc::bind(
+            fd,
+            &sockaddr as *const libc::sockaddr_in as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+        ))?;
+
+        socket.set_nonblocking(true)?;
+        Ok(socket)
+    }
+}
+
+struct Sockets {
+    dhcp: UdpSocket,
+    dns: UdpSocket,
+    /// Router advertisements, for networks with IPv6 enabled.
+    radvd: Option<UdpSocket>,
+}
+
+/// Binds the DHCP and DNS servers of `network`, its bridge must exist.
+fn bind(network: &Network) -> Result<Sockets> {
+    Ok(Sockets {
+        dhcp: dhcpd::bind(&network.bridge)?,
+        dns: dns::bind(network)?,
+        radvd: match network::ipv6_prefix(network)? {
+            Some(_) => Some(radvd::bind(&network.bridge)?),
+            None => None,
+        },
+    })
+}
+
+/// Entry point of the `fireup netd` daemon: binds the DHCP and DNS servers of
+/// every network whose bridge is up as root, then drops to the user that
+/// invoked sudo so the state database stays theirs.
+pub async fn run() -> Result<()> {
+    let mut sockets = vec![];
+    for network in network::all().await? {
+        if !network::bridge_exists(&network.bridge) {
+            continue;
+        }
+        let bound = bind(&network)?;
+        sockets.push((network, bound));
+    }
+    drop_privileges()?;
+
+    let bridges = sockets
+        .iter()
+        .map(|(network, _)| network.bridge.clone())
+        .collect::<Vec<_>>();
+    publish(false, &bridges)?;
+
+    let mut tasks = JoinSet::new();
+    for (network, sockets) in sockets {
+        tasks.spawn(dhcpd::serve(sockets.dhcp, network.clone()));
+        if let Some(socket) = sockets.radvd {
+            tasks.spawn(radvd::serve(socket, network.clone()));
+        }
+        tasks.spawn(dns::serve(sockets.dns, network));
+    }
+    match tasks.join_next().await {
+        Some(result) => result?,
+        None => Err(anyhow!("No network to serve, no bridge is up")),
+    }
+}
+
+/// Runs the network services on the current tokio runtime, taking over from
+/// a running `fireup netd`. The pid and bridges files are published like the
+/// daemon does, so that other fireup processes do not start one next to it,
+/// and a `SIGHUP` makes it serve the networks whose bridge came up since.
+pub async fn start_in_process() -> Result<()> {
+    match daemon() {
+        Some((pid, true)) => {
+            return Err(anyhow!(
+                "Network services already run in fireup serve (pid {})",
+                pid
+            ));
+        }
+        Some(_) => {
+            println!("[+] Stopping fireup network daemon, serving the networks in process...");
+            stop()?;
+        }
+        None => (),
+    }
+
+    IN_PROCESS.store(true, Ordering::SeqCst);
+    serve_new_networks().await?;
+    publish(true, &SERVED.lock().unwrap())?;
+
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .with_context(|| "Failed to listen for SIGHUP")?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            if let Err(e) = serve_new_networks().await {
+                eprintln!("[✗] Failed to serve the new networks: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Serves the networks whose bridge is up and which are not served yet.
+async fn serve_new_networks() -> Result<()> {
+    for network in network::all().await? {
+        if network::bridge_exists(&network.bridge) && !is_serving(&network.bridge) {
+            serve_in_process(network)?;
+        }
+    }
+    Ok(())
+}
+
+fn serve_in_process(network: Network) -> Result<()> {
+    let sockets = bind(&network)?;
+    let bridges = {
+        let mut served = SERVED.lock().unwrap();
+        served.push(network.bridge.clone());
+        served.clone()
+    };
+    publish(true, &bridges)?;
+    let dhcp_network = network.clone();
+    tokio::spawn(async move {
+        if let Err(e) = dhcpd::serve(sockets.dhcp, dhcp_network).await {
+            eprintln!("[✗] DHCP server stopped: {}", e);
+        }
+    });
+    if let Some(socket) = sockets.radvd {
+        let radvd_network = network.clone();
+        tokio::spawn(async move {
+            if let Err(e) = radvd::serve(socket, radvd_network).await {
+                eprintln!("[✗] Router advertisements stopped: {}", e);
+            }
+        });
+    }
+    let dns_socket = sockets.dns;
+    tokio::spawn(async move {
+        if let Err(e) = dns::serve(dns_socket, network).await {
+            eprintln!("[✗] DNS server stopped: {}", e);
+        }
+    });
+    Ok(())
+}
+
+/// Stops the `fireup netd` daemon, if it is running. Fails when the networks
+/// are served by `fireup serve`, which has to be stopped instead.
+pub fn stop() -> Result<()> {
+    let pid = match daemon() {
+        None => return Ok(()),
+        Some((pid, true)) => {
+            return Err(anyhow!(
+                "The networks are served by fireup serve (pid {}), stop it first",
+                pid
+            ));
+        }
+        Some((pid, false)) => pid,
+    };
+    run_command("kill", &[&pid], true)?;
+    for _ in 0..40 {
+        if daemon_pid().is_none() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(250));
+    }
+    Ok(())
+}
+
+/// Makes sure the DHCP and DNS servers of `network` are up, (re)starting the
+/// `fireup netd` daemon in the background if needed.
+pub fn ensure_running(network: &Network) -> Result<()> {
+    if is_serving(&network.bridge) {
+        return Ok(());
+    }
+    if IN_PROCESS.load(Ordering::SeqCst) {
+        return serve_in_process(network.clone());
+    }
+
+    if let Some((pid, true)) = daemon() {
+        // `fireup serve` binds the networks that came up when it gets SIGHUP
+        run_command("kill", &["-HUP", &pid], true)?;
+        for _ in 0..40 {
+            if is_serving(&network.bridge) {
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(250));
+        }
+        return Err(anyhow!(
+            "fireup serve (pid {}) did not start serving {}",
+            pid,
+            network.bridge
+        ));
+    }
+
+    if daemon_pid().is_some() {
+        // the daemon binds its sockets at startup, restart it so it picks up
+        // the new network
+        println!("[+] Restarting fireup network daemon...");
+        stop()?;
+    }
+
+    let app_dir = get_config_dir().with_context(|| "Failed to get configuration directory")?;
+    let home = dirs::home_dir().ok_or_else(|| anyhow!("Failed to get home directory"))?;
+    let exe = std::env::current_exe().with_context(|| "Failed to get current executable")?;
+    fs::create_dir_all(format!("{}/logs", app_dir))
+        .with_context(|| format!("Failed to create logs directory: {}", app_dir))?;
+    let logfile = format!("{}/logs/netd.log", app_dir);
+
+    println!("[+] Starting fireup network daemon...");
+    let script = format!(
+        "HOME='{}' exec '{}' netd >> '{}' 2>&1",
+        home.display(),
+        exe.display(),
+        logfile
+    );
+    let mut cmd = match is_root() {
+        true => Command::new("sh"),
+        false => {
+            let mut c = Command::new("sudo");
+            c.arg("sh");
+            c
+        }
+    };
+    cmd.args(["-c", &script])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| "Failed to start fireup network daemon")?;
+
+    for _ in 0..40 {
+        if is_serving(&network.bridge) {
+            println!("[✓] Network daemon started.");
+            return Ok(());
+        }
+        std::thread::sleep(Duration::from_millis(250));
+    }
+
+    Err(anyhow!(
+        "Network daemon failed to start, see {}",
+        logfile.cyan()
+    ))
+}
+
+fn drop_privileges() -> Result<()> {
+    if !is_root() {
+        return Ok(());
+    }
+    let (uid, gid) = match (std::env::var("SUDO_UID"), std::env::var("SUDO_GID")) {
+        (Ok(uid), Ok(gid)) => (uid.parse::<u32>()?, gid.parse::<u32>()?),
+        _ => return Ok(()),
+    };
+
+    unsafe {
+        if libc::setgroups(0, std::ptr::null()) != 0
+            || libc::setgid(gid) != 0
+            || libc::setuid(uid) != 0
+        {
+            return Err(anyhow!(
+                "Failed to drop privileges: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+    Ok(())
+}
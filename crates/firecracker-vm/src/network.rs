@@ -1,11 +1,494 @@
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
 use crate::{
-    constants::{BRIDGE_IP, MASK_SHORT},
+    constants::{BRIDGE_DEV, BRIDGE_IP, DEFAULT_CIDR, DEFAULT_NETWORK, DNS_DOMAIN},
     types::VmOptions,
 };
 use anyhow::{anyhow, Context, Result};
+use firecracker_state::{
+    entity::{network::Network, virtual_machine::VirtualMachine},
+    repo,
+};
+use owo_colors::OwoColorize;
+use rand::Rng;
 use serde_json::Value;
 
-use crate::command::run_command;
+use crate::{command::run_command, firewall};
+
+/// IPv4 subnet in CIDR notation, e.g. `172.16.0.0/16`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cidr {
+    pub addr: Ipv4Addr,
+    pub prefix: u8,
+}
+
+impl FromStr for Cidr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (addr, prefix) = s
+            .split_once('/')
+            .ok_or_else(|| anyhow!("Invalid CIDR '{}', expected <address>/<prefix>", s))?;
+        let addr = addr
+            .parse::<Ipv4Addr>()
+            .with_context(|| format!("Invalid address in CIDR '{}'", s))?;
+        let prefix = prefix
+            .parse::<u8>()
+            .ok()
+            .filter(|prefix| (8..=30).contains(prefix))
+            .ok_or_else(|| anyhow!("Invalid prefix in CIDR '{}', expected 8 to 30", s))?;
+        Ok(Cidr { addr, prefix })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network(), self.prefix)
+    }
+}
+
+impl Cidr {
+    pub fn netmask(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::MAX << (32 - self.prefix))
+    }
+
+    pub fn network(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.addr) & u32::from(self.netmask()))
+    }
+
+    pub fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.network()) | !u32::from(self.netmask()))
+    }
+
+    pub fn first_host(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.network()) + 1)
+    }
+
+    pub fn last_host(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.broadcast()) - 1)
+    }
+
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        u32::from(ip) & u32::from(self.netmask()) == u32::from(self.network())
+    }
+
+    pub fn overlaps(&self, other: &Cidr) -> bool {
+        self.contains(other.network()) || other.contains(self.network())
+    }
+}
+
+/// The network VMs are attached to when none is specified, it maps to the
+/// historical `br0` bridge.
+pub fn default_network() -> Network {
+    Network {
+        name: DEFAULT_NETWORK.into(),
+        bridge: BRIDGE_DEV.into(),
+        cidr: DEFAULT_CIDR.into(),
+        gateway: BRIDGE_IP.into(),
+        domain: DNS_DOMAIN.into(),
+        nat: true,
+        ..Default::default()
+    }
+}
+
+pub fn cidr(network: &Network) -> Result<Cidr> {
+    network.cidr.parse()
+}
+
+/// IPv6 prefix of `network`, `None` when the network is IPv4 only.
+pub fn ipv6_prefix(network: &Network) -> Result<Option<Ipv6Addr>> {
+    network
+        .ipv6_prefix
+        .as_deref()
+        .map(parse_ipv6_prefix)
+        .transpose()
+}
+
+/// Parses an IPv6 prefix, only /64 prefixes are supported since guests
+/// configure their address through SLAAC.
+fn parse_ipv6_prefix(prefix: &str) -> Result<Ipv6Addr> {
+    let (addr, len) = prefix.split_once('/').unwrap_or((prefix, "64"));
+    if len != "64" {
+        return Err(anyhow!(
+            "Invalid IPv6 prefix '{}', only /64 prefixes are supported",
+            prefix
+        ));
+    }
+    let segments = addr
+        .parse::<Ipv6Addr>()
+        .with_context(|| format!("Invalid IPv6 prefix '{}'", prefix))?
+        .segments();
+    Ok(Ipv6Addr::new(
+        segments[0],
+        segments[1],
+        segments[2],
+        segments[3],
+        0,
+        0,
+        0,
+        0,
+    ))
+}
+
+/// Random unique local /64 prefix, see RFC 4193.
+fn ula_prefix() -> Ipv6Addr {
+    let mut rng = rand::thread_rng();
+    Ipv6Addr::new(
+        0xfd00 | rng.gen::<u8>() as u16,
+        rng.gen(),
+        rng.gen(),
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+/// Address of the bridge in `prefix`.
+pub fn ipv6_gateway(prefix: Ipv6Addr) -> Ipv6Addr {
+    let mut segments = prefix.segments();
+    segments[7] = 1;
+    Ipv6Addr::from(segments)
+}
+
+/// Address a guest with `mac_address` picks in `prefix` through SLAAC
+/// (modified EUI-64 interface identifier).
+pub fn eui64(prefix: Ipv6Addr, mac_address: &str) -> Result<Ipv6Addr> {
+    let mac = mac_address
+        .split(':')
+        .map(|byte| u8::from_str_radix(byte, 16))
+        .collect::<Result<Vec<_>, _>>()
+        .ok()
+        .filter(|mac| mac.len() == 6)
+        .ok_or_else(|| anyhow!("Invalid MAC address: {}", mac_address))?;
+    let mut octets = prefix.octets();
+    octets[8..].copy_from_slice(&[
+        mac[0] ^ 0x02,
+        mac[1],
+        mac[2],
+        0xff,
+        0xfe,
+        mac[3],
+        mac[4],
+        mac[5],
+    ]);
+    Ok(Ipv6Addr::from(octets))
+}
+
+async fn ensure_default(pool: &sqlx::Pool<sqlx::Sqlite>) -> Result<()> {
+    if repo::network::find(pool, DEFAULT_NETWORK).await?.is_none() {
+        repo::network::create(pool, default_network()).await?;
+    }
+    Ok(())
+}
+
+pub async fn all() -> Result<Vec<Network>> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    ensure_default(&pool).await?;
+    repo::network::all(&pool).await
+}
+
+pub async fn get(name: &str) -> Result<Network> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    ensure_default(&pool).await?;
+    repo::network::find(&pool, name)
+        .await?
+        .ok_or_else(|| anyhow!("No network found with name or id '{}'", name))
+}
+
+/// Resolves the network of a VM: the one it names, else the one owning its
+/// bridge, else the default network.
+pub async fn for_vm(options: &VmOptions) -> Result<Network> {
+    if let Some(name) = &options.network {
+        return get(name).await;
+    }
+    let pool = firecracker_state::create_connection_pool().await?;
+    ensure_default(&pool).await?;
+    match repo::network::find_by_bridge(&pool, &options.bridge).await? {
+        Some(network) => Ok(network),
+        None => get(DEFAULT_NETWORK).await,
+    }
+}
+
+/// VMs attached to the network `name` by any of their interfaces, whatever
+/// their status.
+pub async fn vms(name: &str) -> Result<Vec<VirtualMachine>> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let interfaces = repo::vm_interface::all(&pool).await?;
+    Ok(repo::virtual_machine::all(&pool)
+        .await?
+        .into_iter()
+        .filter(|vm| {
+            vm.network.as_deref().unwrap_or(DEFAULT_NETWORK) == name
+                || interfaces
+                    .iter()
+                    .any(|interface| interface.vm_id == vm.id && interface.network == name)
+        })
+        .collect())
+}
+
+/// Fully qualified DNS name of a VM, `<name>.<network domain>`.
+pub async fn fqdn(vm_name: &str) -> Result<String> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let network = match repo::virtual_machine::find(&pool, vm_name).await? {
+        Some(vm) => repo::network::find(&pool, vm.network.as_deref().unwrap_or(DEFAULT_NETWORK))
+            .await?
+            .unwrap_or_else(default_network),
+        None => default_network(),
+    };
+    Ok(format!("{}.{}", vm_name, network.domain))
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NetworkOptions {
+    pub cidr: Option<String>,
+    pub bridge: Option<String>,
+    pub gateway: Option<String>,
+    pub domain: Option<String>,
+    pub nat: bool,
+    /// Enables IPv6 with a random unique local prefix unless `ipv6_prefix` is set.
+    pub ipv6: bool,
+    pub ipv6_prefix: Option<String>,
+}
+
+pub async fn create(name: &str, options: NetworkOptions) -> Result<Network> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(anyhow!(
+            "Invalid network name '{}', only letters, digits, '-' and '_' are allowed",
+            name
+        ));
+    }
+
+    let pool = firecracker_state::create_connection_pool().await?;
+    ensure_default(&pool).await?;
+    let networks = repo::network::all(&pool).await?;
+    if networks.iter().any(|network| network.name == name) {
+        return Err(anyhow!("Network '{}' already exists", name));
+    }
+
+    let taken = networks.iter().map(cidr).collect::<Result<Vec<_>>>()?;
+    let cidr = match &options.cidr {
+        Some(cidr) => {
+            let cidr = cidr.parse::<Cidr>()?;
+            if let Some(network) = networks
+                .iter()
+                .zip(&taken)
+                .find(|(_, other)| other.overlaps(&cidr))
+                .map(|(network, _)| network)
+            {
+                return Err(anyhow!(
+                    "Subnet {} overlaps with network '{}' ({})",
+                    cidr,
+                    network.name,
+                    network.cidr
+                ));
+            }
+            cidr
+        }
+        None => free_cidr(&taken)?,
+    };
+
+    let gateway = match &options.gateway {
+        Some(gateway) => {
+            let gateway = gateway
+                .parse::<Ipv4Addr>()
+                .with_context(|| format!("Invalid gateway address: {}", gateway))?;
+            if !cidr.contains(gateway) || gateway == cidr.network() || gateway == cidr.broadcast() {
+                return Err(anyhow!("Gateway {} is not a host of {}", gateway, cidr));
+            }
+            gateway
+        }
+        None => cidr.first_host(),
+    };
+
+    let bridge = options.bridge.clone().unwrap_or_else(|| {
+        let bridge = format!("fc-{}", name);
+        bridge.chars().take(15).collect()
+    });
+    if bridge.len() > 15
+        || !bridge
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(anyhow!(
+            "Invalid bridge name '{}', at most 15 letters, digits, '-' or '_'",
+            bridge
+        ));
+    }
+    if networks.iter().any(|network| network.bridge == bridge) {
+        return Err(anyhow!(
+            "Bridge '{}' is already used by another network",
+            bridge
+        ));
+    }
+
+    let domain = options
+        .domain
+        .clone()
+        .unwrap_or_else(|| format!("{}.{}", name.to_lowercase(), DNS_DOMAIN));
+    let domain = domain.trim_matches('.').to_lowercase();
+    if domain.is_empty()
+        || !domain.split('.').all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+    {
+        return Err(anyhow!("Invalid DNS domain '{}'", domain));
+    }
+    if networks.iter().any(|network| network.domain == domain) {
+        return Err(anyhow!(
+            "Domain '{}' is already used by another network",
+            domain
+        ));
+    }
+
+    let prefix = match &options.ipv6_prefix {
+        Some(prefix) => Some(parse_ipv6_prefix(prefix)?),
+        None if options.ipv6 => Some(ula_prefix()),
+        None => None,
+    };
+    if let Some(prefix) = prefix {
+        if let Some(network) = networks
+            .iter()
+            .find(|network| ipv6_prefix(network).ok().flatten() == Some(prefix))
+        {
+            return Err(anyhow!(
+                "IPv6 prefix {}/64 is already used by network '{}'",
+                prefix,
+                network.name
+            ));
+        }
+    }
+
+    let id = repo::network::create(
+        &pool,
+        Network {
+            name: name.into(),
+            bridge,
+            cidr: cidr.to_string(),
+            gateway: gateway.to_string(),
+            domain,
+            nat: options.nat,
+            ipv6_prefix: prefix.map(|prefix| format!("{}/64", prefix)),
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    repo::network::find(&pool, &id)
+        .await?
+        .ok_or_else(|| anyhow!("Failed to retrieve the created network"))
+}
+
+/// Picks the first /16 of 172.17.0.0 - 172.31.0.0 neither used by another
+/// network nor routed on the host.
+fn free_cidr(taken: &[Cidr]) -> Result<Cidr> {
+    let output = run_command("ip", &["-j", "route", "list"], false)?;
+    let routes: Value = serde_json::from_slice(&output.stdout).unwrap_or(Value::Null);
+    let routed = routes
+        .as_array()
+        .map(|routes| {
+            routes
+                .iter()
+                .filter_map(|route| route["dst"].as_str())
+                .filter_map(|dst| match dst.contains('/') {
+                    true => dst.parse::<Cidr>().ok(),
+                    false => format!("{}/32", dst).parse::<Cidr>().ok(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    (17..=31)
+        .map(|octet| Cidr {
+            addr: Ipv4Addr::new(172, octet, 0, 0),
+            prefix: 16,
+        })
+        .find(|cidr| {
+            !taken
+                .iter()
+                .chain(&routed)
+                .any(|other| other.overlaps(cidr))
+        })
+        .ok_or_else(|| anyhow!("No free subnet left, pass one with --cidr"))
+}
+
+pub async fn remove(name: &str) -> Result<Network> {
+    let network = get(name).await?;
+    if network.name == DEFAULT_NETWORK {
+        return Err(anyhow!("The default network cannot be removed"));
+    }
+
+    let vms = vms(&network.name).await?;
+    if !vms.is_empty() {
+        return Err(anyhow!(
+            "Network '{}' is used by {}, remove them first",
+            network.name,
+            vms.iter()
+                .map(|vm| vm.name.clone())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    if bridge_exists(&network.bridge) {
+        println!("[+] Removing bridge {}...", network.bridge.cyan());
+        run_command("ip", &["link", "del", &network.bridge], true)?;
+    }
+    let pool = firecracker_state::create_connection_pool().await?;
+    repo::network::delete(&pool, &network.id).await?;
+    firewall::sync().await?;
+    Ok(network)
+}
+
+/// Removes everything fireup set up on the host for its networks: the
+/// firewall rules, the bridges and the network daemon. The networks stay
+/// recorded and are set up again by the next MicroVM started on them.
+pub async fn prune() -> Result<Vec<Network>> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let running = repo::virtual_machine::all(&pool)
+        .await?
+        .into_iter()
+        .filter(|vm| vm.status == "RUNNING" || vm.status == "PAUSED")
+        .map(|vm| vm.name)
+        .collect::<Vec<_>>();
+    if !running.is_empty() {
+        return Err(anyhow!(
+            "MicroVMs are still running: {}, stop them first",
+            running.join(", ")
+        ));
+    }
+
+    firewall::prune()?;
+    crate::netd::stop()?;
+
+    let mut pruned = vec![];
+    for network in all().await? {
+        if bridge_exists(&network.bridge) {
+            println!("[+] Removing bridge {}...", network.bridge.cyan());
+            run_command("ip", &["link", "del", &network.bridge], true)?;
+            pruned.push(network);
+        }
+    }
+    Ok(pruned)
+}
+
+/// Interface attaching a MicroVM to its bridge: the tap itself, or the veth
+/// leading to the jail network namespace.
+pub fn host_port(tap: &str, jailed: bool) -> String {
+    match jailed {
+        true => format!("{}-jail", tap),
+        false => tap.to_string(),
+    }
+}
 
 fn check_tap_exists(config: &VmOptions) -> bool {
     run_command("ip", &["link", "show", &config.tap], false)
@@ -13,8 +496,8 @@ fn check_tap_exists(config: &VmOptions) -> bool {
         .unwrap_or(false)
 }
 
-fn check_bridge_exists(config: &VmOptions) -> bool {
-    run_command("ip", &["link", "show", &config.bridge], false)
+pub fn bridge_exists(bridge: &str) -> bool {
+    run_command("ip", &["link", "show", bridge], false)
         .map(|output| output.status.success())
         .unwrap_or(false)
 }
@@ -34,92 +517,262 @@ fn create_new_tap(config: &VmOptions) -> Result<()> {
     Ok(())
 }
 
-pub fn setup_network(config: &VmOptions) -> Result<()> {
+pub async fn setup_network(config: &VmOptions, network: &Network) -> Result<()> {
     if check_tap_exists(config) {
         run_command("ip", &["addr", "flush", "dev", &config.tap], true)?;
     }
 
-    if check_tap_exists(config) && check_bridge_exists(config) {
+    if check_tap_exists(config) && bridge_exists(&config.bridge) {
+        // the tap may be left over from a VM that was on another network
+        run_command(
+            "ip",
+            &["link", "set", &config.tap, "master", &config.bridge],
+            true,
+        )?;
         println!("[✓] Network already configured. Skipping setup.");
         return Ok(());
     }
 
-    if !check_bridge_exists(config) {
-        println!("[+] Configuring {}...", config.bridge);
+    if !bridge_exists(&config.bridge) {
+        create_bridge(network)?;
+    }
+
+    if !check_tap_exists(config) {
+        println!("[+] Configuring {}...", &config.tap);
+        create_new_tap(config)?;
+    }
+
+    setup_forwarding(network)
+}
+
+fn create_bridge(network: &Network) -> Result<()> {
+    let cidr = cidr(network)?;
+    println!("[+] Configuring {}...", network.bridge);
+    run_command(
+        "ip",
+        &["link", "add", "name", &network.bridge, "type", "bridge"],
+        true,
+    )?;
+    run_command("ip", &["link", "set", &network.bridge, "up"], true)?;
+    run_command(
+        "ip",
+        &[
+            "addr",
+            "add",
+            &format!("{}/{}", network.gateway, cidr.prefix),
+            "dev",
+            &network.bridge,
+        ],
+        true,
+    )?;
+    if let Some(prefix) = ipv6_prefix(network)? {
         run_command(
-            "ip",
-            &["link", "add", "name", &config.bridge, "type", "bridge"],
+            "sysctl",
+            &[
+                "-w",
+                &format!("net.ipv6.conf.{}.disable_ipv6=0", network.bridge),
+            ],
             true,
         )?;
-        run_command("ip", &["link", "set", &config.bridge, "up"], true)?;
         run_command(
             "ip",
             &[
+                "-6",
                 "addr",
                 "add",
-                &format!("{}{}", BRIDGE_IP, MASK_SHORT),
+                &format!("{}/64", ipv6_gateway(prefix)),
                 "dev",
-                &config.bridge,
+                &network.bridge,
+                "nodad",
             ],
             true,
         )?;
     }
+    Ok(())
+}
 
-    if !check_tap_exists(config) {
-        println!("[+] Configuring {}...", &config.tap);
-        create_new_tap(config)?;
-    }
+pub(crate) fn host_interface() -> Result<String> {
+    default_route_interface(&["-j", "route", "list", "default"])
+}
 
+pub(crate) fn host_interface6() -> Result<String> {
+    default_route_interface(&["-j", "-6", "route", "list", "default"])
+}
+
+fn default_route_interface(args: &[&str]) -> Result<String> {
+    let output = run_command("ip", args, false)?;
+    let json: Value =
+        serde_json::from_slice(&output.stdout).with_context(|| "Failed to parse route JSON")?;
+    json[0]["dev"]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| anyhow!("Failed to get host interface"))
+}
+
+fn setup_forwarding(network: &Network) -> Result<()> {
     let ip_forward = run_command("cat", &["/proc/sys/net/ipv4/ip_forward"], false)?.stdout;
     if String::from_utf8_lossy(&ip_forward).trim() != "1" {
         println!("[+] Enabling IP forwarding...");
         run_command("sysctl", &["-w", "net.ipv4.ip_forward=1"], true)?;
     }
 
-    let output = run_command("ip", &["-j", "route", "list", "default"], false)?;
-    let json: Value =
-        serde_json::from_slice(&output.stdout).with_context(|| "Failed to parse route JSON")?;
-    let host_iface = json[0]["dev"]
-        .as_str()
-        .ok_or_else(|| anyhow!("Failed to get host interface"))?;
+    if network.nat {
+        println!(
+            "[+] Setting up NAT for {} on {}...",
+            network.cidr,
+            host_interface()?
+        );
+    }
+
+    if ipv6_prefix(network)?.is_some() {
+        setup_ipv6_forwarding(network)?;
+    }
+
+    Ok(())
+}
 
-    println!("[+] Setting up NAT on {}...", host_iface);
+/// Forwards the IPv6 traffic of `network`, masqueraded behind the host (NAT66)
+/// when NAT is enabled, routed as is otherwise.
+fn setup_ipv6_forwarding(network: &Network) -> Result<()> {
+    let host_iface = host_interface6();
+    let forwarding = run_command("cat", &["/proc/sys/net/ipv6/conf/all/forwarding"], false)?.stdout;
+    if String::from_utf8_lossy(&forwarding).trim() != "1" {
+        // a forwarding host ignores router advertisements unless accept_ra
+        // is 2, keep the host IPv6 connectivity
+        if let Ok(host_iface) = &host_iface {
+            run_command(
+                "sysctl",
+                &["-w", &format!("net.ipv6.conf.{}.accept_ra=2", host_iface)],
+                true,
+            )?;
+        }
+        println!("[+] Enabling IPv6 forwarding...");
+        run_command("sysctl", &["-w", "net.ipv6.conf.all.forwarding=1"], true)?;
+    }
 
-    let rule_exists = run_command(
-        "iptables",
+    let prefix = network.ipv6_prefix.clone().unwrap_or_default();
+    match (network.nat, host_iface) {
+        (true, Ok(host_iface)) => {
+            println!("[+] Setting up NAT66 for {} on {}...", prefix, host_iface)
+        }
+        (true, Err(_)) => println!(
+            "[!] The host has no IPv6 default route, {} is only reachable from the host.",
+            prefix.cyan()
+        ),
+        (false, _) => println!(
+            "[!] NAT is disabled, route {} to this host to reach the MicroVMs over IPv6.",
+            prefix.cyan()
+        ),
+    }
+    Ok(())
+}
+
+/// Sets up the network of a jailed MicroVM: the tap lives in the jail network
+/// namespace, bridged to the host bridge through a veth pair.
+pub async fn setup_jailed_network(
+    config: &VmOptions,
+    network: &Network,
+    netns: &str,
+    uid: u32,
+) -> Result<()> {
+    if !bridge_exists(&config.bridge) {
+        create_bridge(network)?;
+    }
+
+    let veth = host_port(&config.tap, true);
+    let owner = uid.to_string();
+    println!("[+] Configuring {} in {}...", &config.tap, netns);
+
+    // a stale pair would be left over if the namespace was not cleaned up
+    run_command("ip", &["link", "del", &veth], true).ok();
+    run_command(
+        "ip",
         &[
-            "-t",
-            "nat",
-            "-C",
-            "POSTROUTING",
-            "-o",
-            host_iface,
-            "-j",
-            "MASQUERADE",
+            "link", "add", &veth, "type", "veth", "peer", "name", "veth0", "netns", netns,
         ],
         true,
-    )
-    .map(|output| output.status.success())
-    .unwrap_or(false);
+    )?;
+    run_command(
+        "ip",
+        &["link", "set", &veth, "master", &config.bridge],
+        true,
+    )?;
+    run_command("ip", &["link", "set", &veth, "up"], true)?;
 
-    if !rule_exists {
-        run_command(
-            "iptables",
-            &[
-                "-t",
-                "nat",
-                "-A",
-                "POSTROUTING",
-                "-o",
-                host_iface,
-                "-j",
-                "MASQUERADE",
-            ],
-            true,
-        )?;
+    let commands: [&[&str]; 7] = [
+        &["link", "add", "name", "br0", "type", "bridge"],
+        &[
+            "tuntap",
+            "add",
+            "dev",
+            &config.tap,
+            "mode",
+            "tap",
+            "user",
+            &owner,
+            "group",
+            &owner,
+        ],
+        &["link", "set", "veth0", "master", "br0"],
+        &["link", "set", &config.tap, "master", "br0"],
+        &["link", "set", "veth0", "up"],
+        &["link", "set", &config.tap, "up"],
+        &["link", "set", "br0", "up"],
+    ];
+    for args in commands {
+        let args = [&["netns", "exec", netns, "ip"], args].concat();
+        run_command("ip", &args, true)?;
+    }
+
+    setup_forwarding(network)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cidr() {
+        let cidr: Cidr = "172.16.3.4/16".parse().unwrap();
+        assert_eq!(cidr.to_string(), "172.16.0.0/16");
+        assert_eq!(cidr.netmask(), Ipv4Addr::new(255, 255, 0, 0));
+        assert_eq!(cidr.first_host(), Ipv4Addr::new(172, 16, 0, 1));
+        assert_eq!(cidr.last_host(), Ipv4Addr::new(172, 16, 255, 254));
+        assert!(cidr.contains(Ipv4Addr::new(172, 16, 200, 1)));
+        assert!(!cidr.contains(Ipv4Addr::new(172, 17, 0, 1)));
     }
 
-    run_command("iptables", &["-P", "FORWARD", "ACCEPT"], true)?;
+    #[test]
+    fn test_cidr_overlaps() {
+        let a: Cidr = "10.0.0.0/16".parse().unwrap();
+        let b: Cidr = "10.0.42.0/24".parse().unwrap();
+        let c: Cidr = "10.1.0.0/24".parse().unwrap();
+        assert!(a.overlaps(&b));
+        assert!(b.overlaps(&a));
+        assert!(!a.overlaps(&c));
+    }
 
-    Ok(())
+    #[test]
+    fn test_cidr_rejects_invalid() {
+        assert!("10.0.0.0".parse::<Cidr>().is_err());
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("10.0.0/24".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_ipv6_prefix() {
+        let prefix = parse_ipv6_prefix("fd12:3456:789a:1::42/64").unwrap();
+        assert_eq!(prefix.to_string(), "fd12:3456:789a:1::");
+        assert_eq!(ipv6_gateway(prefix).to_string(), "fd12:3456:789a:1::1");
+        assert!(parse_ipv6_prefix("fd12:3456:789a::/48").is_err());
+        assert_eq!(ula_prefix().segments()[0] & 0xff00, 0xfd00);
+    }
+
+    #[test]
+    fn test_eui64() {
+        let prefix = parse_ipv6_prefix("fd12:3456:789a::/64").unwrap();
+        let addr = eui64(prefix, "06:00:AC:10:00:02").unwrap();
+        assert_eq!(addr.to_string(), "fd12:3456:789a:0:400:acff:fe10:2");
+        assert!(eui64(prefix, "06:00:AC").is_err());
+    }
 }
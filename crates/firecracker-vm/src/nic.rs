@@ -0,0 +1,151 @@
+use anyhow::{anyhow, Result};
+use fire_config::NicOptions;
+use firecracker_state::entity::{network::Network, vm_interface::VmInterface};
+
+use crate::{constants::DEFAULT_NETWORK, mac::generate_unique_mac, network, types::VmOptions};
+
+/// Longest interface name the kernel accepts.
+const IFNAMSIZ: usize = 15;
+
+/// Network interface of a MicroVM being started.
+#[derive(Debug, Clone)]
+pub struct Nic {
+    pub iface_id: String,
+    pub network: Network,
+    pub tap: String,
+    pub mac_address: String,
+}
+
+impl Nic {
+    /// Options to set up the tap of the interface with
+    /// [`network::setup_network`].
+    pub fn options(&self, options: &VmOptions) -> VmOptions {
+        VmOptions {
+            bridge: self.network.bridge.clone(),
+            tap: self.tap.clone(),
+            mac_address: self.mac_address.clone(),
+            network: Some(self.network.name.clone()),
+            ..options.clone()
+        }
+    }
+}
+
+/// Applies the first of the `nics` of a VM to its own `network` and
+/// `mac_address`, which describe `eth0`.
+pub fn with_primary(options: &VmOptions) -> VmOptions {
+    let Some(primary) = options.nics.as_ref().and_then(|nics| nics.first()) else {
+        return options.clone();
+    };
+    VmOptions {
+        network: primary.network.clone().or(options.network.clone()),
+        mac_address: primary.mac.clone().unwrap_or(options.mac_address.clone()),
+        ..options.clone()
+    }
+}
+
+/// Interfaces of a VM recorded in the state database, to start it again with
+/// the same MAC addresses. `None` when it only has `eth0`.
+pub fn from_interfaces(interfaces: &[VmInterface]) -> Option<Vec<NicOptions>> {
+    (interfaces.len() > 1).then(|| {
+        interfaces
+            .iter()
+            .map(|interface| NicOptions {
+                network: Some(interface.network.clone()),
+                mac: Some(interface.mac_address.clone()),
+            })
+            .collect()
+    })
+}
+
+/// Tap of the extra interface `index`, next to the tap of `eth0`.
+pub fn tap(primary: &str, index: usize) -> String {
+    format!("{}-{}", primary, index)
+}
+
+/// Resolves the network interfaces of a VM, `eth0` on `network` first. Extra
+/// interfaces without a MAC address keep the one they had in `existing`.
+pub async fn resolve(
+    options: &VmOptions,
+    network: &Network,
+    existing: &[VmInterface],
+) -> Result<Vec<Nic>> {
+    let mut nics = vec![Nic {
+        iface_id: "eth0".into(),
+        network: network.clone(),
+        tap: options.tap.clone(),
+        mac_address: options.mac_address.clone(),
+    }];
+
+    for (index, nic) in options.nics.iter().flatten().enumerate().skip(1) {
+        let iface_id = format!("eth{}", index);
+        let tap = tap(&options.tap, index);
+        if tap.len() > IFNAMSIZ {
+            return Err(anyhow!(
+                "Tap name {} of {} is longer than {} characters, use a shorter tap name",
+                tap,
+                iface_id,
+                IFNAMSIZ
+            ));
+        }
+        let network = network::get(nic.network.as_deref().unwrap_or(DEFAULT_NETWORK)).await?;
+        let mac_address = nic
+            .mac
+            .clone()
+            .or_else(|| {
+                existing
+                    .iter()
+                    .find(|interface| interface.iface_id == iface_id)
+                    .map(|interface| interface.mac_address.clone())
+            })
+            .unwrap_or_else(generate_unique_mac);
+        nics.push(Nic {
+            iface_id,
+            network,
+            tap,
+            mac_address,
+        });
+    }
+
+    for (i, nic) in nics.iter().enumerate() {
+        if let Some(other) = nics[..i]
+            .iter()
+            .find(|other| other.mac_address.eq_ignore_ascii_case(&nic.mac_address))
+        {
+            return Err(anyhow!(
+                "{} and {} have the same MAC address {}",
+                other.iface_id,
+                nic.iface_id,
+                nic.mac_address
+            ));
+        }
+    }
+    Ok(nics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_primary() {
+        let options = VmOptions {
+            network: Some("default".into()),
+            mac_address: "06:00:AC:10:00:02".into(),
+            nics: Some(vec![
+                NicOptions {
+                    network: Some("lan".into()),
+                    mac: None,
+                },
+                NicOptions {
+                    network: Some("wan".into()),
+                    mac: None,
+                },
+            ]),
+            ..Default::default()
+        };
+        let options = with_primary(&options);
+        assert_eq!(options.network.as_deref(), Some("lan"));
+        assert_eq!(options.mac_address, "06:00:AC:10:00:02");
+        assert_eq!(tap("tap0", 1), "tap0-1");
+    }
+}
@@ -0,0 +1,200 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Context, Result};
+use firecracker_state::repo;
+use owo_colors::OwoColorize;
+
+use crate::{command::run_command, firewall};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Protocol::Tcp => write!(f, "tcp"),
+            Protocol::Udp => write!(f, "udp"),
+        }
+    }
+}
+
+/// Host port forwarded to a guest port, written `8080:80` or `5353:53/udp`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PortMapping {
+    pub host_port: u16,
+    pub guest_port: u16,
+    pub protocol: Protocol,
+}
+
+impl FromStr for PortMapping {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (ports, protocol) = match s.trim().split_once('/') {
+            Some((ports, "tcp")) => (ports, Protocol::Tcp),
+            Some((ports, "udp")) => (ports, Protocol::Udp),
+            Some((_, protocol)) => {
+                return Err(anyhow!(
+                    "Invalid protocol '{}' in port mapping '{}', expected tcp or udp",
+                    protocol,
+                    s
+                ))
+            }
+            None => (s.trim(), Protocol::Tcp),
+        };
+        let port = |port: &str| {
+            port.parse::<u16>()
+                .ok()
+                .filter(|port| *port > 0)
+                .ok_or_else(|| anyhow!("Invalid port '{}' in port mapping '{}'", port, s))
+        };
+        let (host_port, guest_port) = match ports.split_once(':') {
+            Some((host, guest)) => (port(host)?, port(guest)?),
+            None => (port(ports)?, port(ports)?),
+        };
+        Ok(PortMapping {
+            host_port,
+            guest_port,
+            protocol,
+        })
+    }
+}
+
+impl fmt::Display for PortMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}/{}",
+            self.host_port, self.guest_port, self.protocol
+        )
+    }
+}
+
+/// Parses the `ports` of a VM, a host port can only be forwarded once per protocol.
+pub fn parse(ports: &[String]) -> Result<Vec<PortMapping>> {
+    let mappings = ports
+        .iter()
+        .map(|port| port.parse::<PortMapping>())
+        .collect::<Result<Vec<_>>>()?;
+    for (i, mapping) in mappings.iter().enumerate() {
+        if mappings[..i]
+            .iter()
+            .any(|other| other.host_port == mapping.host_port && other.protocol == mapping.protocol)
+        {
+            return Err(anyhow!(
+                "Host port {}/{} is forwarded more than once",
+                mapping.host_port,
+                mapping.protocol
+            ));
+        }
+    }
+    Ok(mappings)
+}
+
+/// Serializes port mappings separated by commas.
+pub fn to_column(mappings: &[PortMapping]) -> String {
+    mappings
+        .iter()
+        .map(|m| m.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn from_column(column: &str) -> Vec<PortMapping> {
+    column
+        .split(',')
+        .filter_map(|entry| entry.parse::<PortMapping>().ok())
+        .collect()
+}
+
+/// Fails if a host port is already forwarded to another running MicroVM.
+pub async fn check_available(name: &str, mappings: &[PortMapping]) -> Result<()> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    for vm in repo::virtual_machine::all(&pool)
+        .await?
+        .into_iter()
+        .filter(|vm| vm.name != name && (vm.status == "RUNNING" || vm.status == "PAUSED"))
+    {
+        let taken = vm.ports.as_deref().map(from_column).unwrap_or_default();
+        if let Some(mapping) = mappings.iter().find(|mapping| {
+            taken.iter().any(|other| {
+                other.host_port == mapping.host_port && other.protocol == mapping.protocol
+            })
+        }) {
+            return Err(anyhow!(
+                "Host port {}/{} is already forwarded to {}",
+                mapping.host_port,
+                mapping.protocol,
+                vm.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Forwards the host ports of a MicroVM that is already recorded as running
+/// in the state database.
+pub async fn publish(bridge: &str, guest_ip: &str, mappings: &[PortMapping]) -> Result<()> {
+    if mappings.is_empty() {
+        return Ok(());
+    }
+
+    // lets connections to localhost be routed out of the bridge
+    run_command(
+        "sysctl",
+        &["-w", &format!("net.ipv4.conf.{}.route_localnet=1", bridge)],
+        true,
+    )
+    .with_context(|| format!("Failed to enable route_localnet on {}", bridge))?;
+
+    for mapping in mappings {
+        println!(
+            "[+] Forwarding host port {} to {}:{}...",
+            format!("{}/{}", mapping.host_port, mapping.protocol).cyan(),
+            guest_ip,
+            mapping.guest_port
+        );
+    }
+    firewall::sync().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_port_mapping() {
+        let mapping = "8080:80".parse::<PortMapping>().unwrap();
+        assert_eq!(mapping.host_port, 8080);
+        assert_eq!(mapping.guest_port, 80);
+        assert_eq!(mapping.protocol, Protocol::Tcp);
+
+        let mapping = "5353:53/udp".parse::<PortMapping>().unwrap();
+        assert_eq!(mapping.protocol, Protocol::Udp);
+        assert_eq!(mapping.to_string(), "5353:53/udp");
+
+        let mapping = "5432".parse::<PortMapping>().unwrap();
+        assert_eq!((mapping.host_port, mapping.guest_port), (5432, 5432));
+
+        assert!("8080:80/sctp".parse::<PortMapping>().is_err());
+        assert!("0:80".parse::<PortMapping>().is_err());
+        assert!("http:80".parse::<PortMapping>().is_err());
+    }
+
+    #[test]
+    fn test_ports_column_roundtrip() {
+        let mappings = parse(&["8080:80".into(), "5432:5432/tcp".into()]).unwrap();
+        let column = to_column(&mappings);
+        assert_eq!(column, "8080:80/tcp,5432:5432/tcp");
+        assert_eq!(from_column(&column), mappings);
+    }
+
+    #[test]
+    fn test_duplicate_host_port() {
+        assert!(parse(&["8080:80".into(), "8080:8080".into()]).is_err());
+        assert!(parse(&["53:53/tcp".into(), "53:53/udp".into()]).is_ok());
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use fire_config::ProvisionOptions;
+use firecracker_state::repo;
+use owo_colors::OwoColorize;
+use sha2::{Digest, Sha256};
+
+use crate::{guest::Guest, types::VmOptions};
+
+fn hash_path(hasher: &mut Sha256, path: &Path) -> Result<()> {
+    if path.is_dir() {
+        let mut entries = fs::read_dir(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort();
+        for entry in entries {
+            hasher.update(entry.display().to_string().as_bytes());
+            hash_path(hasher, &entry)?;
+        }
+        return Ok(());
+    }
+    let content = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    hasher.update(&content);
+    Ok(())
+}
+
+/// Hash of the provisioning, covering the content of its scripts and files,
+/// whose relative paths are resolved against `dir`.
+pub fn hash(provision: &ProvisionOptions, dir: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    for file in provision.files.iter().flatten() {
+        hasher.update(format!("file\0{}\0{}\0", file.src, file.dst).as_bytes());
+        hash_path(&mut hasher, &dir.join(&file.src))?;
+    }
+    for command in provision.inline.iter().flatten() {
+        hasher.update(format!("inline\0{}\0", command).as_bytes());
+    }
+    for script in provision.scripts.iter().flatten() {
+        hasher.update(format!("script\0{}\0", script).as_bytes());
+        hash_path(&mut hasher, &dir.join(script))?;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Serializes the provisioning of a VM as JSON.
+pub fn to_column(provision: &ProvisionOptions) -> String {
+    serde_json::to_string(provision).unwrap_or_default()
+}
+
+pub fn from_column(column: &str) -> Option<ProvisionOptions> {
+    serde_json::from_str(column).ok()
+}
+
+/// Applies the provisioning of the VM `vm_id`: copies its files, then runs
+/// its inline commands and its scripts. A VM with a persistent disk keeps
+/// what it was provisioned with, it is only provisioned again once the hash
+/// of the provisioning changes; the others start from a clean overlay.
+pub async fn provision(vm_id: &str, name: &str, guest: &Guest, options: &VmOptions) -> Result<()> {
+    let provision = match &options.provision {
+        Some(provision) => provision,
+        None => return Ok(()),
+    };
+    let pool = firecracker_state::create_connection_pool().await?;
+    let vm = repo::virtual_machine::find(&pool, vm_id).await?;
+    // the paths of fire.toml, wherever the VM is started again from
+    let dir = vm
+        .as_ref()
+        .and_then(|vm| vm.project_dir.as_ref())
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    let hash = hash(provision, &dir)?;
+    if options.disk_size.is_some()
+        && vm.and_then(|vm| vm.provision_hash).as_deref() == Some(hash.as_str())
+    {
+        println!("[✓] {} is already provisioned.", name.cyan());
+        return Ok(());
+    }
+
+    println!("[+] Provisioning {}...", name.cyan());
+    for file in provision.files.iter().flatten() {
+        println!("[+] Copying {} to {}...", file.src, file.dst);
+        guest.copy(&dir.join(&file.src), &file.dst)?;
+    }
+    for command in provision.inline.iter().flatten() {
+        println!("[+] Running {}", command.bright_green());
+        guest.run_with_output(command)?;
+    }
+    for (i, script) in provision.scripts.iter().flatten().enumerate() {
+        println!("[+] Running script {}", script.bright_green());
+        let target = format!("/tmp/fireup-provision-{}", i);
+        guest.copy(&dir.join(script), &target)?;
+        guest.run_with_output(&format!(
+            "chmod +x {0} && {0}; code=$?; rm -f {0}; exit $code",
+            target
+        ))?;
+    }
+
+    repo::virtual_machine::update_provision_hash(&pool, vm_id, &hash).await?;
+    println!("[✓] {} provisioned.", name.cyan());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash() {
+        let script =
+            std::env::temp_dir().join(format!("fireup-provision-{}.sh", std::process::id()));
+        fs::write(&script, "apt-get install -y nginx\n").unwrap();
+        let provision = ProvisionOptions {
+            inline: Some(vec!["echo hello".into()]),
+            scripts: Some(vec![script.display().to_string()]),
+            files: None,
+        };
+        let first = hash(&provision, Path::new("")).unwrap();
+        assert_eq!(hash(&provision, Path::new("")).unwrap(), first);
+
+        fs::write(&script, "apt-get install -y caddy\n").unwrap();
+        let second = hash(&provision, Path::new("")).unwrap();
+        assert_ne!(second, first);
+
+        let without_inline = ProvisionOptions {
+            inline: None,
+            scripts: provision.scripts.clone(),
+            files: None,
+        };
+        assert_ne!(hash(&without_inline, Path::new("")).unwrap(), second);
+        fs::remove_file(&script).ok();
+        assert!(hash(&provision, Path::new("")).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    ffi::CString,
+    net::{Ipv6Addr, SocketAddrV6, UdpSocket},
+    os::fd::FromRawFd,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Error, Result};
+use firecracker_state::entity::network::Network;
+
+use crate::{
+    netd::{bind_to_device, setsockopt},
+    network,
+};
+
+const ROUTER_SOLICITATION: u8 = 133;
+const ROUTER_ADVERTISEMENT: u8 = 134;
+const OPT_PREFIX_INFORMATION: u8 = 3;
+
+/// On-link and autonomous address configuration flags of the prefix option.
+const PREFIX_FLAGS: u8 = 0xc0;
+const HOP_LIMIT: u8 = 64;
+const ROUTER_LIFETIME: u16 = 1800;
+const VALID_LIFETIME: u32 = 86400;
+const PREFERRED_LIFETIME: u32 = 14400;
+const INTERVAL: Duration = Duration::from_secs(60);
+
+const ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+const ALL_ROUTERS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+
+/// Router advertisement announcing `prefix` for SLAAC, the kernel fills in
+/// the ICMPv6 checksum.
+pub fn advertisement(prefix: Ipv6Addr) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(48);
+    buf.extend_from_slice(&[ROUTER_ADVERTISEMENT, 0, 0, 0, HOP_LIMIT, 0]);
+    buf.extend_from_slice(&ROUTER_LIFETIME.to_be_bytes());
+    // reachable time and retransmission timer left unspecified
+    buf.extend_from_slice(&[0; 8]);
+
+    buf.extend_from_slice(&[OPT_PREFIX_INFORMATION, 4, 64, PREFIX_FLAGS]);
+    buf.extend_from_slice(&VALID_LIFETIME.to_be_bytes());
+    buf.extend_from_slice(&PREFERRED_LIFETIME.to_be_bytes());
+    buf.extend_from_slice(&[0; 4]);
+    buf.extend_from_slice(&prefix.octets());
+    buf
+}
+
+fn interface_index(bridge: &str) -> Result<u32> {
+    let name = CString::new(bridge)?;
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => Err(anyhow!("No such interface: {}", bridge)),
+        index => Ok(index),
+    }
+}
+
+/// Opens a raw ICMPv6 socket on `bridge` listening for router solicitations,
+/// requires root. It is wrapped in a `UdpSocket` to get `send_to`/`recv_from`.
+pub fn bind(bridge: &str) -> Result<UdpSocket> {
+    let index = interface_index(bridge)?;
+    let socket = unsafe {
+        let fd = libc::socket(
+            libc::AF_INET6,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::IPPROTO_ICMPV6,
+        );
+        if fd < 0 {
+            return Err(Error::new(std::io::Error::last_os_error()))
+                .with_context(|| "Failed to open ICMPv6 socket");
+        }
+        UdpSocket::from_raw_fd(fd)
+    };
+
+    // neighbor discovery messages must be sent with a hop limit of 255
+    let hops: libc::c_int = 255;
+    setsockopt(
+        &socket,
+        libc::IPPROTO_IPV6,
+        libc::IPV6_MULTICAST_HOPS,
+        &hops,
+    )?;
+    setsockopt(&socket, libc::IPPROTO_IPV6, libc::IPV6_UNICAST_HOPS, &hops)?;
+    setsockopt(&socket, libc::IPPROTO_IPV6, libc::IPV6_MULTICAST_IF, &index)?;
+    setsockopt(
+        &socket,
+        libc::IPPROTO_IPV6,
+        libc::IPV6_ADD_MEMBERSHIP,
+        &libc::ipv6_mreq {
+            ipv6mr_multiaddr: libc::in6_addr {
+                s6_addr: ALL_ROUTERS.octets(),
+            },
+            ipv6mr_interface: index,
+        },
+    )?;
+    bind_to_device(&socket, bridge)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+/// Advertises the IPv6 prefix of `network` periodically and whenever a guest
+/// sends a router solicitation.
+pub async fn serve(socket: UdpSocket, network: Network) -> Result<()> {
+    let prefix = network::ipv6_prefix(&network)?
+        .ok_or_else(|| anyhow!("Network {} has no IPv6 prefix", network.name))?;
+    let destination = SocketAddrV6::new(ALL_NODES, 0, 0, interface_index(&network.bridge)?);
+    let socket = tokio::net::UdpSocket::from_std(socket)?;
+    let packet = advertisement(prefix);
+    let mut interval = tokio::time::interval(INTERVAL);
+    let mut buf = [0u8; 1500];
+
+    println!("[+] Advertising {}/64 on {}", prefix, network.bridge);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            received = socket.recv_from(&mut buf) => {
+                let (len, _) = received?;
+                if len == 0 || buf[0] != ROUTER_SOLICITATION {
+                    continue;
+                }
+            }
+        }
+        if let Err(e) = socket.send_to(&packet, destination).await {
+            // the bridge has no link-local address until a tap joins it
+            if e.raw_os_error() != Some(libc::EADDRNOTAVAIL) {
+                eprintln!(
+                    "[!] Failed to send router advertisement on {}: {}",
+                    network.bridge, e
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advertisement() {
+        let prefix: Ipv6Addr = "fd12:3456:789a::".parse().unwrap();
+        let packet = advertisement(prefix);
+
+        assert_eq!(packet.len(), 48);
+        assert_eq!(packet[0], ROUTER_ADVERTISEMENT);
+        assert_eq!(u16::from_be_bytes([packet[6], packet[7]]), ROUTER_LIFETIME);
+        // prefix information option, 4 units of 8 bytes, /64
+        assert_eq!(
+            &packet[16..20],
+            &[OPT_PREFIX_INFORMATION, 4, 64, PREFIX_FLAGS]
+        );
+        assert_eq!(&packet[32..], &prefix.octets());
+    }
+}
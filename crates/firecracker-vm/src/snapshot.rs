@@ -0,0 +1,224 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use firecracker_state::{
+    entity::{snapshot::Snapshot, virtual_machine::VirtualMachine},
+    repo,
+};
+use owo_colors::OwoColorize;
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    agent,
+    client::{
+        FirecrackerClient, MemoryBackend, MemoryBackendType, SnapshotCreateParams,
+        SnapshotLoadParams, SnapshotType, State, Vm,
+    },
+    command::run_command,
+    config::get_config_dir,
+    firecracker, firewall, netd, network, ports,
+    types::VmOptions,
+};
+
+pub async fn create(vm: &VirtualMachine, name: &str) -> Result<Snapshot> {
+    // the name is the directory Firecracker writes the snapshot files to
+    if name.is_empty()
+        || name == "."
+        || name == ".."
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        return Err(anyhow!(
+            "Invalid snapshot name '{}', only letters, digits, '-', '_' and '.' are allowed",
+            name
+        ));
+    }
+    if vm.jailer_uid.is_some() {
+        return Err(anyhow!(
+            "Snapshots are not supported for jailed MicroVMs ({})",
+            vm.name
+        ));
+    }
+
+    let pool = firecracker_state::create_connection_pool().await?;
+    if repo::snapshot::find(&pool, name).await?.is_some() {
+        return Err(anyhow!("Snapshot '{}' already exists", name));
+    }
+
+    let app_dir = get_config_dir().with_context(|| "Failed to get configuration directory")?;
+    let snapshot_dir = format!("{}/snapshots/{}", app_dir, name);
+    fs::create_dir_all(&snapshot_dir)
+        .with_context(|| format!("Failed to create snapshot directory: {}", snapshot_dir))?;
+
+    let state_file = format!("{}/vmstate", snapshot_dir);
+    let memory_file = format!("{}/memory", snapshot_dir);
+
+    let client = FirecrackerClient::new(&vm.api_socket);
+    let was_paused = vm.status == "PAUSED";
+
+    if !was_paused {
+        println!("[+] Pausing {}...", vm.name.cyan());
+        client
+            .patch_vm(&Vm {
+                state: State::Paused,
+            })
+            .await
+            .with_context(|| format!("Failed to pause {}", vm.name))?;
+    }
+
+    println!("[+] Creating snapshot {}...", name.cyan());
+    let result = client
+        .put_snapshot_create(&SnapshotCreateParams {
+            snapshot_type: SnapshotType::Full,
+            snapshot_path: state_file.clone(),
+            mem_file_path: memory_file.clone(),
+        })
+        .await;
+
+    // Resume the VM even if the snapshot failed
+    if !was_paused {
+        println!("[+] Resuming {}...", vm.name.cyan());
+        client
+            .patch_vm(&Vm {
+                state: State::Resumed,
+            })
+            .await
+            .with_context(|| format!("Failed to resume {}", vm.name))?;
+    }
+
+    result.with_context(|| format!("Failed to create snapshot {}", name))?;
+
+    let id = repo::snapshot::create(
+        &pool,
+        Snapshot {
+            name: name.into(),
+            vm_id: vm.id.clone(),
+            vm_name: vm.name.clone(),
+            state_file,
+            memory_file,
+            vmlinux: vm.vmlinux.clone(),
+            rootfs: vm.rootfs.clone(),
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    repo::snapshot::find(&pool, &id)
+        .await?
+        .ok_or_else(|| anyhow!("Failed to retrieve the created snapshot"))
+}
+
+/// Removes the snapshot `name`, its files and its record.
+pub async fn remove(name: &str) -> Result<Snapshot> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let snapshot = repo::snapshot::find(&pool, name)
+        .await?
+        .ok_or_else(|| anyhow!("No snapshot found with name or id '{}'", name))?;
+    delete(&pool, &snapshot).await?;
+    Ok(snapshot)
+}
+
+/// Removes the snapshots of `vm`, they cannot be restored without it.
+pub async fn remove_all(pool: &Pool<Sqlite>, vm: &VirtualMachine) -> Result<()> {
+    for snapshot in repo::snapshot::find_by_vm(pool, &vm.id).await? {
+        delete(pool, &snapshot).await?;
+        println!("[+] Snapshot {} has been removed.", snapshot.name.cyan());
+    }
+    Ok(())
+}
+
+async fn delete(pool: &Pool<Sqlite>, snapshot: &Snapshot) -> Result<()> {
+    // the state and memory files are written by Firecracker, owned by root
+    for file in [&snapshot.state_file, &snapshot.memory_file] {
+        run_command("rm", &["-f", file], true)?;
+    }
+    if let Some(dir) = Path::new(&snapshot.state_file).parent() {
+        fs::remove_dir(dir).ok();
+    }
+    repo::snapshot::delete(pool, &snapshot.id).await
+}
+
+pub async fn restore(options: &VmOptions, pid: u32, snapshot: &Snapshot) -> Result<()> {
+    let files = [
+        Some(&snapshot.state_file),
+        Some(&snapshot.memory_file),
+        snapshot.vmlinux.as_ref(),
+        snapshot.rootfs.as_ref(),
+    ];
+    for file in files.into_iter().flatten() {
+        if !Path::new(file).exists() {
+            return Err(anyhow!(
+                "Snapshot {} is missing {}, it can no longer be restored",
+                snapshot.name,
+                file
+            ));
+        }
+    }
+
+    let network = network::for_vm(options).await?;
+    network::setup_network(options, &network).await?;
+    netd::ensure_running(&network)?;
+
+    // the snapshot expects the taps of its extra interfaces to be there too
+    let pool = firecracker_state::create_connection_pool().await?;
+    for interface in repo::vm_interface::find_by_vm(&pool, &snapshot.vm_id).await? {
+        if interface.tap == options.tap {
+            continue;
+        }
+        let network = network::get(&interface.network).await?;
+        let options = VmOptions {
+            bridge: network.bridge.clone(),
+            tap: interface.tap.clone(),
+            mac_address: interface.mac_address.clone(),
+            ..options.clone()
+        };
+        network::setup_network(&options, &network).await?;
+        netd::ensure_running(&network)?;
+    }
+
+    let app_dir = get_config_dir().with_context(|| "Failed to get configuration directory")?;
+    let logfile = format!("{}/logs/firecracker-{}.log", app_dir, snapshot.vm_name);
+    fs::create_dir_all(format!("{}/logs", app_dir))
+        .with_context(|| format!("Failed to create logs directory: {}", app_dir))?;
+    fs::File::create(&logfile)
+        .with_context(|| format!("Failed to create log file: {}", logfile))?;
+
+    let client = FirecrackerClient::new(&options.api_socket);
+    firecracker::configure_logger(&client, &logfile).await?;
+
+    // the vsock device of the snapshot binds its socket again
+    run_command("rm", &["-f", &agent::socket(&options.api_socket)], true)?;
+
+    println!("[+] Loading snapshot {}...", snapshot.name.cyan());
+    client
+        .put_snapshot_load(&SnapshotLoadParams {
+            snapshot_path: snapshot.state_file.clone(),
+            mem_backend: MemoryBackend {
+                backend_type: MemoryBackendType::File,
+                backend_path: snapshot.memory_file.clone(),
+            },
+            enable_diff_snapshots: false,
+            resume_vm: true,
+        })
+        .await
+        .with_context(|| format!("Failed to load snapshot {}", snapshot.name))?;
+    agent::expose(&options.api_socket)?;
+
+    let mut vm = repo::virtual_machine::find(&pool, &snapshot.vm_id)
+        .await?
+        .ok_or_else(|| anyhow!("No virtual machine found with id '{}'", snapshot.vm_id))?;
+    vm.pid = Some(pid);
+    vm.status = "RUNNING".into();
+    vm.exit_code = None;
+    let published = vm.ports.clone().zip(vm.ipv4_address());
+    repo::virtual_machine::update(&pool, &snapshot.vm_id, vm).await?;
+    match published {
+        Some((ports, guest_ip)) => {
+            ports::publish(&network.bridge, &guest_ip, &ports::from_column(&ports)).await?
+        }
+        None => firewall::sync().await?,
+    }
+
+    Ok(())
+}
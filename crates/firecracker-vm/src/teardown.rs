@@ -0,0 +1,258 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use anyhow::{Context, Result};
+use firecracker_state::{entity::virtual_machine::VirtualMachine, repo};
+use owo_colors::OwoColorize;
+use serde::Deserialize;
+
+use crate::{
+    agent, command::run_command, console, disk, firewall, jailer, logs, network, snapshot,
+};
+
+/// Host resources `fireup prune` removed.
+#[derive(Debug, Default)]
+pub struct Pruned {
+    pub interfaces: Vec<String>,
+    pub sockets: Vec<String>,
+    pub jails: Vec<String>,
+    pub leases: usize,
+    pub logs: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Link {
+    ifname: String,
+    master: Option<String>,
+}
+
+/// Links of the given `kind` attached to a bridge, from `ip -j link show`.
+fn bridged_links(kind: &str) -> Result<Vec<(String, String)>> {
+    let output = run_command("ip", &["-j", "link", "show", "type", kind], false)?;
+    parse_links(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_links(json: &str) -> Result<Vec<(String, String)>> {
+    let links: Vec<Link> = match json.trim().is_empty() {
+        true => vec![],
+        false => serde_json::from_str(json).with_context(|| "Failed to parse network links")?,
+    };
+    Ok(links
+        .into_iter()
+        .filter_map(|link| Some((link.ifname, link.master?)))
+        .collect())
+}
+
+fn remove_link(name: &str) -> Result<()> {
+    let exists = run_command("ip", &["link", "show", name], false)
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+    if exists {
+        println!("[+] Removing {}...", name.cyan());
+        run_command("ip", &["link", "del", name], true)
+            .with_context(|| format!("Failed to remove {}", name))?;
+    }
+    Ok(())
+}
+
+/// Releases what `setup` created on the host for a MicroVM whose Firecracker
+/// process is gone: its API, vsock and console sockets, its jail and its taps.
+/// Everything is set up again the next time it starts.
+pub async fn release(vm: &VirtualMachine) -> Result<()> {
+    run_command("rm", &["-rf", &vm.api_socket], true)?;
+    run_command("rm", &["-f", &agent::socket(&vm.api_socket)], true)?;
+    run_command("rm", &["-f", &console::socket(&vm.api_socket)], true)?;
+    if vm.jailer_uid.is_some() {
+        // the taps live in the jail network namespace and go away with it
+        return jailer::cleanup(&vm.name).await;
+    }
+
+    let pool = firecracker_state::create_connection_pool().await?;
+    let mut taps = vec![vm.tap.clone()];
+    for interface in repo::vm_interface::find_by_vm(&pool, &vm.id).await? {
+        if !taps.contains(&interface.tap) {
+            taps.push(interface.tap);
+        }
+    }
+    for tap in taps.iter().filter(|tap| !tap.is_empty()) {
+        remove_link(tap)?;
+    }
+    Ok(())
+}
+
+/// Reverses everything `setup` created for a stopped MicroVM, then forgets
+/// it: taps, jail, persistent disk, leases, log files and state. Its DNS
+/// records go away with its row.
+pub async fn remove(vm: &VirtualMachine) -> Result<()> {
+    release(vm).await?;
+    if let Some(disk) = &vm.disk {
+        disk::remove_disk(disk)?;
+    }
+
+    let pool = firecracker_state::create_connection_pool().await?;
+    repo::lease::delete_by_mac(&pool, &vm.mac_address).await?;
+    for interface in repo::vm_interface::find_by_vm(&pool, &vm.id).await? {
+        repo::lease::delete_by_mac(&pool, &interface.mac_address).await?;
+    }
+    repo::vm_interface::delete_by_vm(&pool, &vm.id).await?;
+    snapshot::remove_all(&pool, vm).await?;
+
+    for source in logs::Source::ALL {
+        let logfile = source.log_file(&vm.name)?;
+        if Path::new(&logfile).exists() {
+            fs::remove_file(&logfile)
+                .with_context(|| format!("Failed to remove log file: {}", logfile))?;
+        }
+    }
+
+    repo::virtual_machine::delete(&pool, &vm.id).await?;
+    firewall::sync().await
+}
+
+/// Reconciles the host with the state database: removes the taps, API and
+/// vsock and console sockets, jails, leases and log files no running MicroVM accounts for.
+pub async fn prune() -> Result<Pruned> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let vms = repo::virtual_machine::all(&pool).await?;
+    let running = vms
+        .iter()
+        .filter(|vm| {
+            (vm.status == "RUNNING" || vm.status == "PAUSED") && Path::new(&vm.api_socket).exists()
+        })
+        .collect::<Vec<_>>();
+    let interfaces = repo::vm_interface::all(&pool).await?;
+    let mut pruned = Pruned::default();
+
+    let mut in_use = HashSet::new();
+    for vm in &running {
+        in_use.insert(network::host_port(&vm.tap, vm.jailer_uid.is_some()));
+        for interface in interfaces
+            .iter()
+            .filter(|interface| interface.vm_id == vm.id)
+        {
+            in_use.insert(network::host_port(&interface.tap, vm.jailer_uid.is_some()));
+        }
+    }
+    let bridges = network::all()
+        .await?
+        .into_iter()
+        .map(|network| network.bridge)
+        .collect::<HashSet<_>>();
+    for kind in ["tun", "veth"] {
+        for (link, master) in bridged_links(kind)? {
+            if bridges.contains(&master) && !in_use.contains(&link) {
+                remove_link(&link)?;
+                pruned.interfaces.push(link);
+            }
+        }
+    }
+
+    let sockets = running
+        .iter()
+        .flat_map(|vm| {
+            [
+                vm.api_socket.clone(),
+                agent::socket(&vm.api_socket),
+                console::socket(&vm.api_socket),
+            ]
+        })
+        .collect::<HashSet<_>>();
+    let stale = glob::glob("/tmp/firecracker-*.sock")?
+        .chain(glob::glob("/tmp/firecracker-*.vsock")?)
+        .chain(glob::glob("/tmp/firecracker-*.console")?);
+    for socket in stale.flatten() {
+        let socket = socket.display().to_string();
+        if !sockets.contains(&socket) {
+            println!("[+] Removing stale socket {}...", socket.cyan());
+            run_command("rm", &["-f", &socket], true)?;
+            pruned.sockets.push(socket);
+        }
+    }
+
+    let jails = format!("{}/firecracker", jailer::chroot_base_dir()?);
+    let mut names = glob::glob(&format!("{}/*", jails))?
+        .flatten()
+        .filter_map(|dir| Some(dir.file_name()?.to_string_lossy().to_string()))
+        .collect::<Vec<_>>();
+    if let Ok(output) = run_command("ip", &["netns", "list"], false) {
+        names.extend(
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter_map(|line| line.split_whitespace().next())
+                .filter_map(|netns| netns.strip_prefix("fireup-"))
+                .map(String::from),
+        );
+    }
+    names.sort();
+    names.dedup();
+    for name in names {
+        if !running.iter().any(|vm| vm.name == name) {
+            jailer::cleanup(&name).await?;
+            pruned.jails.push(name);
+        }
+    }
+
+    let macs = vms
+        .iter()
+        .map(|vm| vm.mac_address.to_lowercase())
+        .chain(
+            interfaces
+                .iter()
+                .map(|interface| interface.mac_address.to_lowercase()),
+        )
+        .collect::<HashSet<_>>();
+    for lease in repo::lease::all(&pool).await? {
+        if !macs.contains(&lease.mac_address.to_lowercase()) {
+            repo::lease::delete_by_mac(&pool, &lease.mac_address).await?;
+            pruned.leases += 1;
+        }
+    }
+    let ids = vms.iter().map(|vm| vm.id.clone()).collect::<HashSet<_>>();
+    for interface in interfaces
+        .iter()
+        .filter(|interface| !ids.contains(&interface.vm_id))
+    {
+        repo::vm_interface::delete_by_vm(&pool, &interface.vm_id).await?;
+    }
+
+    let names = vms.iter().map(|vm| vm.name.clone()).collect::<HashSet<_>>();
+    let mut logfiles = vec![];
+    for source in logs::Source::ALL {
+        logfiles.extend(glob::glob(&source.log_file("*")?)?.flatten());
+    }
+    for logfile in logfiles {
+        // the name of the VM follows the source in `<source>-<name>.log`
+        let name = logfile
+            .file_stem()
+            .and_then(|stem| {
+                stem.to_string_lossy()
+                    .split_once('-')
+                    .map(|(_, name)| name.to_string())
+            })
+            .unwrap_or_default();
+        if !names.contains(&name) {
+            println!("[+] Removing log file {}...", logfile.display());
+            fs::remove_file(&logfile)
+                .with_context(|| format!("Failed to remove log file: {}", logfile.display()))?;
+            pruned.logs.push(logfile.display().to_string());
+        }
+    }
+
+    firewall::sync().await?;
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_links() {
+        let links = parse_links(
+            r#"[{"ifindex":5,"ifname":"tap0","flags":["UP"],"master":"br0"},
+                {"ifindex":6,"ifname":"tap1","flags":["UP"]}]"#,
+        )
+        .unwrap();
+        assert_eq!(links, vec![("tap0".to_string(), "br0".to_string())]);
+        assert!(parse_links("").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,26 @@
+/// Puts the terminal of the host in raw mode, the one of the guest
+/// interprets the keys, until dropped.
+pub(crate) struct RawMode(libc::termios);
+
+impl RawMode {
+    pub(crate) fn enable() -> Option<Self> {
+        unsafe {
+            let mut termios = std::mem::zeroed();
+            if libc::tcgetattr(0, &mut termios) < 0 {
+                return None;
+            }
+            let original = termios;
+            libc::cfmakeraw(&mut termios);
+            libc::tcsetattr(0, libc::TCSANOW, &termios);
+            Some(RawMode(original))
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(0, libc::TCSANOW, &self.0);
+        }
+    }
+}
@@ -1,7 +1,10 @@
-use fire_config::{EtcdConfig, FireConfig, TailscaleOptions};
+use fire_config::{
+    FireConfig, FirewallOptions, LimitsOptions, NicOptions, ProvisionOptions, RestartPolicy,
+    TailscaleOptions, VolumeMount,
+};
 use firecracker_prepare::Distro;
 
-use crate::constants::{BRIDGE_DEV, FC_MAC, FIRECRACKER_SOCKET};
+use crate::constants::{BRIDGE_DEV, DEFAULT_DISK_SIZE, FC_MAC, FIRECRACKER_SOCKET};
 
 #[derive(Default, Clone)]
 pub struct VmOptions {
@@ -26,9 +29,34 @@ pub struct VmOptions {
     pub tap: String,
     pub api_socket: String,
     pub mac_address: String,
-    pub etcd: Option<EtcdConfig>,
     pub ssh_keys: Option<Vec<String>>,
     pub tailscale: Option<TailscaleOptions>,
+    /// Size in MiB of the persistent overlay disk, `None` keeps the overlay in RAM.
+    pub disk_size: Option<u32>,
+    pub volumes: Option<Vec<VolumeMount>>,
+    /// Launch Firecracker through the jailer (chroot, dedicated uid, cgroup and netns).
+    pub jailer: Option<bool>,
+    /// Static IPv4 address, handed out through a reserved DHCP lease.
+    pub ip: Option<String>,
+    /// Name of the network to attach the VM to, the default network if unset.
+    pub network: Option<String>,
+    /// Host ports forwarded to the guest, e.g. `8080:80` or `5353:53/udp`.
+    pub ports: Option<Vec<String>>,
+    /// Egress and isolation policy enforced on the tap of the VM.
+    pub firewall: Option<FirewallOptions>,
+    /// Network and drive rate limits.
+    pub limits: Option<LimitsOptions>,
+    /// Network interfaces, the first one overrides `network` and
+    /// `mac_address`, the others are attached as `eth1`, `eth2`...
+    pub nics: Option<Vec<NicOptions>>,
+    /// Whether `fireup serve` starts the VM again once Firecracker is gone.
+    pub restart: Option<RestartPolicy>,
+    /// Multi-VM project of `fire.toml` the VM belongs to.
+    pub project: Option<String>,
+    /// Files and commands applied to the guest once its network is up.
+    pub provision: Option<ProvisionOptions>,
+    /// Image of `fireup build` used as rootfs instead of the one of the distribution.
+    pub image: Option<String>,
 }
 
 impl From<FireConfig> for VmOptions {
@@ -56,9 +84,46 @@ impl From<FireConfig> for VmOptions {
             tap: vm.tap.unwrap_or("".into()),
             api_socket: vm.api_socket.unwrap_or(FIRECRACKER_SOCKET.into()),
             mac_address: vm.mac.unwrap_or(FC_MAC.into()),
-            etcd: config.etcd.clone(),
             ssh_keys: vm.ssh_keys.clone(),
             tailscale: vm.tailscale.clone(),
+            disk_size: match vm.persistent {
+                Some(false) => None,
+                Some(true) => Some(vm.disk_size.unwrap_or(DEFAULT_DISK_SIZE)),
+                None => vm.disk_size,
+            },
+            volumes: vm.volumes.clone(),
+            jailer: vm.jailer,
+            ip: vm.ip.clone(),
+            network: vm.network.clone(),
+            ports: vm.ports.clone(),
+            firewall: vm.firewall.clone(),
+            limits: vm.limits.clone(),
+            nics: vm.nics.clone(),
+            restart: vm.restart,
+            project: None,
+            provision: vm.provision.or(config.provision),
+            image: vm.image,
+        }
+    }
+}
+
+impl VmOptions {
+    /// `self` set to run `distro`.
+    pub fn with_distro(self, distro: Distro) -> Self {
+        VmOptions {
+            debian: Some(distro == Distro::Debian),
+            alpine: Some(distro == Distro::Alpine),
+            ubuntu: Some(distro == Distro::Ubuntu),
+            nixos: Some(distro == Distro::NixOS),
+            fedora: Some(distro == Distro::Fedora),
+            gentoo: Some(distro == Distro::Gentoo),
+            slackware: Some(distro == Distro::Slackware),
+            opensuse: Some(distro == Distro::Opensuse),
+            opensuse_tumbleweed: Some(distro == Distro::OpensuseTumbleweed),
+            almalinux: Some(distro == Distro::Almalinux),
+            rockylinux: Some(distro == Distro::RockyLinux),
+            archlinux: Some(distro == Distro::Archlinux),
+            ..self
         }
     }
 }
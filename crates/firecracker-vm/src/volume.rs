@@ -0,0 +1,206 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use fire_config::VolumeMount;
+use firecracker_state::{
+    entity::{virtual_machine::VirtualMachine, volume::Volume},
+    repo,
+};
+use owo_colors::OwoColorize;
+
+use crate::{config::get_config_dir, disk::create_image};
+
+pub async fn create(name: &str, size: u32) -> Result<Volume> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(anyhow!(
+            "Invalid volume name '{}', only letters, digits, '-' and '_' are allowed",
+            name
+        ));
+    }
+
+    let pool = firecracker_state::create_connection_pool().await?;
+    if repo::volume::find(&pool, name).await?.is_some() {
+        return Err(anyhow!("Volume '{}' already exists", name));
+    }
+
+    let app_dir = get_config_dir().with_context(|| "Failed to get configuration directory")?;
+    fs::create_dir_all(format!("{}/volumes", app_dir))
+        .with_context(|| format!("Failed to create volumes directory: {}", app_dir))?;
+
+    let path = format!("{}/volumes/{}.img", app_dir, name);
+    println!("[+] Creating volume {} ({} MiB)...", name.cyan(), size);
+    create_image(&path, size).with_context(|| format!("Failed to create volume: {}", path))?;
+
+    let id = repo::volume::create(
+        &pool,
+        Volume {
+            name: name.into(),
+            path,
+            size,
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    repo::volume::find(&pool, &id)
+        .await?
+        .ok_or_else(|| anyhow!("Failed to retrieve the created volume"))
+}
+
+pub async fn remove(name: &str) -> Result<Volume> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let volume = repo::volume::find(&pool, name)
+        .await?
+        .ok_or_else(|| anyhow!("No volume found with name or id '{}'", name))?;
+
+    if let Some(vm) = attached_to(&volume.name).await? {
+        return Err(anyhow!(
+            "Volume '{}' is in use by {}, stop it first",
+            volume.name,
+            vm.name
+        ));
+    }
+
+    if Path::new(&volume.path).exists() {
+        fs::remove_file(&volume.path)
+            .with_context(|| format!("Failed to remove volume: {}", volume.path))?;
+    }
+    repo::volume::delete(&pool, &volume.id).await?;
+
+    Ok(volume)
+}
+
+/// Resolves the volumes to attach to `vm_name`, making sure none of them is
+/// already mounted by another running MicroVM.
+pub async fn attach(vm_name: &str, mounts: &[VolumeMount]) -> Result<Vec<(Volume, String)>> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let mut volumes = vec![];
+
+    for mount in mounts {
+        if !is_mount_point(&mount.mount) {
+            return Err(anyhow!(
+                "Invalid mount point '{}' for volume '{}', expected an absolute path of letters, digits, '-', '_' and '.'",
+                mount.mount,
+                mount.name
+            ));
+        }
+
+        let volume = repo::volume::find(&pool, &mount.name)
+            .await?
+            .ok_or_else(|| {
+                anyhow!(
+                    "No volume found with name '{}', create it with `fireup volume create {}`",
+                    mount.name,
+                    mount.name
+                )
+            })?;
+
+        if let Some(vm) = attached_to(&volume.name).await? {
+            if vm.name != vm_name {
+                return Err(anyhow!(
+                    "Volume '{}' is already in use by {}",
+                    volume.name,
+                    vm.name
+                ));
+            }
+        }
+
+        if !Path::new(&volume.path).exists() {
+            return Err(anyhow!(
+                "Volume '{}' is missing its image: {}",
+                volume.name,
+                volume.path
+            ));
+        }
+
+        volumes.push((volume, mount.mount.clone()));
+    }
+
+    Ok(volumes)
+}
+
+/// Whether `mount` is an absolute path safe to use in the guest shell and in
+/// the `name:mount` column, without `..` components.
+fn is_mount_point(mount: &str) -> bool {
+    mount.starts_with('/')
+        && mount
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '-' | '_' | '.'))
+        && !mount.split('/').any(|component| component == "..")
+}
+
+/// Returns the running or paused MicroVM using the given volume, if any.
+pub async fn attached_to(name: &str) -> Result<Option<VirtualMachine>> {
+    let pool = firecracker_state::create_connection_pool().await?;
+    let vms = repo::virtual_machine::all(&pool).await?;
+    Ok(vms.into_iter().find(|vm| {
+        (vm.status == "RUNNING" || vm.status == "PAUSED")
+            && Path::new(&vm.api_socket).exists()
+            && vm
+                .volumes
+                .as_deref()
+                .map(|volumes| from_column(volumes).iter().any(|v| v.name == name))
+                .unwrap_or(false)
+    }))
+}
+
+/// Serializes volume mounts as `name:mount` pairs separated by commas.
+pub fn to_column(mounts: &[VolumeMount]) -> String {
+    mounts
+        .iter()
+        .map(|m| format!("{}:{}", m.name, m.mount))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn from_column(column: &str) -> Vec<VolumeMount> {
+    column
+        .split(',')
+        .filter_map(|entry| entry.split_once(':'))
+        .map(|(name, mount)| VolumeMount {
+            name: name.into(),
+            mount: mount.into(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_volume_column_roundtrip() {
+        let mounts = vec![
+            VolumeMount {
+                name: "pgdata".into(),
+                mount: "/var/lib/postgresql".into(),
+            },
+            VolumeMount {
+                name: "cache".into(),
+                mount: "/var/cache/app".into(),
+            },
+        ];
+        let column = to_column(&mounts);
+        assert_eq!(column, "pgdata:/var/lib/postgresql,cache:/var/cache/app");
+
+        let parsed = from_column(&column);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].name, "pgdata");
+        assert_eq!(parsed[1].mount, "/var/cache/app");
+    }
+
+    #[test]
+    fn test_mount_point() {
+        assert!(is_mount_point("/var/lib/postgresql"));
+        assert!(is_mount_point("/srv/app-data_1.d"));
+        assert!(!is_mount_point("var/lib"));
+        assert!(!is_mount_point("/data'; reboot; '"));
+        assert!(!is_mount_point("/data dir"));
+        assert!(!is_mount_point("/data,cache:/tmp"));
+        assert!(!is_mount_point("/mnt/../etc"));
+    }
+}
@@ -0,0 +1,125 @@
+//! Protocol spoken over vsock between fireup and the agent running in its
+//! MicroVMs.
+//!
+//! Every message is a JSON frame on its own line, those carrying data are
+//! followed by `size` raw bytes.
+
+use std::io::{self, BufRead, Write};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Vsock port the agent listens on.
+pub const PORT: u32 = 1024;
+
+/// Context id of the guest, each MicroVM has its own vsock device.
+pub const GUEST_CID: u32 = 3;
+
+/// Terminal the command of an [`Request::Exec`] is attached to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tty {
+    pub rows: u16,
+    pub cols: u16,
+    pub term: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// Answered with [`Response::Booted`].
+    Ping,
+    /// Runs `command` with `sh -c`, or a login shell when it is `None`. The
+    /// output is streamed back until [`Response::Exit`], the input is sent
+    /// with [`Request::Stdin`].
+    Exec {
+        command: Option<String>,
+        tty: Option<Tty>,
+    },
+    Stdin {
+        size: u64,
+    },
+    /// Closes the input of the running command.
+    CloseStdin,
+    /// Answered with [`Response::File`] and its content.
+    Read {
+        path: String,
+    },
+    /// Writes the `size` bytes that follow to `path`, or to `file_name` in it
+    /// when `path` is a directory.
+    Write {
+        path: String,
+        file_name: String,
+        mode: u32,
+        size: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Booted,
+    Stdout { size: u64 },
+    Stderr { size: u64 },
+    Exit { code: i32 },
+    File { mode: u32, size: u64 },
+    Written,
+    Error { message: String },
+}
+
+/// Writes `frame`, followed by `payload`.
+pub fn write_frame<T: Serialize>(
+    writer: &mut impl Write,
+    frame: &T,
+    payload: &[u8],
+) -> io::Result<()> {
+    let mut line = serde_json::to_vec(frame)?;
+    line.push(b'\n');
+    line.extend_from_slice(payload);
+    writer.write_all(&line)?;
+    writer.flush()
+}
+
+/// Reads the next frame, `None` once the other side closed the connection.
+/// Its payload is left in `reader`.
+pub fn read_frame<T: DeserializeOwned>(reader: &mut impl BufRead) -> io::Result<Option<T>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames() {
+        let mut buffer = vec![];
+        write_frame(&mut buffer, &Response::Stdout { size: 5 }, b"hello").unwrap();
+        write_frame(&mut buffer, &Response::Exit { code: 2 }, &[]).unwrap();
+        assert!(buffer.starts_with(b"{\"type\":\"stdout\",\"size\":5}\nhello"));
+
+        let mut reader = io::BufReader::new(buffer.as_slice());
+        let frame = read_frame::<Response>(&mut reader).unwrap();
+        assert_eq!(frame, Some(Response::Stdout { size: 5 }));
+        let mut payload = [0; 5];
+        io::Read::read_exact(&mut reader, &mut payload).unwrap();
+        assert_eq!(&payload, b"hello");
+        let frame = read_frame::<Response>(&mut reader).unwrap();
+        assert_eq!(frame, Some(Response::Exit { code: 2 }));
+        assert_eq!(read_frame::<Response>(&mut reader).unwrap(), None);
+
+        let request =
+            serde_json::from_str::<Request>(r#"{"type":"exec","command":"uname -a","tty":null}"#)
+                .unwrap();
+        assert_eq!(
+            request,
+            Request::Exec {
+                command: Some("uname -a".into()),
+                tty: None
+            }
+        );
+    }
+}
@@ -0,0 +1,361 @@
+use std::{
+    fs,
+    io::{self, BufReader, Read, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::{
+            fs::PermissionsExt,
+            net::UnixStream,
+            process::{CommandExt, ExitStatusExt},
+        },
+    },
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    ptr,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use fireup_agent::{read_frame, write_frame, Request, Response, Tty, PORT};
+
+/// The agent is started before the init system, which did not set a PATH yet.
+const PATH: &str =
+    "/run/current-system/sw/bin:/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+const CHUNK_SIZE: usize = 32 * 1024;
+
+/// A command started by [`Request::Exec`], with its input and outputs.
+struct Spawned {
+    child: Child,
+    input: Box<dyn Write + Send>,
+    /// Outputs of the command, `true` for stderr.
+    outputs: Vec<(Box<dyn Read + Send>, bool)>,
+}
+
+fn listen(port: u32) -> io::Result<OwnedFd> {
+    unsafe {
+        let fd = libc::socket(libc::AF_VSOCK, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = OwnedFd::from_raw_fd(fd);
+        let mut addr: libc::sockaddr_vm = std::mem::zeroed();
+        addr.svm_family = libc::AF_VSOCK as libc::sa_family_t;
+        addr.svm_cid = libc::VMADDR_CID_ANY;
+        addr.svm_port = port;
+        if libc::bind(
+            fd.as_raw_fd(),
+            &addr as *const libc::sockaddr_vm as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t,
+        ) < 0
+            || libc::listen(fd.as_raw_fd(), 16) < 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(fd)
+    }
+}
+
+fn accept(listener: &OwnedFd) -> io::Result<UnixStream> {
+    let fd = unsafe {
+        libc::accept4(
+            listener.as_raw_fd(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            libc::SOCK_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // only read, write and shutdown are used on the stream, which behave the
+    // same on a vsock connection
+    Ok(unsafe { UnixStream::from_raw_fd(fd) })
+}
+
+fn error(writer: &mut impl Write, message: String) -> io::Result<()> {
+    write_frame(writer, &Response::Error { message }, &[])
+}
+
+fn read(path: &str, writer: &mut UnixStream) -> Result<()> {
+    let opened = fs::File::open(path).and_then(|file| Ok((file.metadata()?, file)));
+    let (metadata, file) = match opened {
+        Ok((metadata, _)) if metadata.is_dir() => {
+            return Ok(error(writer, format!("{} is a directory", path))?);
+        }
+        Ok(opened) => opened,
+        Err(e) => return Ok(error(writer, format!("{}: {}", path, e))?),
+    };
+
+    let size = metadata.len();
+    let file_frame = Response::File {
+        mode: metadata.permissions().mode() & 0o7777,
+        size,
+    };
+    write_frame(writer, &file_frame, &[])?;
+    let copied = io::copy(&mut file.take(size), writer)?;
+    // a file truncated meanwhile is padded, the host expects `size` bytes
+    io::copy(&mut io::repeat(0).take(size - copied), writer)?;
+    Ok(())
+}
+
+fn write(
+    path: &str,
+    file_name: &str,
+    mode: u32,
+    size: u64,
+    reader: &mut BufReader<UnixStream>,
+    writer: &mut UnixStream,
+) -> Result<()> {
+    let target = match Path::new(path).is_dir() {
+        true => Path::new(path).join(file_name),
+        false => PathBuf::from(path),
+    };
+    let mut payload = Read::take(reader, size);
+    let written = fs::File::create(&target).and_then(|mut file| {
+        io::copy(&mut payload, &mut file)?;
+        file.set_permissions(fs::Permissions::from_mode(mode))
+    });
+    // skip what was not written, the next frame follows the payload
+    io::copy(&mut payload, &mut io::sink())?;
+
+    match written {
+        Ok(()) => write_frame(writer, &Response::Written, &[])?,
+        Err(e) => error(writer, format!("{}: {}", target.display(), e))?,
+    }
+    Ok(())
+}
+
+fn login_shell() -> &'static str {
+    ["/bin/bash", "/run/current-system/sw/bin/bash"]
+        .into_iter()
+        .find(|shell| Path::new(shell).exists())
+        .unwrap_or("/bin/sh")
+}
+
+fn spawn_piped(mut command: Command) -> io::Result<Spawned> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let input = Box::new(child.stdin.take().unwrap());
+    let stdout = Box::new(child.stdout.take().unwrap());
+    let stderr = Box::new(child.stderr.take().unwrap());
+    Ok(Spawned {
+        child,
+        input,
+        outputs: vec![(stdout, false), (stderr, true)],
+    })
+}
+
+/// Starts `command` in a new session, with a pseudo terminal of the size of
+/// the one of the host as its controlling terminal.
+fn spawn_tty(mut command: Command, tty: &Tty) -> io::Result<Spawned> {
+    let (mut master, mut slave) = (0, 0);
+    let size = libc::winsize {
+        ws_row: tty.rows,
+        ws_col: tty.cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    if unsafe { libc::openpty(&mut master, &mut slave, ptr::null_mut(), ptr::null(), &size) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (master, slave) = unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+
+    command
+        .env("TERM", tty.term.as_deref().unwrap_or("xterm"))
+        .stdin(Stdio::from(slave.try_clone()?))
+        .stdout(Stdio::from(slave.try_clone()?))
+        .stderr(Stdio::from(slave));
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let child = command.spawn()?;
+
+    let output = fs::File::from(master.try_clone()?);
+    Ok(Spawned {
+        child,
+        input: Box::new(fs::File::from(master)),
+        outputs: vec![(Box::new(output), false)],
+    })
+}
+
+/// Streams `output` to the host until the command closes it.
+fn pump(
+    mut output: Box<dyn Read + Send>,
+    stderr: bool,
+    writer: &Mutex<UnixStream>,
+) -> io::Result<()> {
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        let n = match output.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            // the terminal hung up, every process attached to it is gone
+            Err(e) if e.raw_os_error() == Some(libc::EIO) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let size = n as u64;
+        let frame = match stderr {
+            true => Response::Stderr { size },
+            false => Response::Stdout { size },
+        };
+        let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+        write_frame(&mut *writer, &frame, &buffer[..n])?;
+    }
+}
+
+/// Feeds the input sent by the host to the command. Closing it is ignored
+/// on a terminal, where it would hang up the whole session.
+fn feed(mut reader: BufReader<UnixStream>, input: Box<dyn Write + Send>, tty: bool) {
+    let mut input = Some(input);
+    while let Ok(Some(request)) = read_frame::<Request>(&mut reader) {
+        match request {
+            Request::Stdin { size } => {
+                let mut payload = Read::take(&mut reader, size);
+                if let Some(writer) = input.as_mut() {
+                    if io::copy(&mut payload, writer)
+                        .and_then(|_| writer.flush())
+                        .is_err()
+                    {
+                        input = None;
+                    }
+                }
+                if io::copy(&mut payload, &mut io::sink()).is_err() {
+                    return;
+                }
+            }
+            Request::CloseStdin if !tty => input = None,
+            _ => {}
+        }
+    }
+}
+
+fn exec(
+    command: Option<String>,
+    tty: Option<Tty>,
+    reader: BufReader<UnixStream>,
+    mut writer: UnixStream,
+) -> Result<()> {
+    let mut cmd = match &command {
+        Some(command) => {
+            let mut cmd = Command::new("/bin/sh");
+            cmd.arg("-c").arg(command);
+            cmd
+        }
+        None => {
+            let mut cmd = Command::new(login_shell());
+            cmd.arg("-l");
+            cmd
+        }
+    };
+    cmd.env(
+        "PATH",
+        std::env::var("PATH").unwrap_or_else(|_| PATH.into()),
+    )
+    .env("HOME", "/root")
+    .current_dir(match Path::new("/root").is_dir() {
+        true => "/root",
+        false => "/",
+    });
+
+    let spawned = match &tty {
+        Some(tty) => spawn_tty(cmd, tty),
+        None => spawn_piped(cmd),
+    };
+    let Spawned {
+        mut child,
+        input,
+        outputs,
+    } = match spawned {
+        Ok(spawned) => spawned,
+        Err(e) => return Ok(error(&mut writer, format!("Failed to run command: {}", e))?),
+    };
+
+    let is_tty = tty.is_some();
+    thread::spawn(move || feed(reader, input, is_tty));
+
+    let writer = Arc::new(Mutex::new(writer));
+    let pumps = outputs
+        .into_iter()
+        .map(|(output, stderr)| {
+            let writer = writer.clone();
+            thread::spawn(move || pump(output, stderr, &writer))
+        })
+        .collect::<Vec<_>>();
+    for pump in pumps {
+        pump.join().ok();
+    }
+
+    let status = child.wait()?;
+    let code = status
+        .code()
+        .unwrap_or_else(|| 128 + status.signal().unwrap_or_default());
+    let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+    write_frame(&mut *writer, &Response::Exit { code }, &[])?;
+    Ok(())
+}
+
+fn handle(stream: UnixStream) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    while let Some(request) = read_frame::<Request>(&mut reader)? {
+        match request {
+            Request::Ping => write_frame(&mut writer, &Response::Booted, &[])?,
+            Request::Exec { command, tty } => return exec(command, tty, reader, writer),
+            Request::Read { path } => read(&path, &mut writer)?,
+            Request::Write {
+                path,
+                file_name,
+                mode,
+                size,
+            } => write(&path, &file_name, mode, size, &mut reader, &mut writer)?,
+            Request::Stdin { size } => {
+                // no command is running
+                io::copy(&mut Read::take(&mut reader, size), &mut io::sink())?;
+            }
+            Request::CloseStdin => {}
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    // the vsock device may still be probed this early in the boot
+    let mut attempts = 0;
+    let listener = loop {
+        match listen(PORT) {
+            Ok(listener) => break listener,
+            Err(_) if attempts < 30 => {
+                attempts += 1;
+                thread::sleep(Duration::from_secs(1));
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to listen on vsock port {}", PORT))
+            }
+        }
+    };
+
+    loop {
+        match accept(&listener) {
+            Ok(stream) => {
+                thread::spawn(move || {
+                    if let Err(e) = handle(stream) {
+                        eprintln!("fireup-agent: {:#}", e);
+                    }
+                });
+            }
+            Err(e) => eprintln!("fireup-agent: {}", e),
+        }
+    }
+}